@@ -1,45 +1,400 @@
+pub mod access;
+pub mod device;
+pub mod diagnostics;
+pub mod flat;
+pub mod ines;
 pub mod ram;
+pub mod recording;
 pub mod rom;
 
+use std::any::Any;
+use std::ops::RangeInclusive;
+
+use crate::bus::device::Device;
+use crate::bus::diagnostics::{BusEvent, Diagnostics};
 use crate::bus::ram::Ram;
-use crate::bus::rom::Rom;
+use crate::bus::rom::{Rom, RomError};
 
 /// The Bus trait defines how the CPU interacts with memory or devices.
 pub trait Bus {
-    fn read(&self, addr: u16) -> u8;
+    /// Reads a byte as the CPU does. Takes `&mut self` because reading a
+    /// memory-mapped register can change device state (e.g. PPU status
+    /// clearing vblank, ACIA status acknowledging an IRQ, controller shifts).
+    fn read(&mut self, addr: u16) -> u8;
+
+    /// Reads a byte without any side effects. Used by the disassembler and
+    /// debugger so that inspecting memory never disturbs the machine.
+    fn peek(&self, addr: u16) -> u8;
+
     fn write(&mut self, addr: u16, data: u8);
+
+    /// Returns (and clears) a bus event that asked the debugger to break.
+    /// Buses without diagnostics never request a break.
+    fn take_break_event(&mut self) -> Option<BusEvent> {
+        None
+    }
 }
 
-/// Composite Bus supporting multiple devices
+/// Handle to a device attached to a `SimpleBus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceId(usize);
+
+/// One address window of a `SimpleBus` that is routed to a device.
+#[derive(Debug, Clone)]
+pub struct Mapping {
+    range: RangeInclusive<u16>,
+    device: DeviceId,
+    mirror_size: Option<u16>,
+    priority: i32,
+}
+
+impl Mapping {
+    /// Repeats the first `size` bytes of the device across the whole window,
+    /// e.g. 2K of RAM mirrored through $0000-$1FFF.
+    pub fn mirror(&mut self, size: u16) -> &mut Self {
+        assert!(size > 0, "mirror size must be non-zero");
+        self.mirror_size = Some(size);
+        self
+    }
+
+    /// Sets the priority used when windows overlap. The highest priority wins;
+    /// on a tie the mapping added last wins. The default priority is 0.
+    pub fn priority(&mut self, priority: i32) -> &mut Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Translates a bus address inside this window into a device offset.
+    fn offset(&self, addr: u16) -> u16 {
+        let offset = addr - self.range.start();
+        match self.mirror_size {
+            Some(size) => offset % size,
+            None => offset,
+        }
+    }
+}
+
+/// Composite Bus supporting multiple devices.
+///
+/// Works as a general address decoder: devices implementing `Device` are
+/// attached to address windows. Windows may mirror a device and may overlap,
+/// in which case the priority decides which device answers. Addresses not
+/// covered by any window are open bus: reads return the last value that was
+/// on the data bus and writes are ignored.
+///
+/// Unusual accesses (ROM writes, unmapped or open-bus accesses) are reported
+/// to `diagnostics` as `BusEvent`s instead of being printed.
 pub struct SimpleBus {
-    pub ram: Ram,
-    pub rom: Rom,
+    devices: Vec<Box<dyn Device>>,
+    mappings: Vec<Mapping>,
+
+    /// Last value driven on the data bus (returned for unmapped reads).
+    open_bus: u8,
+
+    /// Event sink and per-event policies.
+    pub diagnostics: Diagnostics,
 }
 
 impl SimpleBus {
-    pub fn new(ram_size: usize, rom_data: &[u8]) -> Self {
+    /// Creates the default layout: RAM at $0000-$7FFF (mirrored if smaller
+    /// than 32K) and a 32K ROM at $8000-$FFFF. Fails if `rom_data` isn't
+    /// 32K, since the vectors at $FFFA-$FFFF would be unreadable; use
+    /// `with_rom` with `Rom::mirrored` for smaller images. A `ram_size` of 0
+    /// leaves $0000-$7FFF unmapped (open bus).
+    pub fn new(ram_size: usize, rom_data: &[u8]) -> Result<Self, RomError> {
+        Ok(Self::with_rom(ram_size, Rom::with_base(rom_data, 0x8000)?))
+    }
+
+    /// Creates a bus with `rom` mapped from its base address up to $FFFF and
+    /// RAM filling the space below it (mirrored if smaller than that space).
+    /// Use `Rom::with_base` or `Rom::mirrored` to get a validated ROM.
+    /// A `ram_size` of 0 leaves the space below the ROM unmapped.
+    pub fn with_rom(ram_size: usize, rom: Rom) -> Self {
+        let mut bus = Self::empty();
+        let base_addr = rom.base_addr();
+
+        if base_addr > 0 && ram_size > 0 {
+            let ram = bus.add_device(Ram::new(ram_size));
+            let mapping = bus.map(0x0000..=base_addr - 1, ram);
+            if ram_size < base_addr as usize {
+                mapping.mirror(ram_size as u16);
+            }
+        }
+
+        bus.attach(base_addr..=0xFFFF, rom);
+        bus
+    }
+
+    /// Creates a bus with no devices; every address is open bus.
+    pub fn empty() -> Self {
         Self {
-            ram: Ram::new(ram_size),
-            rom: Rom::new(rom_data),
+            devices: Vec::new(),
+            mappings: Vec::new(),
+            open_bus: 0,
+            diagnostics: Diagnostics::default(),
         }
     }
+
+    /// Adds a device to the bus without mapping it yet.
+    pub fn add_device(&mut self, device: impl Device) -> DeviceId {
+        self.devices.push(Box::new(device));
+        DeviceId(self.devices.len() - 1)
+    }
+
+    /// Routes an address window to a device. A device can be mapped into
+    /// several windows. Returns the mapping so mirroring and priority can be set.
+    pub fn map(&mut self, range: RangeInclusive<u16>, device: DeviceId) -> &mut Mapping {
+        assert!(device.0 < self.devices.len(), "unknown device {:?}", device);
+        self.mappings.push(Mapping {
+            range,
+            device,
+            mirror_size: None,
+            priority: 0,
+        });
+        self.mappings.last_mut().unwrap()
+    }
+
+    /// Adds a device and maps it into a single window in one go.
+    pub fn attach(&mut self, range: RangeInclusive<u16>, device: impl Device) -> DeviceId {
+        let id = self.add_device(device);
+        self.map(range, id);
+        id
+    }
+
+    /// Returns a device by handle, if it is of type `T`.
+    pub fn device<T: Device>(&self, id: DeviceId) -> Option<&T> {
+        let device: &dyn Any = self.devices.get(id.0)?.as_ref();
+        device.downcast_ref()
+    }
+
+    /// Returns a device mutably by handle, if it is of type `T`.
+    pub fn device_mut<T: Device>(&mut self, id: DeviceId) -> Option<&mut T> {
+        let device: &mut dyn Any = self.devices.get_mut(id.0)?.as_mut();
+        device.downcast_mut()
+    }
+
+    /// Finds the mapping that answers for `addr`, honoring priorities.
+    fn decode(&self, addr: u16) -> Option<(DeviceId, u16)> {
+        self.mappings
+            .iter()
+            .filter(|m| m.range.contains(&addr))
+            .max_by_key(|m| m.priority)
+            .map(|m| (m.device, m.offset(addr)))
+    }
 }
 
 impl Bus for SimpleBus {
-    fn read(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x7FFF => self.ram.read(addr),
-            0x8000..=0xFFFF => self.rom.read(addr),
+    fn read(&mut self, addr: u16) -> u8 {
+        match self.decode(addr) {
+            Some((id, offset)) => match self.devices[id.0].read(offset) {
+                Some(value) => self.open_bus = value,
+                None => self.diagnostics.report(BusEvent::OpenBusRead { addr }),
+            },
+            None => self.diagnostics.report(BusEvent::UnmappedRead { addr }),
         }
+        self.open_bus
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.decode(addr)
+            .and_then(|(id, offset)| self.devices[id.0].peek(offset))
+            .unwrap_or(self.open_bus)
     }
 
     fn write(&mut self, addr: u16, data: u8) {
-        match addr {
-            0x0000..=0x7FFF => self.ram.write(addr, data),
-            0x8000..=0xFFFF => {
-                // Writes to ROM are ignored
-                eprintln!("Warning: write to ROM at {:#06X} ignored", addr);
+        self.open_bus = data;
+        match self.decode(addr) {
+            Some((id, offset)) => {
+                let device = &mut self.devices[id.0];
+                if device.is_read_only() {
+                    self.diagnostics
+                        .report(BusEvent::WriteToRom { addr, value: data });
+                } else {
+                    device.write(offset, data);
+                }
             }
+            None => self
+                .diagnostics
+                .report(BusEvent::UnmappedWrite { addr, value: data }),
+        }
+    }
+
+    fn take_break_event(&mut self) -> Option<BusEvent> {
+        self.diagnostics.take_break()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A register that counts how often it was read, to tell `read` from `peek`.
+    struct Counter {
+        reads: u8,
+    }
+
+    impl Device for Counter {
+        fn read(&mut self, _offset: u16) -> Option<u8> {
+            self.reads += 1;
+            Some(self.reads)
+        }
+
+        fn peek(&self, _offset: u16) -> Option<u8> {
+            Some(self.reads)
         }
+
+        fn write(&mut self, _offset: u16, _data: u8) {}
+    }
+
+    fn rom_image() -> Vec<u8> {
+        (0..0x8000).map(|i| (i >> 8) as u8).collect()
+    }
+
+    #[test]
+    fn ram_is_mirrored_through_its_window() {
+        let mut bus = SimpleBus::new(0x800, &rom_image()).unwrap();
+
+        bus.write(0x0012, 0xAB);
+        assert_eq!(bus.read(0x0812), 0xAB);
+        assert_eq!(bus.read(0x7812), 0xAB);
+
+        bus.write(0x1FFF, 0xCD);
+        assert_eq!(bus.peek(0x07FF), 0xCD);
+        assert_eq!(bus.diagnostics.events().count(), 0);
+    }
+
+    #[test]
+    fn unmirrored_ram_smaller_than_its_window_is_open_bus() {
+        let mut bus = SimpleBus::empty();
+        bus.attach(0x0000..=0x0FFF, Ram::new(0x100));
+
+        bus.write(0x0010, 0x42);
+        assert_eq!(bus.read(0x0010), 0x42);
+
+        // Past the end of the RAM: the write is dropped and the read
+        // returns whatever was last on the data bus.
+        bus.write(0x0200, 0x99);
+        assert_eq!(bus.read(0x0200), 0x99);
+        bus.write(0x0010, 0x42);
+        assert_eq!(bus.read(0x0200), 0x42);
+        assert_eq!(
+            bus.diagnostics.drain(),
+            vec![
+                BusEvent::OpenBusRead { addr: 0x0200 },
+                BusEvent::OpenBusRead { addr: 0x0200 },
+            ]
+        );
+    }
+
+    #[test]
+    fn default_layout_needs_a_32k_rom() {
+        assert!(matches!(
+            SimpleBus::new(0x800, &[0xEA; 0x4000]),
+            Err(RomError::VectorsNotCovered {
+                size: 0x4000,
+                base_addr: 0x8000
+            })
+        ));
+        assert!(matches!(SimpleBus::new(0x800, &[]), Err(RomError::Empty)));
+    }
+
+    #[test]
+    fn zero_sized_ram_leaves_the_space_unmapped() {
+        let mut bus = SimpleBus::new(0, &rom_image()).unwrap();
+        bus.write(0x0000, 0x12);
+        assert_eq!(bus.read(0x0000), 0x12);
+        assert_eq!(bus.read(0x8100), 0x01);
+
+        let rom = Rom::with_base(&[0xEA; 0x4000], 0xC000).unwrap();
+        let mut bus = SimpleBus::with_rom(0, rom);
+        assert_eq!(bus.read(0xC000), 0xEA);
+        assert_eq!(bus.read(0x1234), 0xEA);
+        assert_eq!(
+            bus.diagnostics.drain(),
+            vec![BusEvent::UnmappedRead { addr: 0x1234 }]
+        );
+    }
+
+    #[test]
+    fn highest_priority_mapping_wins() {
+        let mut bus = SimpleBus::empty();
+        let low = bus.add_device(Ram::new(0x100));
+        let high = bus.add_device(Ram::new(0x100));
+        bus.map(0x0000..=0x00FF, high).priority(1);
+        bus.map(0x0000..=0x00FF, low);
+
+        bus.write(0x0040, 0x55);
+        assert_eq!(bus.device::<Ram>(high).unwrap().read(0x40), 0x55);
+        assert_eq!(bus.device::<Ram>(low).unwrap().read(0x40), 0x00);
+    }
+
+    #[test]
+    fn last_mapping_wins_a_priority_tie() {
+        let mut bus = SimpleBus::empty();
+        let first = bus.attach(0x0000..=0x00FF, Ram::new(0x100));
+        let second = bus.attach(0x0080..=0x00FF, Ram::new(0x80));
+
+        bus.write(0x0010, 0x11);
+        bus.write(0x0090, 0x22);
+        assert_eq!(bus.device::<Ram>(first).unwrap().read(0x10), 0x11);
+        assert_eq!(bus.device::<Ram>(first).unwrap().read(0x90), 0x00);
+        assert_eq!(bus.device::<Ram>(second).unwrap().read(0x10), 0x22);
+    }
+
+    #[test]
+    fn unmapped_reads_return_the_open_bus_value() {
+        let mut bus = SimpleBus::empty();
+        bus.attach(0x0000..=0x00FF, Ram::new(0x100));
+
+        assert_eq!(bus.read(0x4000), 0x00);
+        bus.write(0x0001, 0x7E);
+        assert_eq!(bus.read(0x4000), 0x7E);
+        assert_eq!(bus.peek(0x4000), 0x7E);
+
+        bus.write(0x5000, 0x33);
+        assert_eq!(bus.read(0x4000), 0x33);
+        assert_eq!(
+            bus.diagnostics.drain(),
+            vec![
+                BusEvent::UnmappedRead { addr: 0x4000 },
+                BusEvent::UnmappedRead { addr: 0x4000 },
+                BusEvent::UnmappedWrite {
+                    addr: 0x5000,
+                    value: 0x33
+                },
+                BusEvent::UnmappedRead { addr: 0x4000 },
+            ]
+        );
+    }
+
+    #[test]
+    fn rom_ignores_writes() {
+        let mut bus = SimpleBus::new(0x800, &rom_image()).unwrap();
+
+        bus.write(0x9000, 0x00);
+        assert_eq!(bus.read(0x9000), 0x10);
+        assert_eq!(
+            bus.diagnostics.drain(),
+            vec![BusEvent::WriteToRom {
+                addr: 0x9000,
+                value: 0x00
+            }]
+        );
+    }
+
+    #[test]
+    fn peek_has_no_side_effects() {
+        let mut bus = SimpleBus::empty();
+        let counter = bus.attach(0x2000..=0x2000, Counter { reads: 0 });
+
+        assert_eq!(bus.peek(0x2000), 0);
+        assert_eq!(bus.peek(0x2000), 0);
+        assert_eq!(bus.peek(0x3000), 0);
+        assert_eq!(bus.device::<Counter>(counter).unwrap().reads, 0);
+        assert_eq!(bus.diagnostics.events().count(), 0);
+
+        assert_eq!(bus.read(0x2000), 1);
+        assert_eq!(bus.peek(0x2000), 1);
+        assert_eq!(bus.device::<Counter>(counter).unwrap().reads, 1);
     }
 }
@@ -0,0 +1,28 @@
+use std::any::Any;
+
+/// A memory-mapped device that can be attached to a `SimpleBus`.
+///
+/// Devices never see absolute CPU addresses: the bus translates every access
+/// into an `offset` from the start of the device's mapping (after mirroring),
+/// so the same device can be placed anywhere in the address space.
+pub trait Device: Any {
+    /// Reads a byte as the CPU does; may change device state.
+    /// Defaults to `peek` for devices without read side effects.
+    /// `None` means the device does not drive the data bus at this offset,
+    /// and the bus answers with its open-bus value instead.
+    fn read(&mut self, offset: u16) -> Option<u8> {
+        self.peek(offset)
+    }
+
+    /// Reads a byte without side effects (debugger/disassembler view).
+    fn peek(&self, offset: u16) -> Option<u8>;
+
+    /// Writes a byte to the device.
+    fn write(&mut self, offset: u16, data: u8);
+
+    /// Returns true if the device ignores writes (ROM). The bus reports
+    /// writes to such devices as `BusEvent::WriteToRom`.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
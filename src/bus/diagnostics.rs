@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Something noteworthy that happened on the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusEvent {
+    /// A write hit a read-only device. The write was ignored.
+    WriteToRom { addr: u16, value: u8 },
+
+    /// A read hit an address with no device mapped; the open-bus value was returned.
+    UnmappedRead { addr: u16 },
+
+    /// A write hit an address with no device mapped. The write was ignored.
+    UnmappedWrite { addr: u16, value: u8 },
+
+    /// A mapped device did not drive the data bus (e.g. a read past the end
+    /// of a ROM image); the open-bus value was returned.
+    OpenBusRead { addr: u16 },
+}
+
+/// The kind of a `BusEvent`, used to configure policies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BusEventKind {
+    WriteToRom,
+    UnmappedRead,
+    UnmappedWrite,
+    OpenBusRead,
+}
+
+impl BusEvent {
+    pub fn kind(&self) -> BusEventKind {
+        match self {
+            BusEvent::WriteToRom { .. } => BusEventKind::WriteToRom,
+            BusEvent::UnmappedRead { .. } => BusEventKind::UnmappedRead,
+            BusEvent::UnmappedWrite { .. } => BusEventKind::UnmappedWrite,
+            BusEvent::OpenBusRead { .. } => BusEventKind::OpenBusRead,
+        }
+    }
+}
+
+impl fmt::Display for BusEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BusEvent::WriteToRom { addr, value } => {
+                write!(f, "write ${:02X} to ROM at ${:04X}", value, addr)
+            }
+            BusEvent::UnmappedRead { addr } => write!(f, "read from unmapped ${:04X}", addr),
+            BusEvent::UnmappedWrite { addr, value } => {
+                write!(f, "write ${:02X} to unmapped ${:04X}", value, addr)
+            }
+            BusEvent::OpenBusRead { addr } => write!(f, "open bus read at ${:04X}", addr),
+        }
+    }
+}
+
+/// What to do when a bus event of a given kind happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPolicy {
+    /// Drop the event.
+    Ignore,
+
+    /// Deliver the event to the callback, or the ring buffer if no callback is set.
+    Log,
+
+    /// Like `Log`, and additionally ask the debugger to stop after the current instruction.
+    Break,
+}
+
+/// User callback receiving logged events.
+pub type EventCallback = Box<dyn FnMut(&BusEvent)>;
+
+/// Default number of events kept in the ring buffer.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Collects bus events according to per-kind policies.
+///
+/// Events are delivered to a user-registered callback if there is one,
+/// otherwise they are kept in a bounded ring buffer (oldest dropped first).
+pub struct Diagnostics {
+    write_to_rom: EventPolicy,
+    unmapped_read: EventPolicy,
+    unmapped_write: EventPolicy,
+    open_bus_read: EventPolicy,
+
+    log: VecDeque<BusEvent>,
+    capacity: usize,
+    callback: Option<EventCallback>,
+
+    /// First event with the `Break` policy since the last `take_break`.
+    break_event: Option<BusEvent>,
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Diagnostics {
+    /// Creates a sink that logs every event kind into a ring buffer of `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            write_to_rom: EventPolicy::Log,
+            unmapped_read: EventPolicy::Log,
+            unmapped_write: EventPolicy::Log,
+            open_bus_read: EventPolicy::Log,
+            log: VecDeque::with_capacity(capacity),
+            capacity,
+            callback: None,
+            break_event: None,
+        }
+    }
+
+    /// Returns the policy for an event kind.
+    pub fn policy(&self, kind: BusEventKind) -> EventPolicy {
+        match kind {
+            BusEventKind::WriteToRom => self.write_to_rom,
+            BusEventKind::UnmappedRead => self.unmapped_read,
+            BusEventKind::UnmappedWrite => self.unmapped_write,
+            BusEventKind::OpenBusRead => self.open_bus_read,
+        }
+    }
+
+    /// Sets the policy for an event kind.
+    pub fn set_policy(&mut self, kind: BusEventKind, policy: EventPolicy) {
+        match kind {
+            BusEventKind::WriteToRom => self.write_to_rom = policy,
+            BusEventKind::UnmappedRead => self.unmapped_read = policy,
+            BusEventKind::UnmappedWrite => self.unmapped_write = policy,
+            BusEventKind::OpenBusRead => self.open_bus_read = policy,
+        }
+    }
+
+    /// Delivers logged events to `callback` instead of the ring buffer.
+    pub fn set_callback(&mut self, callback: impl FnMut(&BusEvent) + 'static) {
+        self.callback = Some(Box::new(callback));
+    }
+
+    /// Removes the callback; events go to the ring buffer again.
+    pub fn clear_callback(&mut self) {
+        self.callback = None;
+    }
+
+    /// Events currently held in the ring buffer, oldest first.
+    pub fn events(&self) -> impl Iterator<Item = &BusEvent> {
+        self.log.iter()
+    }
+
+    /// Removes and returns all events held in the ring buffer.
+    pub fn drain(&mut self) -> Vec<BusEvent> {
+        self.log.drain(..).collect()
+    }
+
+    /// Returns (and clears) the event that requested a break, if any.
+    pub fn take_break(&mut self) -> Option<BusEvent> {
+        self.break_event.take()
+    }
+
+    /// Records an event according to its policy.
+    pub fn report(&mut self, event: BusEvent) {
+        let policy = self.policy(event.kind());
+        if policy == EventPolicy::Ignore {
+            return;
+        }
+
+        if let Some(callback) = self.callback.as_mut() {
+            callback(&event);
+        } else if self.capacity > 0 {
+            if self.log.len() == self.capacity {
+                self.log.pop_front();
+            }
+            self.log.push_back(event);
+        }
+
+        if policy == EventPolicy::Break && self.break_event.is_none() {
+            self.break_event = Some(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::bus::{Bus, SimpleBus};
+
+    /// 2K of RAM mirrored below $8000 and a blank ROM above.
+    fn bus() -> SimpleBus {
+        SimpleBus::new(0x800, &[0xEA; 0x8000]).unwrap()
+    }
+
+    #[test]
+    fn every_kind_is_logged_by_default() {
+        let mut bus = SimpleBus::empty();
+        bus.read(0x1234);
+        bus.write(0x1234, 0x56);
+
+        assert_eq!(
+            bus.diagnostics.events().copied().collect::<Vec<_>>(),
+            vec![
+                BusEvent::UnmappedRead { addr: 0x1234 },
+                BusEvent::UnmappedWrite {
+                    addr: 0x1234,
+                    value: 0x56
+                },
+            ]
+        );
+        assert_eq!(bus.take_break_event(), None);
+    }
+
+    #[test]
+    fn ignored_events_are_dropped() {
+        let mut bus = bus();
+        bus.diagnostics
+            .set_policy(BusEventKind::WriteToRom, EventPolicy::Ignore);
+
+        bus.write(0x8000, 0x00);
+        assert_eq!(bus.diagnostics.drain(), vec![]);
+        assert_eq!(bus.take_break_event(), None);
+    }
+
+    #[test]
+    fn break_policy_logs_and_requests_a_break_once() {
+        let mut bus = SimpleBus::empty();
+        bus.diagnostics
+            .set_policy(BusEventKind::UnmappedRead, EventPolicy::Break);
+
+        bus.read(0x0010);
+        bus.read(0x0020);
+        assert_eq!(bus.diagnostics.drain().len(), 2);
+
+        // Only the first event is kept until the break is taken.
+        assert_eq!(
+            bus.take_break_event(),
+            Some(BusEvent::UnmappedRead { addr: 0x0010 })
+        );
+        assert_eq!(bus.take_break_event(), None);
+
+        bus.read(0x0030);
+        assert_eq!(
+            bus.take_break_event(),
+            Some(BusEvent::UnmappedRead { addr: 0x0030 })
+        );
+    }
+
+    #[test]
+    fn break_policy_only_applies_to_its_kind() {
+        let mut bus = bus();
+        bus.diagnostics
+            .set_policy(BusEventKind::WriteToRom, EventPolicy::Break);
+
+        bus.write(0x0000, 0x01);
+        assert_eq!(bus.take_break_event(), None);
+
+        bus.write(0xC000, 0x02);
+        assert_eq!(
+            bus.take_break_event(),
+            Some(BusEvent::WriteToRom {
+                addr: 0xC000,
+                value: 0x02
+            })
+        );
+    }
+
+    #[test]
+    fn ring_buffer_drops_the_oldest_events() {
+        let mut bus = SimpleBus::empty();
+        bus.diagnostics = Diagnostics::new(3);
+
+        for addr in 0..5 {
+            bus.read(addr);
+        }
+        assert_eq!(
+            bus.diagnostics.drain(),
+            vec![
+                BusEvent::UnmappedRead { addr: 2 },
+                BusEvent::UnmappedRead { addr: 3 },
+                BusEvent::UnmappedRead { addr: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_capacity_still_breaks() {
+        let mut bus = SimpleBus::empty();
+        bus.diagnostics = Diagnostics::new(0);
+        bus.diagnostics
+            .set_policy(BusEventKind::UnmappedWrite, EventPolicy::Break);
+
+        bus.write(0x0001, 0xFF);
+        assert_eq!(bus.diagnostics.events().count(), 0);
+        assert_eq!(
+            bus.take_break_event(),
+            Some(BusEvent::UnmappedWrite {
+                addr: 0x0001,
+                value: 0xFF
+            })
+        );
+    }
+
+    #[test]
+    fn callback_replaces_the_ring_buffer() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = bus();
+        let sink = Rc::clone(&seen);
+        bus.diagnostics
+            .set_callback(move |event| sink.borrow_mut().push(*event));
+
+        bus.write(0x9000, 0x42);
+        assert_eq!(
+            *seen.borrow(),
+            vec![BusEvent::WriteToRom {
+                addr: 0x9000,
+                value: 0x42
+            }]
+        );
+        assert_eq!(bus.diagnostics.events().count(), 0);
+
+        bus.diagnostics.clear_callback();
+        bus.write(0x9001, 0x43);
+        assert_eq!(seen.borrow().len(), 1);
+        assert_eq!(bus.diagnostics.events().count(), 1);
+    }
+}
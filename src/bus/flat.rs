@@ -0,0 +1,66 @@
+use crate::bus::Bus;
+
+const MEMORY_SIZE: usize = 0x10000;
+
+/// 64K of plain RAM covering the whole address space, for test programs.
+///
+/// No devices, mirroring or diagnostics: every address reads back what was
+/// last written to it, and memory starts out zeroed.
+#[derive(Clone)]
+pub struct FlatBus {
+    mem: Vec<u8>,
+}
+
+impl Default for FlatBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlatBus {
+    pub fn new() -> Self {
+        Self {
+            mem: vec![0; MEMORY_SIZE],
+        }
+    }
+
+    /// A bus with `image` loaded at `addr`, e.g. a full 64K test binary at $0000
+    pub fn with_image(image: &[u8], addr: u16) -> Self {
+        let mut bus = Self::new();
+        bus.load(addr, image);
+        bus
+    }
+
+    /// Copies `data` into memory at `addr`
+    pub fn load(&mut self, addr: u16, data: &[u8]) {
+        let start = addr as usize;
+        let end = start + data.len();
+        if end > MEMORY_SIZE {
+            panic!("Attempt to load past $FFFF");
+        }
+        self.mem[start..end].copy_from_slice(data);
+    }
+
+    /// The whole address space
+    pub fn memory(&self) -> &[u8] {
+        &self.mem
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.mem
+    }
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.mem[addr as usize]
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.mem[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.mem[addr as usize] = data;
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+
+use crate::bus::SimpleBus;
+use crate::bus::device::Device;
+use crate::bus::ram::Ram;
+use crate::bus::rom::{Rom, RomError};
+
+/// First four bytes of every iNES file
+const MAGIC: &[u8; 4] = b"NES\x1A";
+
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/// Flags 6: a 512-byte trainer sits between the header and PRG ROM
+const FLAG_TRAINER: u8 = 0x04;
+
+/// Internal RAM of the NES, mirrored through $0000-$1FFF
+const INTERNAL_RAM_SIZE: u16 = 0x0800;
+
+/// PRG RAM some NROM boards have at $6000-$7FFF
+const PRG_RAM_SIZE: usize = 0x2000;
+
+/// Reasons an iNES image cannot be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InesError {
+    /// The file doesn't start with `NES<EOF>`.
+    BadMagic,
+
+    /// The file is shorter than its header says.
+    Truncated { expected: usize, actual: usize },
+
+    /// Only mapper 0 (NROM) is supported.
+    UnsupportedMapper(u8),
+
+    /// The PRG ROM cannot be mapped.
+    Rom(RomError),
+}
+
+impl fmt::Display for InesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            InesError::BadMagic => write!(f, "not an iNES image"),
+            InesError::Truncated { expected, actual } => write!(
+                f,
+                "iNES image is {} bytes, the header asks for {}",
+                actual, expected
+            ),
+            InesError::UnsupportedMapper(mapper) => {
+                write!(f, "mapper {} is not supported (only NROM)", mapper)
+            }
+            InesError::Rom(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for InesError {}
+
+/// A cartridge image in the iNES format (`.nes`).
+#[derive(Debug, Clone)]
+pub struct InesImage {
+    pub mapper: u8,
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+}
+
+impl InesImage {
+    /// True if `data` starts with the iNES magic.
+    pub fn detect(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, InesError> {
+        if !Self::detect(data) {
+            return Err(InesError::BadMagic);
+        }
+        if data.len() < HEADER_SIZE {
+            return Err(InesError::Truncated {
+                expected: HEADER_SIZE,
+                actual: data.len(),
+            });
+        }
+        let prg_size = data[4] as usize * PRG_BANK_SIZE;
+        let chr_size = data[5] as usize * CHR_BANK_SIZE;
+        let mapper = (data[7] & 0xF0) | (data[6] >> 4);
+
+        let mut start = HEADER_SIZE;
+        if data[6] & FLAG_TRAINER != 0 {
+            start += TRAINER_SIZE;
+        }
+        let expected = start + prg_size + chr_size;
+        if data.len() < expected {
+            return Err(InesError::Truncated {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        Ok(Self {
+            mapper,
+            prg_rom: data[start..start + prg_size].to_vec(),
+            chr_rom: data[start + prg_size..expected].to_vec(),
+        })
+    }
+
+    /// The CPU side of an NROM (mapper 0) board: 2K of RAM mirrored through
+    /// $0000-$1FFF, PRG RAM at $6000-$7FFF and PRG ROM at $8000, a 16K
+    /// image mirrored at $C000. The PPU and APU aren't emulated yet; their
+    /// registers ($2000-$401F) read $FF and ignore writes.
+    pub fn nrom_bus(&self) -> Result<SimpleBus, InesError> {
+        if self.mapper != 0 {
+            return Err(InesError::UnsupportedMapper(self.mapper));
+        }
+        let rom = Rom::mirrored(&self.prg_rom, 0x8000).map_err(InesError::Rom)?;
+
+        let mut bus = SimpleBus::empty();
+        let ram = bus.add_device(Ram::new(INTERNAL_RAM_SIZE as usize));
+        bus.map(0x0000..=0x1FFF, ram).mirror(INTERNAL_RAM_SIZE);
+        bus.attach(0x2000..=0x401F, Unemulated);
+        bus.attach(0x6000..=0x7FFF, Ram::new(PRG_RAM_SIZE));
+        bus.attach(0x8000..=0xFFFF, rom);
+        Ok(bus)
+    }
+}
+
+/// Stand-in for the PPU and APU registers
+struct Unemulated;
+
+impl Device for Unemulated {
+    fn peek(&self, _offset: u16) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    fn write(&mut self, _offset: u16, _data: u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Bus;
+
+    /// An image with `prg_banks` 16K PRG banks (each filled with its bank
+    /// number), one CHR bank of $CC and the given flags 6 and 7. A trainer
+    /// of $77 is inserted when flags 6 asks for one.
+    fn image(prg_banks: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend([prg_banks, 1, flags6, flags7]);
+        data.resize(HEADER_SIZE, 0);
+        if flags6 & FLAG_TRAINER != 0 {
+            data.extend([0x77; TRAINER_SIZE]);
+        }
+        for bank in 0..prg_banks {
+            data.extend(vec![bank; PRG_BANK_SIZE]);
+        }
+        data.extend(vec![0xCC; CHR_BANK_SIZE]);
+        data
+    }
+
+    #[test]
+    fn parse_splits_prg_and_chr() {
+        let image = InesImage::parse(&image(2, 0, 0)).unwrap();
+        assert_eq!(image.mapper, 0);
+        assert_eq!(image.prg_rom.len(), 2 * PRG_BANK_SIZE);
+        assert_eq!(image.prg_rom[PRG_BANK_SIZE - 1], 0);
+        assert_eq!(image.prg_rom[PRG_BANK_SIZE], 1);
+        assert_eq!(image.chr_rom, vec![0xCC; CHR_BANK_SIZE]);
+    }
+
+    #[test]
+    fn trainer_is_skipped() {
+        let image = InesImage::parse(&image(1, FLAG_TRAINER, 0)).unwrap();
+        assert_eq!(image.prg_rom, vec![0; PRG_BANK_SIZE]);
+        assert_eq!(image.chr_rom, vec![0xCC; CHR_BANK_SIZE]);
+    }
+
+    #[test]
+    fn mapper_comes_from_the_high_nibbles_of_flags_6_and_7() {
+        // Low nibble from flags 6, high nibble from flags 7; the other
+        // bits (mirroring, trainer, console type) don't leak in
+        let image = InesImage::parse(&image(1, 0x41, 0x13)).unwrap();
+        assert_eq!(image.mapper, 0x14);
+        assert_eq!(
+            image.nrom_bus().err(),
+            Some(InesError::UnsupportedMapper(0x14))
+        );
+    }
+
+    #[test]
+    fn malformed_images_are_rejected() {
+        assert_eq!(
+            InesImage::parse(b"NEZ\x1A\x01\x01").err(),
+            Some(InesError::BadMagic)
+        );
+        assert_eq!(InesImage::parse(&[]).err(), Some(InesError::BadMagic));
+
+        // The magic alone isn't a header
+        assert_eq!(
+            InesImage::parse(b"NES\x1A\x01\x01").err(),
+            Some(InesError::Truncated {
+                expected: HEADER_SIZE,
+                actual: 6
+            })
+        );
+
+        let mut data = image(1, FLAG_TRAINER, 0);
+        let full = data.len();
+        data.pop();
+        assert_eq!(
+            InesImage::parse(&data).err(),
+            Some(InesError::Truncated {
+                expected: full,
+                actual: full - 1
+            })
+        );
+    }
+
+    #[test]
+    fn nrom_bus_mirrors_16k_prg_at_c000() {
+        let mut data = image(1, 0, 0);
+        // Last byte of the bank, i.e. the high byte of the IRQ vector
+        data[HEADER_SIZE + PRG_BANK_SIZE - 1] = 0xAB;
+        let mut bus = InesImage::parse(&data).unwrap().nrom_bus().unwrap();
+        assert_eq!(bus.read(0xBFFF), 0xAB);
+        assert_eq!(bus.read(0xFFFF), 0xAB);
+        assert_eq!(bus.read(0x8000), bus.read(0xC000));
+
+        let mut bus = InesImage::parse(&image(2, 0, 0))
+            .unwrap()
+            .nrom_bus()
+            .unwrap();
+        assert_eq!(bus.read(0x8000), 0);
+        assert_eq!(bus.read(0xC000), 1);
+    }
+
+    #[test]
+    fn nrom_bus_ram_and_unemulated_registers() {
+        let mut bus = InesImage::parse(&image(1, 0, 0))
+            .unwrap()
+            .nrom_bus()
+            .unwrap();
+
+        // 2K of internal RAM repeats through $1FFF
+        bus.write(0x0012, 0x34);
+        assert_eq!(bus.read(0x0812), 0x34);
+        assert_eq!(bus.read(0x1812), 0x34);
+        bus.write(0x1FFF, 0x56);
+        assert_eq!(bus.read(0x07FF), 0x56);
+
+        // PPU and APU registers read $FF and ignore writes
+        for addr in [0x2000, 0x2002, 0x3FFF, 0x4016, 0x401F] {
+            bus.write(addr, 0x00);
+            assert_eq!(bus.read(addr), 0xFF, "${:04X}", addr);
+        }
+
+        // PRG RAM
+        bus.write(0x6000, 0x9A);
+        assert_eq!(bus.read(0x6000), 0x9A);
+        assert_eq!(bus.diagnostics.events().count(), 0);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::bus::device::Device;
+
 /// RAM memory for CPU
 pub struct Ram {
     mem: Vec<u8>,
@@ -28,3 +30,17 @@ impl Ram {
         self.mem[addr as usize] = data;
     }
 }
+
+impl Device for Ram {
+    fn peek(&self, offset: u16) -> Option<u8> {
+        self.mem.get(offset as usize).copied()
+    }
+
+    /// Writes past the end of the RAM (an unmirrored window larger than the
+    /// RAM) are dropped, just as reads there fall back to open bus.
+    fn write(&mut self, offset: u16, data: u8) {
+        if let Some(cell) = self.mem.get_mut(offset as usize) {
+            *cell = data;
+        }
+    }
+}
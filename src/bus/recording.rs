@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::bus::access::AccessKind;
+
+/// One bus access seen by a `RecordingBus`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusCycle {
+    /// Number of accesses before this one. The 6502 accesses the bus on
+    /// every cycle, so this is the cycle number when the CPU models its
+    /// dummy reads and writes.
+    pub cycle: u64,
+    pub addr: u16,
+    pub value: u8,
+    pub kind: AccessKind,
+}
+
+impl fmt::Display for BusCycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "read ",
+            AccessKind::Write => "write",
+        };
+        write!(
+            f,
+            "{:>3}: {} ${:04X} = ${:02X}",
+            self.cycle, kind, self.addr, self.value
+        )
+    }
+}
+
+/// Sparse memory backed by a `HashMap` that records every access, for CPU
+/// unit tests. Addresses never written read as `default_value` (0 unless
+/// changed).
+#[derive(Debug, Clone, Default)]
+pub struct RecordingBus {
+    memory: HashMap<u16, u8>,
+    accesses: Vec<BusCycle>,
+    cycle: u64,
+    pub default_value: u8,
+}
+
+impl RecordingBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A bus holding the given bytes
+    pub fn with_memory(bytes: impl IntoIterator<Item = (u16, u8)>) -> Self {
+        let mut bus = Self::new();
+        bus.memory.extend(bytes);
+        bus
+    }
+
+    /// Sets a byte without recording an access
+    pub fn set(&mut self, addr: u16, value: u8) {
+        self.memory.insert(addr, value);
+    }
+
+    /// Copies `data` into memory at `addr` without recording accesses
+    pub fn load(&mut self, addr: u16, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            self.memory.insert(addr.wrapping_add(i as u16), *byte);
+        }
+    }
+
+    /// Addresses that have a value, with the value
+    pub fn memory(&self) -> &HashMap<u16, u8> {
+        &self.memory
+    }
+
+    /// Accesses since the bus was created or last cleared, in order
+    pub fn accesses(&self) -> &[BusCycle] {
+        &self.accesses
+    }
+
+    /// Hands out the recorded accesses and starts counting cycles from 0
+    pub fn take_accesses(&mut self) -> Vec<BusCycle> {
+        self.cycle = 0;
+        std::mem::take(&mut self.accesses)
+    }
+
+    pub fn clear_accesses(&mut self) {
+        self.take_accesses();
+    }
+
+    /// Compares the recorded accesses with `expected` (address, value,
+    /// direction). On a mismatch the error lists both sequences, marking
+    /// the first difference.
+    pub fn check_accesses(&self, expected: &[(u16, u8, AccessKind)]) -> Result<(), String> {
+        let actual: Vec<(u16, u8, AccessKind)> = self
+            .accesses
+            .iter()
+            .map(|access| (access.addr, access.value, access.kind))
+            .collect();
+        let Some(first) =
+            (0..expected.len().max(actual.len())).find(|&i| expected.get(i) != actual.get(i))
+        else {
+            return Ok(());
+        };
+
+        let describe = |list: &[(u16, u8, AccessKind)]| {
+            let lines: Vec<String> = list
+                .iter()
+                .enumerate()
+                .map(|(cycle, &(addr, value, kind))| {
+                    let marker = if cycle == first { ">" } else { " " };
+                    let access = BusCycle {
+                        cycle: cycle as u64,
+                        addr,
+                        value,
+                        kind,
+                    };
+                    format!("  {}{}", marker, access)
+                })
+                .collect();
+            lines.join("\n")
+        };
+        Err(format!(
+            "bus accesses differ at cycle {}\nexpected:\n{}\nactual:\n{}",
+            first,
+            describe(expected),
+            describe(&actual)
+        ))
+    }
+
+    /// Panics unless the recorded accesses are exactly `expected`
+    #[track_caller]
+    pub fn assert_accesses(&self, expected: &[(u16, u8, AccessKind)]) {
+        if let Err(message) = self.check_accesses(expected) {
+            panic!("{}", message);
+        }
+    }
+
+    fn record(&mut self, addr: u16, value: u8, kind: AccessKind) {
+        self.accesses.push(BusCycle {
+            cycle: self.cycle,
+            addr,
+            value,
+            kind,
+        });
+        self.cycle += 1;
+    }
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.peek(addr);
+        self.record(addr, value, AccessKind::Read);
+        value
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory
+            .get(&addr)
+            .copied()
+            .unwrap_or(self.default_value)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.memory.insert(addr, data);
+        self.record(addr, data, AccessKind::Write);
+    }
+}
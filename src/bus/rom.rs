@@ -1,23 +1,231 @@
+use std::fmt;
+
+use crate::bus::device::Device;
+
+/// Reasons a ROM image cannot be placed at the requested base address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomError {
+    /// The image contains no data.
+    Empty,
+
+    /// The image does not fit between `base_addr` and $FFFF.
+    TooLarge { size: usize, base_addr: u16 },
+
+    /// The image ends before $FFFA, so the NMI/RESET/IRQ vectors would be
+    /// unmapped, or (when mirrored) the window is not a whole number of
+    /// copies, so the vectors would not come from the end of the image.
+    VectorsNotCovered { size: usize, base_addr: u16 },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RomError::Empty => write!(f, "ROM image is empty"),
+            RomError::TooLarge { size, base_addr } => write!(
+                f,
+                "ROM of {} bytes does not fit between ${:04X} and $FFFF",
+                size, base_addr
+            ),
+            RomError::VectorsNotCovered { size, base_addr } => write!(
+                f,
+                "ROM of {} bytes at ${:04X} does not cover the vectors at $FFFA-$FFFF",
+                size, base_addr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
 /// Read-only memory (ROM) for CPU
 pub struct Rom {
     mem: Vec<u8>,
     base_addr: u16, // address where ROM is mapped
+    mirrored: bool, // repeat the image to fill base_addr..=$FFFF
 }
 
 impl Rom {
-    pub fn new(data: &[u8]) -> Self {
-        Self {
+    /// Creates a ROM mapped at `base_addr` that must fill the window up to
+    /// $FFFF exactly, so the interrupt vectors are always readable.
+    pub fn with_base(data: &[u8], base_addr: u16) -> Result<Self, RomError> {
+        let window = Self::window_size(base_addr);
+        if data.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if data.len() > window {
+            return Err(RomError::TooLarge {
+                size: data.len(),
+                base_addr,
+            });
+        }
+        // The window always ends at $FFFF, so a shorter image leaves
+        // (some of) the vectors at $FFFA-$FFFF unmapped.
+        if data.len() < window {
+            return Err(RomError::VectorsNotCovered {
+                size: data.len(),
+                base_addr,
+            });
+        }
+
+        Ok(Self {
             mem: data.to_vec(),
-            base_addr: 0x8000, // default start address
+            base_addr,
+            mirrored: false,
+        })
+    }
+
+    /// Creates a ROM mapped at `base_addr` that repeats itself to fill the
+    /// whole window up to $FFFF, like 16K NES PRG ROM appearing at both
+    /// $8000 and $C000. The image size must divide the window, so the last
+    /// copy ends exactly at $FFFF and the vectors are the image's last 6 bytes.
+    pub fn mirrored(data: &[u8], base_addr: u16) -> Result<Self, RomError> {
+        let window = Self::window_size(base_addr);
+        if data.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if data.len() > window {
+            return Err(RomError::TooLarge {
+                size: data.len(),
+                base_addr,
+            });
         }
+        // E.g. a 12K image at $8000 would map $FFFA to image offset $1FFA.
+        if !window.is_multiple_of(data.len()) {
+            return Err(RomError::VectorsNotCovered {
+                size: data.len(),
+                base_addr,
+            });
+        }
+
+        Ok(Self {
+            mem: data.to_vec(),
+            base_addr,
+            mirrored: true,
+        })
+    }
+
+    /// Number of bytes between `base_addr` and $FFFF (inclusive).
+    fn window_size(base_addr: u16) -> usize {
+        0x10000 - base_addr as usize
+    }
+
+    /// Address where the ROM starts.
+    pub fn base_addr(&self) -> u16 {
+        self.base_addr
+    }
+
+    /// Size of the ROM image in bytes.
+    pub fn len(&self) -> usize {
+        self.mem.len()
     }
 
+    /// Returns true if the ROM image is empty.
+    pub fn is_empty(&self) -> bool {
+        self.mem.is_empty()
+    }
+
+    /// Reads the byte mapped at `addr`. Addresses outside the image return
+    /// $FF (open bus); use `SimpleBus` diagnostics to find out about them.
     pub fn read(&self, addr: u16) -> u8 {
         let offset = addr.wrapping_sub(self.base_addr) as usize;
-        if offset >= self.mem.len() {
-            eprintln!("Warning: read beyond ROM at {:#06X}", addr);
-            return 0xFF; // Open bus behavior
-        }
-        self.mem[offset]
+        self.read_offset(offset).unwrap_or(0xFF)
+    }
+
+    /// Reads relative to the start of the ROM, applying mirroring.
+    /// Returns `None` past the end of the image.
+    fn read_offset(&self, offset: usize) -> Option<u8> {
+        let offset = if self.mirrored && !self.mem.is_empty() {
+            offset % self.mem.len()
+        } else {
+            offset
+        };
+        self.mem.get(offset).copied()
+    }
+}
+
+impl Device for Rom {
+    fn peek(&self, offset: u16) -> Option<u8> {
+        self.read_offset(offset as usize)
+    }
+
+    fn write(&mut self, _offset: u16, _data: u8) {
+        // Writes to ROM are ignored
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image whose bytes encode their own offset's high byte.
+    fn image(size: usize) -> Vec<u8> {
+        (0..size).map(|i| (i >> 8) as u8).collect()
+    }
+
+    #[test]
+    fn with_base_requires_an_exact_fill() {
+        let rom = Rom::with_base(&image(0x4000), 0xC000).unwrap();
+        assert_eq!(rom.base_addr(), 0xC000);
+        assert_eq!(rom.read(0xC000), 0x00);
+        assert_eq!(rom.read(0xFFFF), 0x3F);
+
+        assert_eq!(
+            Rom::with_base(&image(0x2000), 0xC000).err(),
+            Some(RomError::VectorsNotCovered {
+                size: 0x2000,
+                base_addr: 0xC000
+            })
+        );
+    }
+
+    #[test]
+    fn with_base_rejects_too_large_and_empty_images() {
+        assert_eq!(
+            Rom::with_base(&image(0x4001), 0xC000).err(),
+            Some(RomError::TooLarge {
+                size: 0x4001,
+                base_addr: 0xC000
+            })
+        );
+        assert_eq!(Rom::with_base(&[], 0xC000).err(), Some(RomError::Empty));
+    }
+
+    #[test]
+    fn mirrored_16k_image_appears_twice_at_8000() {
+        let mut data = image(0x4000);
+        data[0x3FFC] = 0x34;
+        data[0x3FFD] = 0x12;
+        let rom = Rom::mirrored(&data, 0x8000).unwrap();
+
+        assert_eq!(rom.read(0x8000), 0x00);
+        assert_eq!(rom.read(0xC000), 0x00);
+        assert_eq!(rom.read(0x9234), 0x12);
+        assert_eq!(rom.read(0xD234), 0x12);
+        assert_eq!(rom.read(0xBFFC), 0x34);
+        assert_eq!(rom.read(0xFFFC), 0x34);
+        assert_eq!(rom.read(0xFFFD), 0x12);
+    }
+
+    #[test]
+    fn mirrored_rejects_sizes_that_do_not_divide_the_window() {
+        assert_eq!(
+            Rom::mirrored(&image(0x3000), 0x8000).err(),
+            Some(RomError::VectorsNotCovered {
+                size: 0x3000,
+                base_addr: 0x8000
+            })
+        );
+        assert_eq!(
+            Rom::mirrored(&image(0x8001), 0x8000).err(),
+            Some(RomError::TooLarge {
+                size: 0x8001,
+                base_addr: 0x8000
+            })
+        );
+        assert_eq!(Rom::mirrored(&[], 0x8000).err(), Some(RomError::Empty));
     }
 }
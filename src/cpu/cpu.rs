@@ -1,18 +1,50 @@
+use std::fmt;
+
 use crate::bus::Bus;
 
-const NMI_VECTOR: u16 = 0xfffa;
-const RESET_VECTOR: u16 = 0xfffc;
-const IRQ_VECTOR: u16 = 0xfffe;
+pub(crate) const NMI_VECTOR: u16 = 0xfffa;
+pub(crate) const RESET_VECTOR: u16 = 0xfffc;
+pub(crate) const IRQ_VECTOR: u16 = 0xfffe;
 
 // Status flag bitmask (6502)
-const FLAG_NEGATIVE: u8 = 0b1000_0000;
-const FLAG_OVERFLOW: u8 = 0b0100_0000;
-const FLAG_UNUSED: u8 = 0b0010_0000;
-const FLAG_BREAK: u8 = 0b0001_0000;
-const FLAG_DECIMAL: u8 = 0b0000_1000;
-const FLAG_INTERRUPT: u8 = 0b0000_0100;
-const FLAG_ZERO: u8 = 0b0000_0010;
-const FLAG_CARRY: u8 = 0b0000_0001;
+pub const FLAG_NEGATIVE: u8 = 0b1000_0000;
+pub const FLAG_OVERFLOW: u8 = 0b0100_0000;
+pub const FLAG_UNUSED: u8 = 0b0010_0000;
+pub const FLAG_BREAK: u8 = 0b0001_0000;
+pub const FLAG_DECIMAL: u8 = 0b0000_1000;
+pub const FLAG_INTERRUPT: u8 = 0b0000_0100;
+pub const FLAG_ZERO: u8 = 0b0000_0010;
+pub const FLAG_CARRY: u8 = 0b0000_0001;
+
+/// Cycles charged for servicing RESET, IRQ, NMI (and BRK).
+const INTERRUPT_CYCLES: u64 = 7;
+
+/// Base cycle cost of every opcode (NMOS 6502), indexed by opcode byte.
+/// Page-cross and branch-taken penalties are added on top of these at runtime.
+/// Undocumented opcodes are included so the table stays usable for them too.
+#[rustfmt::skip]
+const BASE_CYCLES: [u8; 256] = [
+    //  0  1  2  3  4  5  6  7  8  9  A  B  C  D  E  F
+    7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, // 0x00
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x10
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, // 0x20
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x30
+    6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, // 0x40
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x50
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, // 0x60
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0x70
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0x80
+    2, 6, 2, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5, // 0x90
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // 0xA0
+    2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, // 0xB0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xC0
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xD0
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // 0xE0
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // 0xF0
+];
+
+/// Opcodes whose behavior depends on analog effects (see `UnstableOpcodes`).
+const UNSTABLE_OPCODES: [u8; 8] = [0x8b, 0xab, 0x93, 0x9f, 0x9b, 0x9e, 0x9c, 0xbb];
 
 /// Represents an operand fetched by an addressing mode helper.
 #[derive(Debug, Clone, Copy)]
@@ -27,6 +59,120 @@ enum Operand {
     Accumulator,
 }
 
+/// Which 6502 derivative the core emulates.
+/// The variants share the instruction set but differ in a few details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuVariant {
+    /// Original NMOS 6502: ADC/SBC honor the decimal (D) flag.
+    Nmos6502,
+
+    /// Ricoh 2A03/2A07 used in the NES: the D flag can be set and cleared,
+    /// but the BCD circuitry is disconnected, so ADC/SBC always work in binary.
+    Ricoh2A03,
+}
+
+/// How the unstable undocumented opcodes (XAA, LXA, AHX, TAS, SHX, SHY, LAS) behave.
+///
+/// On real NMOS chips these depend on analog effects and vary between CPUs,
+/// temperature and bus load, so no single behavior is "correct".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnstableOpcodes {
+    /// Emulate the commonly observed behavior. `magic` is the chip-dependent
+    /// constant ORed into A by XAA and LXA (usually $EE, $FF or $00).
+    /// AHX/TAS/SHX/SHY store `value & (H + 1)` and corrupt the target page
+    /// when indexing crosses a page boundary.
+    Emulate { magic: u8 },
+
+    /// Execute them as NOPs of the same length and cycle count.
+    Nop,
+
+    /// Refuse to execute them: `run_once` returns `CpuError::UnimplementedOpcode`
+    /// and leaves the CPU untouched. Useful to detect programs relying on them.
+    Reject,
+}
+
+/// The hardware interrupt lines of the 6502.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    /// Maskable interrupt request, vector at $FFFE.
+    Irq,
+
+    /// Non-maskable interrupt, vector at $FFFA.
+    Nmi,
+}
+
+/// Result of a successful `run_once` step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An instruction was executed.
+    Instruction { pc: u16, opcode: u8, cycles: u64 },
+
+    /// A BRK instruction was executed and the CPU jumped to the IRQ/BRK vector.
+    Break { pc: u16, cycles: u64 },
+
+    /// A pending interrupt was serviced instead of executing an instruction.
+    /// `pc` is the address execution will return to.
+    Interrupt {
+        kind: Interrupt,
+        pc: u16,
+        cycles: u64,
+    },
+}
+
+impl StepOutcome {
+    /// Number of cycles the step consumed.
+    pub fn cycles(&self) -> u64 {
+        match *self {
+            StepOutcome::Instruction { cycles, .. }
+            | StepOutcome::Break { cycles, .. }
+            | StepOutcome::Interrupt { cycles, .. } => cycles,
+        }
+    }
+}
+
+/// Why a bounded run (`run_for_cycles`, `run_for_instructions`, `run_until`) returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The cycle budget was used up. `cycles` is the number actually executed,
+    /// which can exceed the budget by up to one instruction.
+    CycleBudget { cycles: u64 },
+
+    /// The requested number of instructions was executed.
+    InstructionBudget,
+
+    /// The stop condition returned true.
+    Condition,
+}
+
+/// Conditions that stop the CPU from making progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    /// The opcode at `pc` is not executed by this CPU configuration.
+    UnimplementedOpcode { pc: u16, opcode: u8 },
+
+    /// A KIL/JAM opcode at `pc` halted the CPU. Only `reset` recovers from it.
+    Jammed { pc: u16, opcode: u8 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CpuError::UnimplementedOpcode { pc, opcode } => {
+                write!(
+                    f,
+                    "opcode ${:02X} at ${:04X} is not implemented",
+                    opcode, pc
+                )
+            }
+            CpuError::Jammed { pc, opcode } => {
+                write!(f, "CPU jammed by opcode ${:02X} at ${:04X}", opcode, pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
 /// The CPU struct represents the central processing unit.
 pub struct CPU {
     /// Accumulator register (A), used for arithmetic and logic operations.
@@ -42,6 +188,7 @@ pub struct CPU {
     /// - Bit 7: Negative flag (N)
     /// - Bit 1: Zero flag (Z)
     /// - Bit 0: Carry flag (C)
+    ///
     /// and others (not fully implemented here).
     pub status: u8,
 
@@ -58,11 +205,31 @@ pub struct CPU {
 
     /// Accumulated CPU cycles executed (host-side counter for emulation timing)
     pub cycles: u64,
+
+    /// The chip being emulated (controls decimal mode support).
+    pub variant: CpuVariant,
+
+    /// Behavior of the unstable undocumented opcodes.
+    pub unstable_opcodes: UnstableOpcodes,
+
+    /// Set when a KIL/JAM opcode halted the CPU. Only `reset` clears it.
+    pub jammed: bool,
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl CPU {
-    /// Creates a new CPU instance with all registers and flags initialized to zero.
+    /// Creates a new NMOS 6502 CPU instance with all registers and flags initialized to zero.
     pub fn new() -> Self {
+        Self::with_variant(CpuVariant::Nmos6502)
+    }
+
+    /// Creates a new CPU instance emulating the given 6502 variant.
+    pub fn with_variant(variant: CpuVariant) -> Self {
         let mut cpu = CPU {
             register_a: 0,
             register_x: 0,
@@ -73,6 +240,9 @@ impl CPU {
             nmi_pending: false,
             irq_pending: false,
             cycles: 0,
+            variant,
+            unstable_opcodes: UnstableOpcodes::Emulate { magic: 0xee },
+            jammed: false,
         };
 
         // Caller MUST call reset(bus) before running, but we clear state here.
@@ -95,16 +265,16 @@ impl CPU {
         }
     }
 
+    /// Returns true if ADC/SBC should use BCD arithmetic:
+    /// the D flag is set and the chip actually has decimal mode.
+    fn decimal_mode_active(&self) -> bool {
+        self.get_flag(FLAG_DECIMAL) && self.variant != CpuVariant::Ricoh2A03
+    }
+
     /// Core binary ADC operation (no BCD handling).
     /// Adds `operand` + carry to the accumulator and updates flags.
     /// Returns the new accumulator value (8-bit).
     fn adc_binary(&mut self, operand: u8) -> u8 {
-        // If Decimal mode is set, we don't support it yet.
-        if self.get_flag(FLAG_DECIMAL) {
-            // TODO: implement BDC mode
-            panic!("ADC in decimal (BCD) mode not implemented");
-        }
-
         let a = self.register_a as u16;
         let m = operand as u16;
         let carry_in = if self.get_flag(FLAG_CARRY) {
@@ -136,11 +306,6 @@ impl CPU {
     /// Subtracts `operand` + (1 - carry) from accumulator and updates flags.
     /// Returns the new accumulator value (8-bit).
     fn sbc_binary(&mut self, operand: u8) -> u8 {
-        if self.get_flag(FLAG_DECIMAL) {
-            // TODO: implement BCD SBC
-            panic!("SBC in decimal (BCD) mode not implemented");
-        }
-
         // Implement SBC as A + (~operand) + carry
         let inverted = !operand as u16;
         let a = self.register_a as u16;
@@ -157,8 +322,9 @@ impl CPU {
 
         let result = (sum & 0xff) as u8;
 
-        // Overflow detection same trick as ADC when using two's complement math
-        let overflow = (!(self.register_a ^ operand) & (self.register_a ^ result) & 0x80) != 0;
+        // Overflow detection: same trick as ADC, applied to the inverted operand
+        // that is actually added. Equivalent to ((A ^ M) & (A ^ R) & 0x80) != 0.
+        let overflow = ((self.register_a ^ operand) & (self.register_a ^ result) & 0x80) != 0;
         self.set_flag(FLAG_OVERFLOW, overflow);
 
         self.update_zero_and_negative_flags(result);
@@ -166,6 +332,70 @@ impl CPU {
         result
     }
 
+    /// Decimal (BCD) ADC operation, NMOS behavior.
+    /// Each nibble is added separately and corrected by +6 when it exceeds 9.
+    ///
+    /// On the NMOS 6502 only C is a valid decimal result. The other flags come
+    /// from intermediate values of the adder, which matters for invalid BCD inputs:
+    /// - Z is set from the plain binary sum (as if D were clear)
+    /// - N and V are taken from the high nibble *before* it is decimal-corrected
+    fn adc_decimal(&mut self, operand: u8) -> u8 {
+        let a = self.register_a;
+        let carry_in = self.get_flag(FLAG_CARRY) as u8;
+
+        // Z comes from the binary result
+        let binary = a.wrapping_add(operand).wrapping_add(carry_in);
+        self.set_flag(FLAG_ZERO, binary == 0);
+
+        // Low nibble, corrected into the 0-9 range with a carry into the high nibble
+        let mut lo = (a & 0x0f) + (operand & 0x0f) + carry_in;
+        if lo > 0x09 {
+            lo += 0x06;
+        }
+
+        // High nibble (u16: can exceed 0xFF before correction)
+        let mut hi = (a >> 4) as u16 + (operand >> 4) as u16 + (lo > 0x0f) as u16;
+
+        // N and V are computed from the uncorrected high nibble
+        let uncorrected = ((hi << 4) as u8) | (lo & 0x0f);
+        self.set_flag(FLAG_NEGATIVE, (uncorrected & 0x80) != 0);
+        let overflow = (!(a ^ operand) & (a ^ uncorrected) & 0x80) != 0;
+        self.set_flag(FLAG_OVERFLOW, overflow);
+
+        if hi > 0x09 {
+            hi += 0x06;
+        }
+        self.set_flag(FLAG_CARRY, hi > 0x0f);
+
+        (((hi << 4) as u8) & 0xf0) | (lo & 0x0f)
+    }
+
+    /// Decimal (BCD) SBC operation, NMOS behavior.
+    /// On the NMOS 6502 all flags (N, V, Z, C) are identical to binary mode;
+    /// only the accumulator result is decimal-corrected (-6 per borrowing nibble).
+    fn sbc_decimal(&mut self, operand: u8) -> u8 {
+        let a = self.register_a as i16;
+        let m = operand as i16;
+        let borrow = 1 - self.get_flag(FLAG_CARRY) as i16;
+
+        // Flags come straight from the binary subtraction
+        self.sbc_binary(operand);
+
+        // Low nibble
+        let mut lo = (a & 0x0f) - (m & 0x0f) - borrow;
+        if lo < 0 {
+            lo = ((lo - 0x06) & 0x0f) - 0x10;
+        }
+
+        // High nibble plus the (possibly negative) low nibble
+        let mut result = (a & 0xf0) - (m & 0xf0) + lo;
+        if result < 0 {
+            result -= 0x60;
+        }
+
+        (result & 0xff) as u8
+    }
+
     /// Resets the CPU to its initial power-on state.
     /// This simulates the 6502 RESET interrupt, which initializes
     /// registers and loads the starting address from the RESET vector/
@@ -188,9 +418,13 @@ impl CPU {
         self.register_a = 0;
         self.register_x = 0;
         self.register_y = 0;
-    }
 
-    #[allow(dead_code)]
+        // RESET is the only way out of a KIL/JAM lock-up
+        self.jammed = false;
+
+        // The reset sequence takes as long as an interrupt
+        self.cycles = self.cycles.wrapping_add(INTERRUPT_CYCLES);
+    }
 
     /// Requests a maskable interrupt (IRQ). Ignored if I flag is set.
     pub fn trigger_irq(&mut self) {
@@ -215,15 +449,27 @@ impl CPU {
         let lo = bus.read(vector) as u16;
         let hi = bus.read(vector + 1) as u16;
         self.program_counter = (hi << 8) | lo;
-    }
 
-    #[allow(dead_code)]
+        self.cycles = self.cycles.wrapping_add(INTERRUPT_CYCLES);
+    }
 
     /// Requests a non-maskable interrupt (NMI). Always taken.
     pub fn trigger_nmi(&mut self) {
         self.nmi_pending = true;
     }
 
+    /// The interrupt the next step will service instead of executing an
+    /// instruction, if any. NMI wins over IRQ; IRQ is masked by the I flag.
+    pub fn pending_interrupt(&self) -> Option<Interrupt> {
+        if self.nmi_pending {
+            Some(Interrupt::Nmi)
+        } else if self.irq_pending && !self.get_flag(FLAG_INTERRUPT) {
+            Some(Interrupt::Irq)
+        } else {
+            None
+        }
+    }
+
     /// Computes the absolute memory address of the stack location pointed by 'stack_pointer'.
     /// Stack resides in page 0x0100 (0x0100 - 0x01FF).
     fn stack_address(&self) -> u16 {
@@ -283,13 +529,6 @@ impl CPU {
         }
     }
 
-    /// Adjusts the program counter by a signed offset for branching instructions.
-    fn branch(&mut self, offset: i8) {
-        let pc = self.program_counter as i32;
-        let offset = offset as i32;
-        self.program_counter = (pc + offset) as u16;
-    }
-
     //
     // ---- Addressing mode helpers ----
     //
@@ -299,14 +538,14 @@ impl CPU {
     //
 
     /// Read a little-endian 16-bit value from memory at `addr` (lo then hi).
-    fn read_u16(&self, bus: &impl Bus, addr: u16) -> u16 {
+    fn read_u16(&self, bus: &mut impl Bus, addr: u16) -> u16 {
         let lo = bus.read(addr) as u16;
         let hi = bus.read(addr.wrapping_add(1)) as u16;
         (hi << 8) | lo
     }
 
     /// Immediate: operand is the next byte. PC advances by 2.
-    fn fetch_immediate(&mut self, bus: &impl Bus) -> Operand {
+    fn fetch_immediate(&mut self, bus: &mut impl Bus) -> Operand {
         let value = bus.read(self.program_counter.wrapping_add(1));
         self.program_counter = self.program_counter.wrapping_add(2);
         Operand::Immediate(value)
@@ -319,30 +558,30 @@ impl CPU {
     }
 
     /// Zero Page: single byte address in page $00. PC +2.
-    fn fetch_zeropage(&mut self, bus: &impl Bus) -> Operand {
+    fn fetch_zeropage(&mut self, bus: &mut impl Bus) -> Operand {
         let addr = bus.read(self.program_counter.wrapping_add(1)) as u16;
         self.program_counter = self.program_counter.wrapping_add(2);
         Operand::Address(addr & 0x00ff)
     }
 
     /// Zero Page,X: zero page address + X, wraps within zero page. PC +2.
-    fn fetch_zeropage_x(&mut self, bus: &impl Bus) -> Operand {
-        let base = bus.read(self.program_counter.wrapping_add(1)) as u8;
+    fn fetch_zeropage_x(&mut self, bus: &mut impl Bus) -> Operand {
+        let base = bus.read(self.program_counter.wrapping_add(1));
         let addr = base.wrapping_add(self.register_x) as u16;
         self.program_counter = self.program_counter.wrapping_add(2);
-        Operand::Address(addr as u16 & 0x00ff)
+        Operand::Address(addr & 0x00ff)
     }
 
     /// Zero Page,Y: zero page address + Y, wraps within zero page. PC +2.
-    fn fetch_zeropage_y(&mut self, bus: &impl Bus) -> Operand {
-        let base = bus.read(self.program_counter.wrapping_add(1)) as u8;
+    fn fetch_zeropage_y(&mut self, bus: &mut impl Bus) -> Operand {
+        let base = bus.read(self.program_counter.wrapping_add(1));
         let addr = base.wrapping_add(self.register_y) as u16;
         self.program_counter = self.program_counter.wrapping_add(2);
-        Operand::Address(addr as u16 & 0x00ff)
+        Operand::Address(addr & 0x00ff)
     }
 
     /// Absolute: 16-bit address (lo/hi). PC +3.
-    fn fetch_absolute(&mut self, bus: &impl Bus) -> Operand {
+    fn fetch_absolute(&mut self, bus: &mut impl Bus) -> Operand {
         let lo = bus.read(self.program_counter.wrapping_add(1)) as u16;
         let hi = bus.read(self.program_counter.wrapping_add(2)) as u16;
         let addr = (hi << 8) | lo;
@@ -351,7 +590,7 @@ impl CPU {
     }
 
     /// Absolute,X: absolute + X. Returns operand and `page_crossed` flag. PC +3.
-    fn fetch_absolute_x(&mut self, bus: &impl Bus) -> (Operand, bool) {
+    fn fetch_absolute_x(&mut self, bus: &mut impl Bus) -> (Operand, bool) {
         let lo = bus.read(self.program_counter.wrapping_add(1)) as u16;
         let hi = bus.read(self.program_counter.wrapping_add(2)) as u16;
         let base = (hi << 8) | lo;
@@ -362,7 +601,7 @@ impl CPU {
     }
 
     /// Absolute,Y: absolute + Y. Returns operand and `page_crossed` flag. PC +3.
-    fn fetch_absolute_y(&mut self, bus: &impl Bus) -> (Operand, bool) {
+    fn fetch_absolute_y(&mut self, bus: &mut impl Bus) -> (Operand, bool) {
         let lo = bus.read(self.program_counter.wrapping_add(1)) as u16;
         let hi = bus.read(self.program_counter.wrapping_add(2)) as u16;
         let base = (hi << 8) | lo;
@@ -375,12 +614,12 @@ impl CPU {
     /// (Indirect,X) - "Indexed Indirect"
     /// Effective address = read16((zp + X) & 0xFF)
     /// PC +2.
-    fn fetch_indexed_indirect(&mut self, bus: &impl Bus) -> Operand {
+    fn fetch_indexed_indirect(&mut self, bus: &mut impl Bus) -> Operand {
         let zp = bus.read(self.program_counter.wrapping_add(1));
         let ptr = zp.wrapping_add(self.register_x) as u16 & 0x00ff;
         // zero page wrap for pointer low/high
         let lo = bus.read(ptr) as u16;
-        let hi = bus.read((ptr.wrapping_add(1) & 0x00ff)) as u16;
+        let hi = bus.read(ptr.wrapping_add(1) & 0x00ff) as u16;
         let addr = (hi << 8) | lo;
         self.program_counter = self.program_counter.wrapping_add(2);
         Operand::Address(addr)
@@ -389,10 +628,10 @@ impl CPU {
     /// (Indirect),Y - "Indirect Indexed"
     /// Effective address = read16(zp) + Y
     /// Returns operand and page_cross flag; PC +2.
-    fn fetch_indirect_indexed(&mut self, bus: &impl Bus) -> (Operand, bool) {
+    fn fetch_indirect_indexed(&mut self, bus: &mut impl Bus) -> (Operand, bool) {
         let zp = bus.read(self.program_counter.wrapping_add(1)) as u16 & 0x00ff;
         let lo = bus.read(zp) as u16;
-        let hi = bus.read((zp.wrapping_add(1) & 0x00ff)) as u16;
+        let hi = bus.read(zp.wrapping_add(1) & 0x00ff) as u16;
         let base = (hi << 8) | lo;
         let addr = base.wrapping_add(self.register_y as u16);
         let page_crossed = (base & 0xff00) != (addr & 0xff00);
@@ -402,7 +641,7 @@ impl CPU {
 
     /// Indirect addressing used by JMP (indirect). Implements the 6502 page-boundary bug:
     /// If the indirect vector falls on a page boundary (xxFF), the high byte is fetched from xx00 instead of xx+1 00.
-    fn fetch_indirect_jmp(&mut self, bus: &impl Bus) -> Operand {
+    fn fetch_indirect_jmp(&mut self, bus: &mut impl Bus) -> Operand {
         let ptr_lo = bus.read(self.program_counter.wrapping_add(1)) as u16;
         let ptr_hi = bus.read(self.program_counter.wrapping_add(2)) as u16;
         let ptr = (ptr_hi << 8) | ptr_lo;
@@ -411,7 +650,7 @@ impl CPU {
         let lo = bus.read(ptr) as u16;
         let hi_addr = if (ptr & 0x00ff) == 0x00ff {
             // wrap within page
-            (ptr & 0xff00)
+            ptr & 0xff00
         } else {
             ptr.wrapping_add(1)
         };
@@ -422,7 +661,7 @@ impl CPU {
     }
 
     /// Relative addressing: used by branch instructions; fetch signed offset and advance PC by 2.
-    fn fetch_relative_offset(&mut self, bus: &impl Bus) -> i8 {
+    fn fetch_relative_offset(&mut self, bus: &mut impl Bus) -> i8 {
         let offset = bus.read(self.program_counter.wrapping_add(1)) as i8;
         self.program_counter = self.program_counter.wrapping_add(2);
         offset
@@ -453,130 +692,1221 @@ impl CPU {
         }
     }
 
+    /// Adds the extra cycle charged when an indexed read crosses a page boundary.
+    fn add_page_cross_cycle(&mut self, page_crossed: bool) {
+        if page_crossed {
+            self.cycles = self.cycles.wrapping_add(1);
+        }
+    }
+
+    //
+    // ---- Operand access helpers ----
+    //
+    // Instructions receive the `Operand` produced by an addressing mode helper and
+    // use these two functions to read or write it, so the same instruction code
+    // works for immediate values, memory addresses and the accumulator.
+    //
+
+    /// Reads the value an operand refers to.
+    fn read_operand(&self, bus: &mut impl Bus, op: Operand) -> u8 {
+        match op {
+            Operand::Immediate(value) => value,
+            Operand::Address(addr) => bus.read(addr),
+            Operand::Accumulator => self.register_a,
+        }
+    }
+
+    /// Writes a value back to the location an operand refers to.
+    fn write_operand(&mut self, bus: &mut impl Bus, op: Operand, value: u8) {
+        match op {
+            Operand::Address(addr) => bus.write(addr, value),
+            Operand::Accumulator => self.register_a = value,
+            Operand::Immediate(_) => unreachable!("cannot write to an immediate operand"),
+        }
+    }
+
+    //
+    // ---- Instruction implementations ----
+    //
+    // One function per instruction. The opcode dispatch in `run_once` only picks
+    // the addressing mode; the behavior of the instruction itself lives here.
+    //
+
+    /// LDA: A = M. Updates N and Z.
+    fn lda(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.register_a = self.read_operand(bus, op);
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    /// LDX: X = M. Updates N and Z.
+    fn ldx(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.register_x = self.read_operand(bus, op);
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
+    /// LDY: Y = M. Updates N and Z.
+    fn ldy(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.register_y = self.read_operand(bus, op);
+        self.update_zero_and_negative_flags(self.register_y);
+    }
+
+    /// STA: M = A. No flags affected.
+    fn sta(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.write_operand(bus, op, self.register_a);
+    }
+
+    /// STX: M = X. No flags affected.
+    fn stx(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.write_operand(bus, op, self.register_x);
+    }
+
+    /// STY: M = Y. No flags affected.
+    fn sty(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.write_operand(bus, op, self.register_y);
+    }
+
+    /// AND: A = A & M. Updates N and Z.
+    fn and(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.register_a &= self.read_operand(bus, op);
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    /// ORA: A = A | M. Updates N and Z.
+    fn ora(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.register_a |= self.read_operand(bus, op);
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    /// EOR: A = A ^ M. Updates N and Z.
+    fn eor(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.register_a ^= self.read_operand(bus, op);
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    /// ADC: A = A + M + C. Updates N, V, Z and C.
+    /// Uses BCD arithmetic when decimal mode is active.
+    fn adc(&mut self, bus: &mut impl Bus, op: Operand) {
+        let operand = self.read_operand(bus, op);
+        self.add_with_carry(operand);
+    }
+
+    /// Adds a value to A with carry, in binary or decimal depending on the D flag.
+    fn add_with_carry(&mut self, operand: u8) {
+        self.register_a = if self.decimal_mode_active() {
+            self.adc_decimal(operand)
+        } else {
+            self.adc_binary(operand)
+        };
+    }
+
+    /// SBC: A = A - M - (1 - C). Updates N, V, Z and C.
+    /// Uses BCD arithmetic when decimal mode is active.
+    fn sbc(&mut self, bus: &mut impl Bus, op: Operand) {
+        let operand = self.read_operand(bus, op);
+        self.subtract_with_borrow(operand);
+    }
+
+    /// Subtracts a value from A with borrow, in binary or decimal depending on the D flag.
+    fn subtract_with_borrow(&mut self, operand: u8) {
+        self.register_a = if self.decimal_mode_active() {
+            self.sbc_decimal(operand)
+        } else {
+            self.sbc_binary(operand)
+        };
+    }
+
+    /// Shared compare logic for CMP/CPX/CPY.
+    /// C is set when register >= M, Z when equal, N from bit 7 of the difference.
+    fn compare(&mut self, register: u8, value: u8) {
+        let result = register.wrapping_sub(value);
+        self.set_flag(FLAG_CARRY, register >= value);
+        self.update_zero_and_negative_flags(result);
+    }
+
+    /// CMP: compare A with M.
+    fn cmp(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.read_operand(bus, op);
+        self.compare(self.register_a, value);
+    }
+
+    /// CPX: compare X with M.
+    fn cpx(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.read_operand(bus, op);
+        self.compare(self.register_x, value);
+    }
+
+    /// CPY: compare Y with M.
+    fn cpy(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.read_operand(bus, op);
+        self.compare(self.register_y, value);
+    }
+
+    /// BIT: Z = (A & M) == 0, N = M bit 7, V = M bit 6. A is not modified.
+    fn bit(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.read_operand(bus, op);
+        self.set_flag(FLAG_ZERO, (self.register_a & value) == 0);
+        self.set_flag(FLAG_NEGATIVE, (value & FLAG_NEGATIVE) != 0);
+        self.set_flag(FLAG_OVERFLOW, (value & FLAG_OVERFLOW) != 0);
+    }
+
+    /// ASL: shift left one bit, bit 7 goes into C. Returns the result.
+    fn asl(&mut self, bus: &mut impl Bus, op: Operand) -> u8 {
+        let value = self.read_operand(bus, op);
+        let result = value << 1;
+        self.set_flag(FLAG_CARRY, (value & 0x80) != 0);
+        self.update_zero_and_negative_flags(result);
+        self.write_operand(bus, op, result);
+        result
+    }
+
+    /// LSR: shift right one bit, bit 0 goes into C. Returns the result.
+    fn lsr(&mut self, bus: &mut impl Bus, op: Operand) -> u8 {
+        let value = self.read_operand(bus, op);
+        let result = value >> 1;
+        self.set_flag(FLAG_CARRY, (value & 0x01) != 0);
+        self.update_zero_and_negative_flags(result);
+        self.write_operand(bus, op, result);
+        result
+    }
+
+    /// ROL: rotate left through carry. Returns the result.
+    fn rol(&mut self, bus: &mut impl Bus, op: Operand) -> u8 {
+        let value = self.read_operand(bus, op);
+        let carry_in = self.get_flag(FLAG_CARRY) as u8;
+        let result = (value << 1) | carry_in;
+        self.set_flag(FLAG_CARRY, (value & 0x80) != 0);
+        self.update_zero_and_negative_flags(result);
+        self.write_operand(bus, op, result);
+        result
+    }
+
+    /// ROR: rotate right through carry. Returns the result.
+    fn ror(&mut self, bus: &mut impl Bus, op: Operand) -> u8 {
+        let value = self.read_operand(bus, op);
+        let carry_in = (self.get_flag(FLAG_CARRY) as u8) << 7;
+        let result = (value >> 1) | carry_in;
+        self.set_flag(FLAG_CARRY, (value & 0x01) != 0);
+        self.update_zero_and_negative_flags(result);
+        self.write_operand(bus, op, result);
+        result
+    }
+
+    /// INC: M = M + 1. Updates N and Z. Returns the result.
+    fn inc(&mut self, bus: &mut impl Bus, op: Operand) -> u8 {
+        let result = self.read_operand(bus, op).wrapping_add(1);
+        self.update_zero_and_negative_flags(result);
+        self.write_operand(bus, op, result);
+        result
+    }
+
+    /// DEC: M = M - 1. Updates N and Z. Returns the result.
+    fn dec(&mut self, bus: &mut impl Bus, op: Operand) -> u8 {
+        let result = self.read_operand(bus, op).wrapping_sub(1);
+        self.update_zero_and_negative_flags(result);
+        self.write_operand(bus, op, result);
+        result
+    }
+
+    //
+    // ---- Undocumented (illegal) instruction implementations ----
+    //
+    // The NMOS 6502 decodes every opcode byte. The "illegal" ones mostly combine a
+    // read-modify-write instruction with an ALU instruction, because both parts
+    // of the decode logic fire at once.
+    //
+
+    /// SLO: ASL memory, then ORA the result into A.
+    fn slo(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.asl(bus, op);
+        self.register_a |= value;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    /// RLA: ROL memory, then AND the result into A.
+    fn rla(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.rol(bus, op);
+        self.register_a &= value;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    /// SRE: LSR memory, then EOR the result into A.
+    fn sre(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.lsr(bus, op);
+        self.register_a ^= value;
+        self.update_zero_and_negative_flags(self.register_a);
+    }
+
+    /// RRA: ROR memory, then ADC the result (using the carry ROR produced).
+    fn rra(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.ror(bus, op);
+        self.add_with_carry(value);
+    }
+
+    /// DCP: DEC memory, then CMP A against the result.
+    fn dcp(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.dec(bus, op);
+        self.compare(self.register_a, value);
+    }
+
+    /// ISC (ISB): INC memory, then SBC the result from A.
+    fn isc(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.inc(bus, op);
+        self.subtract_with_borrow(value);
+    }
+
+    /// SAX: M = A & X. No flags affected.
+    fn sax(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.write_operand(bus, op, self.register_a & self.register_x);
+    }
+
+    /// LAX: A = X = M. Updates N and Z.
+    fn lax(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.read_operand(bus, op);
+        self.register_a = value;
+        self.register_x = value;
+        self.update_zero_and_negative_flags(value);
+    }
+
+    /// ANC: AND immediate, then copy N into C.
+    fn anc(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.and(bus, op);
+        self.set_flag(FLAG_CARRY, self.get_flag(FLAG_NEGATIVE));
+    }
+
+    /// ALR (ASR): AND immediate, then LSR A.
+    fn alr(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.and(bus, op);
+        self.lsr(bus, Operand::Accumulator);
+    }
+
+    /// ARR: AND immediate, then ROR A, with C and V taken from bits 6 and 5.
+    ///
+    /// In decimal mode the NMOS chip additionally applies a BCD fixup to each
+    /// nibble of the result, and C reflects the high nibble fixup instead.
+    fn arr(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.register_a & self.read_operand(bus, op);
+        let carry_in = (self.get_flag(FLAG_CARRY) as u8) << 7;
+        let mut result = (value >> 1) | carry_in;
+
+        self.update_zero_and_negative_flags(result);
+
+        if self.decimal_mode_active() {
+            // V is the bit 6 change caused by the rotate
+            self.set_flag(FLAG_OVERFLOW, ((value ^ result) & 0x40) != 0);
+
+            let lo = value & 0x0f;
+            let hi = value >> 4;
+            if lo + (lo & 0x01) > 0x05 {
+                result = (result & 0xf0) | (result.wrapping_add(0x06) & 0x0f);
+            }
+            let carry = hi + (hi & 0x01) > 0x05;
+            if carry {
+                result = result.wrapping_add(0x60);
+            }
+            self.set_flag(FLAG_CARRY, carry);
+        } else {
+            let bit6 = (result & 0x40) != 0;
+            let bit5 = (result & 0x20) != 0;
+            self.set_flag(FLAG_CARRY, bit6);
+            self.set_flag(FLAG_OVERFLOW, bit6 ^ bit5);
+        }
+
+        self.register_a = result;
+    }
+
+    /// AXS (SBX): X = (A & X) - M, without borrow. Sets C, N and Z like CMP.
+    fn axs(&mut self, bus: &mut impl Bus, op: Operand) {
+        let value = self.read_operand(bus, op);
+        let masked = self.register_a & self.register_x;
+        self.compare(masked, value);
+        self.register_x = masked.wrapping_sub(value);
+    }
+
+    //
+    // Unstable opcodes. Their results depend on analog effects on the real chip
+    // and differ between individual CPUs. `self.unstable_opcodes` decides whether
+    // they are emulated with the commonly observed behavior or treated as NOPs.
+    //
+
+    /// XAA (ANE): A = (A | magic) & X & M.
+    fn xaa(&mut self, bus: &mut impl Bus, op: Operand) {
+        if let UnstableOpcodes::Emulate { magic } = self.unstable_opcodes {
+            let value = self.read_operand(bus, op);
+            self.register_a = (self.register_a | magic) & self.register_x & value;
+            self.update_zero_and_negative_flags(self.register_a);
+        }
+    }
+
+    /// LXA (LAX immediate): A = X = (A | magic) & M.
+    fn lxa(&mut self, bus: &mut impl Bus, op: Operand) {
+        if let UnstableOpcodes::Emulate { magic } = self.unstable_opcodes {
+            let value = (self.register_a | magic) & self.read_operand(bus, op);
+            self.register_a = value;
+            self.register_x = value;
+            self.update_zero_and_negative_flags(value);
+        }
+    }
+
+    /// LAS: A = X = SP = M & SP. Updates N and Z.
+    fn las(&mut self, bus: &mut impl Bus, op: Operand) {
+        if let UnstableOpcodes::Emulate { .. } = self.unstable_opcodes {
+            let value = self.read_operand(bus, op) & self.stack_pointer;
+            self.register_a = value;
+            self.register_x = value;
+            self.stack_pointer = value;
+            self.update_zero_and_negative_flags(value);
+        }
+    }
+
+    /// Shared store logic of AHX/TAS/SHX/SHY: M = value & (H + 1), where H is
+    /// the high byte of the base address before indexing. When indexing crosses
+    /// a page, the stored value also replaces the high byte of the address.
+    fn store_high_and(&mut self, bus: &mut impl Bus, op: Operand, index: u8, value: u8) {
+        if let Operand::Address(addr) = op {
+            let base = addr.wrapping_sub(index as u16);
+            let high_plus_one = ((base >> 8) as u8).wrapping_add(1);
+            let result = value & high_plus_one;
+
+            let target = if (base & 0xff00) != (addr & 0xff00) {
+                ((result as u16) << 8) | (addr & 0x00ff)
+            } else {
+                addr
+            };
+            bus.write(target, result);
+        }
+    }
+
+    /// AHX (SHA): M = A & X & (H + 1). Indexed by Y.
+    fn ahx(&mut self, bus: &mut impl Bus, op: Operand) {
+        if let UnstableOpcodes::Emulate { .. } = self.unstable_opcodes {
+            let value = self.register_a & self.register_x;
+            self.store_high_and(bus, op, self.register_y, value);
+        }
+    }
+
+    /// TAS (SHS): SP = A & X, then M = SP & (H + 1). Indexed by Y.
+    fn tas(&mut self, bus: &mut impl Bus, op: Operand) {
+        if let UnstableOpcodes::Emulate { .. } = self.unstable_opcodes {
+            self.stack_pointer = self.register_a & self.register_x;
+            self.store_high_and(bus, op, self.register_y, self.stack_pointer);
+        }
+    }
+
+    /// SHX (SXA): M = X & (H + 1). Indexed by Y.
+    fn shx(&mut self, bus: &mut impl Bus, op: Operand) {
+        if let UnstableOpcodes::Emulate { .. } = self.unstable_opcodes {
+            self.store_high_and(bus, op, self.register_y, self.register_x);
+        }
+    }
+
+    /// SHY (SYA): M = Y & (H + 1). Indexed by X.
+    fn shy(&mut self, bus: &mut impl Bus, op: Operand) {
+        if let UnstableOpcodes::Emulate { .. } = self.unstable_opcodes {
+            self.store_high_and(bus, op, self.register_x, self.register_y);
+        }
+    }
+
+    /// NOP variants that carry an operand. The operand is still read
+    /// (a dummy read, as on the real chip), but the value is discarded.
+    fn nop_read(&mut self, bus: &mut impl Bus, op: Operand) {
+        self.read_operand(bus, op);
+    }
+
+    //
+    // ---- End instruction implementations ----
+    //
+
     /// Runs the CPU emulation loop, fetching and executing instructions from the bus.
-    /// The loop continues until a BRK (0x00) instruction is encountered.
+    /// The loop continues until a BRK (0x00) instruction is encountered,
+    /// which is returned as `StepOutcome::Break`, or until a step fails.
     /// The CPU reads instructions from memory via the Bus trait interface.
-    pub fn run(&mut self, bus: &mut impl Bus) {
+    pub fn run(&mut self, bus: &mut impl Bus) -> Result<StepOutcome, CpuError> {
         loop {
-            self.run_once(bus);
+            let outcome = self.run_once(bus)?;
+            if let StepOutcome::Break { .. } = outcome {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Runs until at least `budget` cycles have elapsed.
+    /// Instructions are never split, so the last one may overshoot the budget;
+    /// the actual count is reported in `StopReason::CycleBudget` so frame-based
+    /// hosts can carry the difference into the next frame.
+    pub fn run_for_cycles(
+        &mut self,
+        bus: &mut impl Bus,
+        budget: u64,
+    ) -> Result<StopReason, CpuError> {
+        let mut cycles = 0;
+        while cycles < budget {
+            cycles += self.run_once(bus)?.cycles();
+        }
+        Ok(StopReason::CycleBudget { cycles })
+    }
+
+    /// Runs exactly `count` instructions. Interrupt entries are not counted
+    /// as instructions, BRK is.
+    pub fn run_for_instructions(
+        &mut self,
+        bus: &mut impl Bus,
+        count: u64,
+    ) -> Result<StopReason, CpuError> {
+        let mut executed = 0;
+        while executed < count {
+            match self.run_once(bus)? {
+                StepOutcome::Interrupt { .. } => {}
+                StepOutcome::Instruction { .. } | StepOutcome::Break { .. } => executed += 1,
+            }
+        }
+        Ok(StopReason::InstructionBudget)
+    }
+
+    /// Runs until `condition` returns true. The condition is checked before
+    /// every step, so it sees the state the next instruction will start from.
+    pub fn run_until<B: Bus>(
+        &mut self,
+        bus: &mut B,
+        mut condition: impl FnMut(&CPU, &B) -> bool,
+    ) -> Result<StopReason, CpuError> {
+        while !condition(self, bus) {
+            self.run_once(bus)?;
         }
+        Ok(StopReason::Condition)
     }
 
     /// Runs the CPU emulation step by step.
-    pub fn run_once(&mut self, bus: &mut impl Bus) {
-        // Handle interrupts before executing next instruction
-        if self.nmi_pending {
-            self.nmi_pending = false;
-            self.handle_interrupt(bus, NMI_VECTOR);
-        } else if self.irq_pending && !self.get_flag(FLAG_INTERRUPT) {
-            self.irq_pending = false;
-            self.handle_interrupt(bus, IRQ_VECTOR);
+    ///
+    /// A step either services one pending interrupt (7 cycles) or executes one
+    /// instruction. The outcome reports what happened and how many cycles the
+    /// step consumed, including page-cross and branch penalties, so devices can
+    /// be kept in sync. Conditions the CPU cannot continue from are returned as
+    /// `CpuError` instead of panicking, so the host stays alive.
+    pub fn run_once(&mut self, bus: &mut impl Bus) -> Result<StepOutcome, CpuError> {
+        let start_cycles = self.cycles;
+
+        // A jammed CPU does nothing (not even interrupts) until reset,
+        // but the clock keeps running so cycle-driven hosts don't stall.
+        if self.jammed {
+            self.cycles = self.cycles.wrapping_add(1);
+            let pc = self.program_counter;
+            let opcode = bus.peek(pc);
+            return Err(CpuError::Jammed { pc, opcode });
         }
 
-        let opcode = bus.read(self.program_counter);
+        // Handle interrupts before executing next instruction.
+        // Entering the handler takes the place of an instruction fetch.
+        if let Some(kind) = self.pending_interrupt() {
+            let vector = match kind {
+                Interrupt::Nmi => {
+                    self.nmi_pending = false;
+                    NMI_VECTOR
+                }
+                Interrupt::Irq => {
+                    self.irq_pending = false;
+                    IRQ_VECTOR
+                }
+            };
+            let pc = self.program_counter;
+            self.handle_interrupt(bus, vector);
+            let cycles = self.cycles.wrapping_sub(start_cycles);
+            return Ok(StepOutcome::Interrupt { kind, pc, cycles });
+        }
+
+        let pc = self.program_counter;
+
+        // Rejecting must not touch the bus, so the opcode is only peeked at;
+        // the real fetch happens once the instruction is known to execute.
+        if self.unstable_opcodes == UnstableOpcodes::Reject {
+            let opcode = bus.peek(pc);
+            if UNSTABLE_OPCODES.contains(&opcode) {
+                return Err(CpuError::UnimplementedOpcode { pc, opcode });
+            }
+        }
+        let opcode = bus.read(pc);
 
         match opcode {
+            // ---- LDA: Load accumulator ----
             0xa9 => {
-                // LDA Immediate: Load accumulator with immediate value
-                if let Operand::Immediate(value) = self.fetch_immediate(bus) {
-                    self.register_a = value;
-                    self.update_zero_and_negative_flags(self.register_a);
-                } else {
-                    unreachable!();
-                }
+                // LDA Immediate
+                let op = self.fetch_immediate(bus);
+                self.lda(bus, op);
             }
             0xa5 => {
                 // LDA Zero Page
-                if let Operand::Address(addr) = self.fetch_zeropage(bus) {
-                    self.register_a = bus.read(addr);
-                    self.update_zero_and_negative_flags(self.register_a);
-                }
+                let op = self.fetch_zeropage(bus);
+                self.lda(bus, op);
             }
             0xb5 => {
                 // LDA Zero Page,X
-                if let Operand::Address(addr) = self.fetch_zeropage_x(bus) {
-                    self.register_a = bus.read(addr);
-                    self.update_zero_and_negative_flags(self.register_a);
-                }
+                let op = self.fetch_zeropage_x(bus);
+                self.lda(bus, op);
             }
             0xad => {
                 // LDA Absolute
-                if let Operand::Address(addr) = self.fetch_absolute(bus) {
-                    self.register_a = bus.read(addr);
-                    self.update_zero_and_negative_flags(self.register_a);
-                }
+                let op = self.fetch_absolute(bus);
+                self.lda(bus, op);
             }
             0xbd => {
                 // LDA Absolute,X
                 let (op, page_crossed) = self.fetch_absolute_x(bus);
-                if let Operand::Address(addr) = op {
-                    self.register_a = bus.read(addr);
-                    self.update_zero_and_negative_flags(self.register_a);
-                }
-                // page-cross penalty if accessed across page boundary
-                if page_crossed {
-                    self.cycles = self.cycles.wrapping_add(1);
-                }
+                self.lda(bus, op);
+                self.add_page_cross_cycle(page_crossed);
             }
             0xb9 => {
                 // LDA Absolute,Y
                 let (op, page_crossed) = self.fetch_absolute_y(bus);
-                if let Operand::Address(addr) = op {
-                    self.register_a = bus.read(addr);
-                    self.update_zero_and_negative_flags(self.register_a);
-                }
-                if page_crossed {
-                    self.cycles = self.cycles.wrapping_add(1);
-                }
+                self.lda(bus, op);
+                self.add_page_cross_cycle(page_crossed);
             }
             0xa1 => {
                 // LDA (Indirect,X)
-                if let Operand::Address(addr) = self.fetch_indexed_indirect(bus) {
-                    self.register_a = bus.read(addr);
-                    self.update_zero_and_negative_flags(self.register_a);
-                }
+                let op = self.fetch_indexed_indirect(bus);
+                self.lda(bus, op);
             }
             0xb1 => {
                 // LDA (Indirect),Y
                 let (op, page_crossed) = self.fetch_indirect_indexed(bus);
-                if let Operand::Address(addr) = op {
-                    self.register_a = bus.read(addr);
-                    self.update_zero_and_negative_flags(self.register_a);
-                }
-                if page_crossed {
-                    self.cycles = self.cycles.wrapping_add(1);
-                }
+                self.lda(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- LDX: Load X register ----
+            0xa2 => {
+                // LDX Immediate
+                let op = self.fetch_immediate(bus);
+                self.ldx(bus, op);
+            }
+            0xa6 => {
+                // LDX Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.ldx(bus, op);
+            }
+            0xb6 => {
+                // LDX Zero Page,Y
+                let op = self.fetch_zeropage_y(bus);
+                self.ldx(bus, op);
+            }
+            0xae => {
+                // LDX Absolute
+                let op = self.fetch_absolute(bus);
+                self.ldx(bus, op);
+            }
+            0xbe => {
+                // LDX Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.ldx(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- LDY: Load Y register ----
+            0xa0 => {
+                // LDY Immediate
+                let op = self.fetch_immediate(bus);
+                self.ldy(bus, op);
+            }
+            0xa4 => {
+                // LDY Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.ldy(bus, op);
+            }
+            0xb4 => {
+                // LDY Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.ldy(bus, op);
+            }
+            0xac => {
+                // LDY Absolute
+                let op = self.fetch_absolute(bus);
+                self.ldy(bus, op);
+            }
+            0xbc => {
+                // LDY Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.ldy(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- STA: Store accumulator ----
+            0x85 => {
+                // STA Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.sta(bus, op);
+            }
+            0x95 => {
+                // STA Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.sta(bus, op);
+            }
+            0x8d => {
+                // STA Absolute
+                let op = self.fetch_absolute(bus);
+                self.sta(bus, op);
+            }
+            0x9d => {
+                // STA Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.sta(bus, op);
+            }
+            0x99 => {
+                // STA Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.sta(bus, op);
+            }
+            0x81 => {
+                // STA (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.sta(bus, op);
+            }
+            0x91 => {
+                // STA (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.sta(bus, op);
+            }
+            // ---- STX: Store X register ----
+            0x86 => {
+                // STX Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.stx(bus, op);
+            }
+            0x96 => {
+                // STX Zero Page,Y
+                let op = self.fetch_zeropage_y(bus);
+                self.stx(bus, op);
+            }
+            0x8e => {
+                // STX Absolute
+                let op = self.fetch_absolute(bus);
+                self.stx(bus, op);
+            }
+            // ---- STY: Store Y register ----
+            0x84 => {
+                // STY Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.sty(bus, op);
+            }
+            0x94 => {
+                // STY Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.sty(bus, op);
+            }
+            0x8c => {
+                // STY Absolute
+                let op = self.fetch_absolute(bus);
+                self.sty(bus, op);
+            }
+            // ---- AND: Logical AND ----
+            0x29 => {
+                // AND Immediate
+                let op = self.fetch_immediate(bus);
+                self.and(bus, op);
+            }
+            0x25 => {
+                // AND Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.and(bus, op);
+            }
+            0x35 => {
+                // AND Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.and(bus, op);
+            }
+            0x2d => {
+                // AND Absolute
+                let op = self.fetch_absolute(bus);
+                self.and(bus, op);
+            }
+            0x3d => {
+                // AND Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.and(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x39 => {
+                // AND Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.and(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x21 => {
+                // AND (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.and(bus, op);
+            }
+            0x31 => {
+                // AND (Indirect),Y
+                let (op, page_crossed) = self.fetch_indirect_indexed(bus);
+                self.and(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- ORA: Logical inclusive OR ----
+            0x09 => {
+                // ORA Immediate
+                let op = self.fetch_immediate(bus);
+                self.ora(bus, op);
+            }
+            0x05 => {
+                // ORA Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.ora(bus, op);
+            }
+            0x15 => {
+                // ORA Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.ora(bus, op);
+            }
+            0x0d => {
+                // ORA Absolute
+                let op = self.fetch_absolute(bus);
+                self.ora(bus, op);
+            }
+            0x1d => {
+                // ORA Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.ora(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x19 => {
+                // ORA Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.ora(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x01 => {
+                // ORA (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.ora(bus, op);
+            }
+            0x11 => {
+                // ORA (Indirect),Y
+                let (op, page_crossed) = self.fetch_indirect_indexed(bus);
+                self.ora(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- EOR: Exclusive OR ----
+            0x49 => {
+                // EOR Immediate
+                let op = self.fetch_immediate(bus);
+                self.eor(bus, op);
+            }
+            0x45 => {
+                // EOR Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.eor(bus, op);
+            }
+            0x55 => {
+                // EOR Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.eor(bus, op);
             }
+            0x4d => {
+                // EOR Absolute
+                let op = self.fetch_absolute(bus);
+                self.eor(bus, op);
+            }
+            0x5d => {
+                // EOR Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.eor(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x59 => {
+                // EOR Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.eor(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x41 => {
+                // EOR (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.eor(bus, op);
+            }
+            0x51 => {
+                // EOR (Indirect),Y
+                let (op, page_crossed) = self.fetch_indirect_indexed(bus);
+                self.eor(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- ADC: Add with Carry ----
+            0x69 => {
+                // ADC Immediate
+                let op = self.fetch_immediate(bus);
+                self.adc(bus, op);
+            }
+            0x65 => {
+                // ADC Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.adc(bus, op);
+            }
+            0x75 => {
+                // ADC Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.adc(bus, op);
+            }
+            0x6d => {
+                // ADC Absolute
+                let op = self.fetch_absolute(bus);
+                self.adc(bus, op);
+            }
+            0x7d => {
+                // ADC Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.adc(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x79 => {
+                // ADC Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.adc(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x61 => {
+                // ADC (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.adc(bus, op);
+            }
+            0x71 => {
+                // ADC (Indirect),Y
+                let (op, page_crossed) = self.fetch_indirect_indexed(bus);
+                self.adc(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- SBC: Subtract with Borrow ----
+            0xe9 => {
+                // SBC Immediate
+                let op = self.fetch_immediate(bus);
+                self.sbc(bus, op);
+            }
+            0xe5 => {
+                // SBC Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.sbc(bus, op);
+            }
+            0xf5 => {
+                // SBC Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.sbc(bus, op);
+            }
+            0xed => {
+                // SBC Absolute
+                let op = self.fetch_absolute(bus);
+                self.sbc(bus, op);
+            }
+            0xfd => {
+                // SBC Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.sbc(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0xf9 => {
+                // SBC Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.sbc(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0xe1 => {
+                // SBC (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.sbc(bus, op);
+            }
+            0xf1 => {
+                // SBC (Indirect),Y
+                let (op, page_crossed) = self.fetch_indirect_indexed(bus);
+                self.sbc(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- CMP: Compare accumulator ----
+            0xc9 => {
+                // CMP Immediate
+                let op = self.fetch_immediate(bus);
+                self.cmp(bus, op);
+            }
+            0xc5 => {
+                // CMP Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.cmp(bus, op);
+            }
+            0xd5 => {
+                // CMP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.cmp(bus, op);
+            }
+            0xcd => {
+                // CMP Absolute
+                let op = self.fetch_absolute(bus);
+                self.cmp(bus, op);
+            }
+            0xdd => {
+                // CMP Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.cmp(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0xd9 => {
+                // CMP Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.cmp(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0xc1 => {
+                // CMP (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.cmp(bus, op);
+            }
+            0xd1 => {
+                // CMP (Indirect),Y
+                let (op, page_crossed) = self.fetch_indirect_indexed(bus);
+                self.cmp(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- CPX: Compare X register ----
+            0xe0 => {
+                // CPX Immediate
+                let op = self.fetch_immediate(bus);
+                self.cpx(bus, op);
+            }
+            0xe4 => {
+                // CPX Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.cpx(bus, op);
+            }
+            0xec => {
+                // CPX Absolute
+                let op = self.fetch_absolute(bus);
+                self.cpx(bus, op);
+            }
+            // ---- CPY: Compare Y register ----
+            0xc0 => {
+                // CPY Immediate
+                let op = self.fetch_immediate(bus);
+                self.cpy(bus, op);
+            }
+            0xc4 => {
+                // CPY Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.cpy(bus, op);
+            }
+            0xcc => {
+                // CPY Absolute
+                let op = self.fetch_absolute(bus);
+                self.cpy(bus, op);
+            }
+            // ---- BIT: Bit test ----
+            0x24 => {
+                // BIT Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.bit(bus, op);
+            }
+            0x2c => {
+                // BIT Absolute
+                let op = self.fetch_absolute(bus);
+                self.bit(bus, op);
+            }
+            // ---- ASL: Arithmetic shift left ----
+            0x0a => {
+                // ASL Accumulator
+                let op = self.fetch_implied();
+                self.asl(bus, op);
+            }
+            0x06 => {
+                // ASL Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.asl(bus, op);
+            }
+            0x16 => {
+                // ASL Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.asl(bus, op);
+            }
+            0x0e => {
+                // ASL Absolute
+                let op = self.fetch_absolute(bus);
+                self.asl(bus, op);
+            }
+            0x1e => {
+                // ASL Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.asl(bus, op);
+            }
+            // ---- LSR: Logical shift right ----
+            0x4a => {
+                // LSR Accumulator
+                let op = self.fetch_implied();
+                self.lsr(bus, op);
+            }
+            0x46 => {
+                // LSR Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.lsr(bus, op);
+            }
+            0x56 => {
+                // LSR Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.lsr(bus, op);
+            }
+            0x4e => {
+                // LSR Absolute
+                let op = self.fetch_absolute(bus);
+                self.lsr(bus, op);
+            }
+            0x5e => {
+                // LSR Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.lsr(bus, op);
+            }
+            // ---- ROL: Rotate left ----
+            0x2a => {
+                // ROL Accumulator
+                let op = self.fetch_implied();
+                self.rol(bus, op);
+            }
+            0x26 => {
+                // ROL Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.rol(bus, op);
+            }
+            0x36 => {
+                // ROL Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.rol(bus, op);
+            }
+            0x2e => {
+                // ROL Absolute
+                let op = self.fetch_absolute(bus);
+                self.rol(bus, op);
+            }
+            0x3e => {
+                // ROL Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.rol(bus, op);
+            }
+            // ---- ROR: Rotate right ----
+            0x6a => {
+                // ROR Accumulator
+                let op = self.fetch_implied();
+                self.ror(bus, op);
+            }
+            0x66 => {
+                // ROR Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.ror(bus, op);
+            }
+            0x76 => {
+                // ROR Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.ror(bus, op);
+            }
+            0x6e => {
+                // ROR Absolute
+                let op = self.fetch_absolute(bus);
+                self.ror(bus, op);
+            }
+            0x7e => {
+                // ROR Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.ror(bus, op);
+            }
+            // ---- INC: Increment memory ----
+            0xe6 => {
+                // INC Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.inc(bus, op);
+            }
+            0xf6 => {
+                // INC Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.inc(bus, op);
+            }
+            0xee => {
+                // INC Absolute
+                let op = self.fetch_absolute(bus);
+                self.inc(bus, op);
+            }
+            0xfe => {
+                // INC Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.inc(bus, op);
+            }
+            // ---- DEC: Decrement memory ----
+            0xc6 => {
+                // DEC Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.dec(bus, op);
+            }
+            0xd6 => {
+                // DEC Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.dec(bus, op);
+            }
+            0xce => {
+                // DEC Absolute
+                let op = self.fetch_absolute(bus);
+                self.dec(bus, op);
+            }
+            0xde => {
+                // DEC Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.dec(bus, op);
+            }
+            // ---- Register transfers ----
             0xaa => {
                 // TAX: Transfer accumulator to X register (implied)
                 self.fetch_implied(); // advance PC
                 self.register_x = self.register_a;
                 self.update_zero_and_negative_flags(self.register_x);
             }
+            0xa8 => {
+                // TAY: Transfer accumulator to Y register
+                self.fetch_implied();
+                self.register_y = self.register_a;
+                self.update_zero_and_negative_flags(self.register_y);
+            }
+            0x8a => {
+                // TXA: Transfer X register to accumulator
+                self.fetch_implied();
+                self.register_a = self.register_x;
+                self.update_zero_and_negative_flags(self.register_a);
+            }
+            0x98 => {
+                // TYA: Transfer Y register to accumulator
+                self.fetch_implied();
+                self.register_a = self.register_y;
+                self.update_zero_and_negative_flags(self.register_a);
+            }
+            0xba => {
+                // TSX: Transfer stack pointer to X register
+                self.fetch_implied();
+                self.register_x = self.stack_pointer;
+                self.update_zero_and_negative_flags(self.register_x);
+            }
+            0x9a => {
+                // TXS: Transfer X register to stack pointer (no flags affected)
+                self.fetch_implied();
+                self.stack_pointer = self.register_x;
+            }
+            // ---- Register increments/decrements ----
             0xe8 => {
                 // INX: Increment X register (implied)
                 self.fetch_implied();
                 self.register_x = self.register_x.wrapping_add(1);
                 self.update_zero_and_negative_flags(self.register_x);
             }
-            0x8d => {
-                // STA Absolute: Store accumulator to memory address
-                if let Operand::Address(addr) = self.fetch_absolute(bus) {
-                    bus.write(addr, self.register_a);
-                }
+            0xc8 => {
+                // INY: Increment Y register
+                self.fetch_implied();
+                self.register_y = self.register_y.wrapping_add(1);
+                self.update_zero_and_negative_flags(self.register_y);
             }
-            0x85 => {
-                // STA Zero Page
-                if let Operand::Address(addr) = self.fetch_zeropage(bus) {
-                    bus.write(addr, self.register_a);
-                }
+            0xca => {
+                // DEX: Decrement X register
+                self.fetch_implied();
+                self.register_x = self.register_x.wrapping_sub(1);
+                self.update_zero_and_negative_flags(self.register_x);
             }
-            0x95 => {
-                // STA Zero Page,X
-                if let Operand::Address(addr) = self.fetch_zeropage_x(bus) {
-                    bus.write(addr, self.register_a);
-                }
+            0x88 => {
+                // DEY: Decrement Y register
+                self.fetch_implied();
+                self.register_y = self.register_y.wrapping_sub(1);
+                self.update_zero_and_negative_flags(self.register_y);
             }
+            // ---- Jumps ----
             0x4c => {
                 // JMP Absolute: Jump to new address
                 if let Operand::Address(addr) = self.fetch_absolute(bus) {
@@ -589,6 +1919,7 @@ impl CPU {
                     self.program_counter = addr;
                 }
             }
+            // ---- Branches ----
             0xf0 => {
                 // BEQ: Branch if equal (zero flag set)
                 let offset = self.fetch_relative_offset(bus);
@@ -631,25 +1962,35 @@ impl CPU {
                     self.branch_with_cycles(offset);
                 }
             }
+            0x50 => {
+                // BVC: Branch if overflow clear
+                let offset = self.fetch_relative_offset(bus);
+                if !self.get_flag(FLAG_OVERFLOW) {
+                    self.branch_with_cycles(offset);
+                }
+            }
+            0x70 => {
+                // BVS: Branch if overflow set
+                let offset = self.fetch_relative_offset(bus);
+                if self.get_flag(FLAG_OVERFLOW) {
+                    self.branch_with_cycles(offset);
+                }
+            }
+            // ---- Stack, subroutines and interrupts ----
             0x00 => {
                 // BRK: Force interrupt
-                // On BRK the return address pushed is PC+2 and break flag is set for pushed flags.
-                // We emulate the basic behavior here.
-                self.program_counter = self.program_counter.wrapping_add(1);
+                // BRK is a 2-byte instruction (opcode + padding byte), so the
+                // return address pushed is PC+2 and the break flag is set for pushed flags.
+                self.program_counter = self.program_counter.wrapping_add(2);
 
                 // Push PC and status (break flag set)
                 self.push_word(bus, self.program_counter);
                 self.push_byte(bus, self.status | FLAG_BREAK | FLAG_UNUSED);
 
                 self.set_flag(FLAG_INTERRUPT, true);
-                self.set_flag(FLAG_BREAK, false);
 
                 // Jump to IRQ/BRK vector
-                let lo = bus.read(IRQ_VECTOR) as u16;
-                let hi = bus.read(IRQ_VECTOR + 1) as u16;
-                self.program_counter = (hi << 8) | lo;
-
-                return;
+                self.program_counter = self.read_u16(bus, IRQ_VECTOR);
             }
             0x48 => {
                 // PHA: Push accumulator to stack
@@ -701,47 +2042,540 @@ impl CPU {
                 self.status = (self.pop_byte(bus) & 0b1100_1111) | 0b0010_0000;
                 self.program_counter = self.pop_word(bus);
             }
-            0x69 => {
-                // ADC - Add with Carry (immediate)
-                if let Operand::Immediate(operand) = self.fetch_immediate(bus) {
-                    self.register_a = self.adc_binary(operand);
-                }
+            // ---- Status flag instructions ----
+            0x18 => {
+                // CLC: Clear carry flag
+                self.fetch_implied();
+                self.set_flag(FLAG_CARRY, false);
             }
-            0x6d => {
-                // ADC - Add with Carry (absolute)
-                if let Operand::Address(addr) = self.fetch_absolute(bus) {
-                    let operand = bus.read(addr);
-                    self.register_a = self.adc_binary(operand);
-                }
+            0x38 => {
+                // SEC: Set carry flag
+                self.fetch_implied();
+                self.set_flag(FLAG_CARRY, true);
             }
-            0x65 => {
-                // ADC - Add with Carry (Zero Page)
-                if let Operand::Address(addr) = self.fetch_zeropage(bus) {
-                    let operand = bus.read(addr);
-                    self.register_a = self.adc_binary(operand);
-                }
+            0x58 => {
+                // CLI: Clear interrupt disable flag
+                self.fetch_implied();
+                self.set_flag(FLAG_INTERRUPT, false);
             }
-            0x75 => {
-                // ADC - Add with Carry (Zero Page,X)
-                if let Operand::Address(addr) = self.fetch_zeropage_x(bus) {
-                    let operand = bus.read(addr);
-                    self.register_a = self.adc_binary(operand);
-                }
+            0x78 => {
+                // SEI: Set interrupt disable flag
+                self.fetch_implied();
+                self.set_flag(FLAG_INTERRUPT, true);
             }
-            0xe9 => {
-                // SBC - Subtract with Borrow (immediate)
-                if let Operand::Immediate(operand) = self.fetch_immediate(bus) {
-                    self.register_a = self.sbc_binary(operand);
-                }
+            0xb8 => {
+                // CLV: Clear overflow flag
+                self.fetch_implied();
+                self.set_flag(FLAG_OVERFLOW, false);
             }
-            0xed => {
-                // SBC - Subtract with Borrow (absolute)
-                if let Operand::Address(addr) = self.fetch_absolute(bus) {
-                    let operand = bus.read(addr);
-                    self.register_a = self.sbc_binary(operand);
-                }
+            0xd8 => {
+                // CLD: Clear decimal mode flag
+                self.fetch_implied();
+                self.set_flag(FLAG_DECIMAL, false);
+            }
+            0xf8 => {
+                // SED: Set decimal mode flag
+                self.fetch_implied();
+                self.set_flag(FLAG_DECIMAL, true);
+            }
+            0xea => {
+                // NOP: No operation
+                self.fetch_implied();
+            }
+            // ---- SLO: ASL + ORA ----
+            0x07 => {
+                // SLO Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.slo(bus, op);
+            }
+            0x17 => {
+                // SLO Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.slo(bus, op);
+            }
+            0x0f => {
+                // SLO Absolute
+                let op = self.fetch_absolute(bus);
+                self.slo(bus, op);
+            }
+            0x1f => {
+                // SLO Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.slo(bus, op);
+            }
+            0x1b => {
+                // SLO Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.slo(bus, op);
+            }
+            0x03 => {
+                // SLO (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.slo(bus, op);
+            }
+            0x13 => {
+                // SLO (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.slo(bus, op);
+            }
+            // ---- RLA: ROL + AND ----
+            0x27 => {
+                // RLA Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.rla(bus, op);
+            }
+            0x37 => {
+                // RLA Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.rla(bus, op);
+            }
+            0x2f => {
+                // RLA Absolute
+                let op = self.fetch_absolute(bus);
+                self.rla(bus, op);
+            }
+            0x3f => {
+                // RLA Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.rla(bus, op);
+            }
+            0x3b => {
+                // RLA Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.rla(bus, op);
+            }
+            0x23 => {
+                // RLA (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.rla(bus, op);
+            }
+            0x33 => {
+                // RLA (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.rla(bus, op);
+            }
+            // ---- SRE: LSR + EOR ----
+            0x47 => {
+                // SRE Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.sre(bus, op);
+            }
+            0x57 => {
+                // SRE Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.sre(bus, op);
+            }
+            0x4f => {
+                // SRE Absolute
+                let op = self.fetch_absolute(bus);
+                self.sre(bus, op);
+            }
+            0x5f => {
+                // SRE Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.sre(bus, op);
+            }
+            0x5b => {
+                // SRE Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.sre(bus, op);
+            }
+            0x43 => {
+                // SRE (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.sre(bus, op);
+            }
+            0x53 => {
+                // SRE (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.sre(bus, op);
+            }
+            // ---- RRA: ROR + ADC ----
+            0x67 => {
+                // RRA Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.rra(bus, op);
+            }
+            0x77 => {
+                // RRA Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.rra(bus, op);
+            }
+            0x6f => {
+                // RRA Absolute
+                let op = self.fetch_absolute(bus);
+                self.rra(bus, op);
+            }
+            0x7f => {
+                // RRA Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.rra(bus, op);
+            }
+            0x7b => {
+                // RRA Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.rra(bus, op);
+            }
+            0x63 => {
+                // RRA (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.rra(bus, op);
+            }
+            0x73 => {
+                // RRA (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.rra(bus, op);
+            }
+            // ---- DCP: DEC + CMP ----
+            0xc7 => {
+                // DCP Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.dcp(bus, op);
             }
-            _ => panic!("Opcode {:#x} not implemented", opcode),
+            0xd7 => {
+                // DCP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.dcp(bus, op);
+            }
+            0xcf => {
+                // DCP Absolute
+                let op = self.fetch_absolute(bus);
+                self.dcp(bus, op);
+            }
+            0xdf => {
+                // DCP Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.dcp(bus, op);
+            }
+            0xdb => {
+                // DCP Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.dcp(bus, op);
+            }
+            0xc3 => {
+                // DCP (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.dcp(bus, op);
+            }
+            0xd3 => {
+                // DCP (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.dcp(bus, op);
+            }
+            // ---- ISC: INC + SBC ----
+            0xe7 => {
+                // ISC Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.isc(bus, op);
+            }
+            0xf7 => {
+                // ISC Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.isc(bus, op);
+            }
+            0xef => {
+                // ISC Absolute
+                let op = self.fetch_absolute(bus);
+                self.isc(bus, op);
+            }
+            0xff => {
+                // ISC Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.isc(bus, op);
+            }
+            0xfb => {
+                // ISC Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.isc(bus, op);
+            }
+            0xe3 => {
+                // ISC (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.isc(bus, op);
+            }
+            0xf3 => {
+                // ISC (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.isc(bus, op);
+            }
+            // ---- SAX: Store A & X ----
+            0x87 => {
+                // SAX Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.sax(bus, op);
+            }
+            0x97 => {
+                // SAX Zero Page,Y
+                let op = self.fetch_zeropage_y(bus);
+                self.sax(bus, op);
+            }
+            0x8f => {
+                // SAX Absolute
+                let op = self.fetch_absolute(bus);
+                self.sax(bus, op);
+            }
+            0x83 => {
+                // SAX (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.sax(bus, op);
+            }
+            // ---- LAX: Load A and X ----
+            0xa7 => {
+                // LAX Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.lax(bus, op);
+            }
+            0xb7 => {
+                // LAX Zero Page,Y
+                let op = self.fetch_zeropage_y(bus);
+                self.lax(bus, op);
+            }
+            0xaf => {
+                // LAX Absolute
+                let op = self.fetch_absolute(bus);
+                self.lax(bus, op);
+            }
+            0xbf => {
+                // LAX Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.lax(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0xa3 => {
+                // LAX (Indirect,X)
+                let op = self.fetch_indexed_indirect(bus);
+                self.lax(bus, op);
+            }
+            0xb3 => {
+                // LAX (Indirect),Y
+                let (op, page_crossed) = self.fetch_indirect_indexed(bus);
+                self.lax(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- ANC: AND + copy N to C ----
+            0x0b => {
+                // ANC Immediate
+                let op = self.fetch_immediate(bus);
+                self.anc(bus, op);
+            }
+            0x2b => {
+                // ANC Immediate
+                let op = self.fetch_immediate(bus);
+                self.anc(bus, op);
+            }
+            // ---- ALR: AND + LSR ----
+            0x4b => {
+                // ALR Immediate
+                let op = self.fetch_immediate(bus);
+                self.alr(bus, op);
+            }
+            // ---- ARR: AND + ROR ----
+            0x6b => {
+                // ARR Immediate
+                let op = self.fetch_immediate(bus);
+                self.arr(bus, op);
+            }
+            // ---- AXS: X = (A & X) - M ----
+            0xcb => {
+                // AXS Immediate
+                let op = self.fetch_immediate(bus);
+                self.axs(bus, op);
+            }
+            // ---- SBC: Subtract with Borrow (undocumented duplicate of $E9) ----
+            0xeb => {
+                // SBC Immediate
+                let op = self.fetch_immediate(bus);
+                self.sbc(bus, op);
+            }
+            // ---- XAA: unstable, A = (A | magic) & X & M ----
+            0x8b => {
+                // XAA Immediate
+                let op = self.fetch_immediate(bus);
+                self.xaa(bus, op);
+            }
+            // ---- LXA: unstable, A = X = (A | magic) & M ----
+            0xab => {
+                // LXA Immediate
+                let op = self.fetch_immediate(bus);
+                self.lxa(bus, op);
+            }
+            // ---- LAS: unstable, A = X = SP = M & SP ----
+            0xbb => {
+                // LAS Absolute,Y
+                let (op, page_crossed) = self.fetch_absolute_y(bus);
+                self.las(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- AHX: unstable, M = A & X & (H + 1) ----
+            0x93 => {
+                // AHX (Indirect),Y
+                let (op, _) = self.fetch_indirect_indexed(bus);
+                self.ahx(bus, op);
+            }
+            0x9f => {
+                // AHX Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.ahx(bus, op);
+            }
+            // ---- TAS: unstable, SP = A & X, M = SP & (H + 1) ----
+            0x9b => {
+                // TAS Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.tas(bus, op);
+            }
+            // ---- SHX: unstable, M = X & (H + 1) ----
+            0x9e => {
+                // SHX Absolute,Y
+                let (op, _) = self.fetch_absolute_y(bus);
+                self.shx(bus, op);
+            }
+            // ---- SHY: unstable, M = Y & (H + 1) ----
+            0x9c => {
+                // SHY Absolute,X
+                let (op, _) = self.fetch_absolute_x(bus);
+                self.shy(bus, op);
+            }
+            // ---- NOP: multi-byte NOPs ----
+            0x80 => {
+                // NOP Immediate
+                let op = self.fetch_immediate(bus);
+                self.nop_read(bus, op);
+            }
+            0x82 => {
+                // NOP Immediate
+                let op = self.fetch_immediate(bus);
+                self.nop_read(bus, op);
+            }
+            0x89 => {
+                // NOP Immediate
+                let op = self.fetch_immediate(bus);
+                self.nop_read(bus, op);
+            }
+            0xc2 => {
+                // NOP Immediate
+                let op = self.fetch_immediate(bus);
+                self.nop_read(bus, op);
+            }
+            0xe2 => {
+                // NOP Immediate
+                let op = self.fetch_immediate(bus);
+                self.nop_read(bus, op);
+            }
+            0x04 => {
+                // NOP Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.nop_read(bus, op);
+            }
+            0x44 => {
+                // NOP Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.nop_read(bus, op);
+            }
+            0x64 => {
+                // NOP Zero Page
+                let op = self.fetch_zeropage(bus);
+                self.nop_read(bus, op);
+            }
+            0x14 => {
+                // NOP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.nop_read(bus, op);
+            }
+            0x34 => {
+                // NOP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.nop_read(bus, op);
+            }
+            0x54 => {
+                // NOP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.nop_read(bus, op);
+            }
+            0x74 => {
+                // NOP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.nop_read(bus, op);
+            }
+            0xd4 => {
+                // NOP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.nop_read(bus, op);
+            }
+            0xf4 => {
+                // NOP Zero Page,X
+                let op = self.fetch_zeropage_x(bus);
+                self.nop_read(bus, op);
+            }
+            0x0c => {
+                // NOP Absolute
+                let op = self.fetch_absolute(bus);
+                self.nop_read(bus, op);
+            }
+            0x1c => {
+                // NOP Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.nop_read(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x3c => {
+                // NOP Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.nop_read(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x5c => {
+                // NOP Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.nop_read(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0x7c => {
+                // NOP Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.nop_read(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0xdc => {
+                // NOP Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.nop_read(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            0xfc => {
+                // NOP Absolute,X
+                let (op, page_crossed) = self.fetch_absolute_x(bus);
+                self.nop_read(bus, op);
+                self.add_page_cross_cycle(page_crossed);
+            }
+            // ---- NOP: undocumented single-byte NOPs ----
+            0x1a | 0x3a | 0x5a | 0x7a | 0xda | 0xfa => {
+                // NOP Implied
+                self.fetch_implied();
+            }
+            // ---- KIL: Halt the CPU ----
+            0x02 | 0x12 | 0x22 | 0x32 | 0x42 | 0x52 | 0x62 | 0x72 | 0x92 | 0xb2 | 0xd2 | 0xf2 => {
+                // KIL (JAM): the CPU locks up until the next reset.
+                // PC is left pointing at the KIL opcode so the host can see where it stopped.
+                self.jammed = true;
+            }
+        }
+
+        // Base cost of the instruction; penalties were added while executing it
+        let base_cycles = BASE_CYCLES[opcode as usize] as u64;
+        self.cycles = self.cycles.wrapping_add(base_cycles);
+        let cycles = self.cycles.wrapping_sub(start_cycles);
+
+        if self.jammed {
+            return Err(CpuError::Jammed { pc, opcode });
         }
+
+        if opcode == 0x00 {
+            return Ok(StepOutcome::Break { pc, cycles });
+        }
+
+        Ok(StepOutcome::Instruction { pc, opcode, cycles })
     }
 }
+
+#[cfg(test)]
+mod tests;
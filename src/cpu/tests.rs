@@ -0,0 +1,1148 @@
+//! Unit tests of the instruction semantics.
+//!
+//! Every case is a small program run on a test bus. Instructions whose result
+//! only depends on their operand are table-driven: `execute` places the operand
+//! where the opcode's addressing mode reads it, so one table covers every
+//! opcode of a mnemonic.
+
+use super::*;
+use crate::bus::access::AccessKind;
+use crate::bus::flat::FlatBus;
+use crate::bus::recording::RecordingBus;
+use crate::disassembler::{AddrMode, OPCODES};
+use crate::fixture::{IRQ_HANDLER, NMI_HANDLER, ORIGIN, machine};
+
+/// X and Y while a table-driven opcode runs
+const INDEX: u8 = 0x04;
+
+/// Operand location of the zero page modes
+const ZP: u8 = 0x10;
+
+/// Operand location of the other memory modes
+const ABS: u16 = 0x0310;
+
+/// ($1C,X) reads the pointer to `ABS` here
+const PTR_X: u8 = 0x20;
+
+/// ($30),Y reads a pointer to `ABS - INDEX` here
+const PTR_Y: u8 = 0x30;
+
+/// Upper bound for `run_to`, so a broken branch fails instead of hanging
+const MAX_STEPS: usize = 1000;
+
+#[track_caller]
+fn step(cpu: &mut CPU, bus: &mut impl Bus) -> StepOutcome {
+    cpu.run_once(bus).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Steps until the PC reaches `end`
+#[track_caller]
+fn run_to(cpu: &mut CPU, bus: &mut impl Bus, end: u16) {
+    for _ in 0..MAX_STEPS {
+        if cpu.program_counter == end {
+            return;
+        }
+        step(cpu, bus);
+    }
+    panic!(
+        "PC never reached ${:04X}, stuck at ${:04X}",
+        end, cpu.program_counter
+    );
+}
+
+/// Runs `program` from `ORIGIN` until it falls off its end
+#[track_caller]
+fn run(program: &[u8]) -> (CPU, FlatBus) {
+    let (mut cpu, mut bus) = machine(program);
+    run_to(&mut cpu, &mut bus, ORIGIN + program.len() as u16);
+    (cpu, bus)
+}
+
+/// The documented and stable undocumented opcodes of `mnemonic`
+fn opcodes(mnemonic: &str) -> Vec<u8> {
+    (0..=0xffu8)
+        .filter(|opcode| OPCODES[*opcode as usize].mnemonic == mnemonic)
+        .filter(|opcode| !UNSTABLE_OPCODES.contains(opcode))
+        .collect()
+}
+
+/// "ADC ZpX ($75)", for assertion messages
+fn describe(opcode: u8) -> String {
+    let info = &OPCODES[opcode as usize];
+    format!("{} {:?} (${:02X})", info.mnemonic, info.mode, opcode)
+}
+
+/// Operand bytes that make `mode` read `value`: `ZP` for the zero page modes,
+/// `ABS` for the other memory modes (with X = Y = `INDEX`)
+fn operand_bytes(mode: AddrMode, value: u8) -> Vec<u8> {
+    let [lo, hi] = ABS.to_le_bytes();
+    let [base_lo, base_hi] = (ABS - INDEX as u16).to_le_bytes();
+    match mode {
+        AddrMode::Imp | AddrMode::Acc => vec![],
+        AddrMode::Imm => vec![value],
+        AddrMode::Zp => vec![ZP],
+        AddrMode::ZpX | AddrMode::ZpY => vec![ZP - INDEX],
+        AddrMode::Abs => vec![lo, hi],
+        AddrMode::AbsX | AddrMode::AbsY => vec![base_lo, base_hi],
+        AddrMode::XInd => vec![PTR_X - INDEX],
+        AddrMode::IndY => vec![PTR_Y],
+        AddrMode::Ind | AddrMode::Rel => panic!("no table-driven operand for {:?}", mode),
+    }
+}
+
+/// The address a memory operand of `mode` ends up at
+fn target(mode: AddrMode) -> u16 {
+    match mode {
+        AddrMode::Zp | AddrMode::ZpX | AddrMode::ZpY => ZP as u16,
+        _ => ABS,
+    }
+}
+
+/// Executes `opcode` once with `value` as its operand: in the instruction
+/// for immediate mode, in A for accumulator mode, in memory otherwise.
+/// P starts out as just the unused bit and X = Y = `INDEX`; `prepare` can
+/// change the registers before the instruction runs.
+#[track_caller]
+fn execute(opcode: u8, value: u8, prepare: impl FnOnce(&mut CPU)) -> (CPU, FlatBus) {
+    let mode = OPCODES[opcode as usize].mode;
+    let mut program = vec![opcode];
+    program.extend(operand_bytes(mode, value));
+
+    let (mut cpu, mut bus) = machine(&program);
+    bus.write(ZP as u16, value);
+    bus.write(ABS, value);
+    bus.load(PTR_X as u16, &ABS.to_le_bytes());
+    bus.load(PTR_Y as u16, &(ABS - INDEX as u16).to_le_bytes());
+    cpu.register_x = INDEX;
+    cpu.register_y = INDEX;
+    cpu.status = FLAG_UNUSED;
+    prepare(&mut cpu);
+    if matches!(mode, AddrMode::Acc) {
+        cpu.register_a = value;
+    }
+
+    step(&mut cpu, &mut bus);
+    assert_eq!(
+        cpu.program_counter,
+        ORIGIN + program.len() as u16,
+        "{}: wrong PC",
+        describe(opcode)
+    );
+    (cpu, bus)
+}
+
+/// What a read-modify-write `opcode` left in its operand
+fn modified(opcode: u8, cpu: &CPU, bus: &FlatBus) -> u8 {
+    match OPCODES[opcode as usize].mode {
+        AddrMode::Acc => cpu.register_a,
+        mode => bus.peek(target(mode)),
+    }
+}
+
+//
+// ---- Loads, stores and transfers ----
+//
+
+#[test]
+fn loads_set_register_and_nz() {
+    // (value, N Z)
+    let cases = [(0x42, 0), (0x00, FLAG_ZERO), (0x80, FLAG_NEGATIVE)];
+    for mnemonic in ["LDA", "LDX", "LDY", "LAX"] {
+        for opcode in opcodes(mnemonic) {
+            for (value, flags) in cases {
+                // C and V must survive
+                let (cpu, _) = execute(opcode, value, |cpu| {
+                    cpu.status |= FLAG_CARRY | FLAG_OVERFLOW;
+                });
+                let register = match mnemonic {
+                    "LDX" => cpu.register_x,
+                    "LDY" => cpu.register_y,
+                    _ => cpu.register_a,
+                };
+                let name = describe(opcode);
+                assert_eq!(register, value, "{}", name);
+                if mnemonic == "LAX" {
+                    assert_eq!(cpu.register_x, value, "{}", name);
+                }
+                assert_eq!(
+                    cpu.status,
+                    FLAG_UNUSED | FLAG_CARRY | FLAG_OVERFLOW | flags,
+                    "{} #${:02X}",
+                    name,
+                    value
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn stores_write_memory_without_flags() {
+    // A is chosen so that A & X (SAX) is nonzero
+    for (mnemonic, stored) in [
+        ("STA", 0x9e),
+        ("STX", INDEX),
+        ("STY", INDEX),
+        ("SAX", INDEX),
+    ] {
+        for opcode in opcodes(mnemonic) {
+            let (cpu, bus) = execute(opcode, 0x00, |cpu| {
+                cpu.register_a = 0x9e;
+                cpu.status = FLAG_UNUSED | FLAG_ZERO;
+            });
+            let mode = OPCODES[opcode as usize].mode;
+            assert_eq!(bus.peek(target(mode)), stored, "{}", describe(opcode));
+            assert_eq!(cpu.status, FLAG_UNUSED | FLAG_ZERO, "{}", describe(opcode));
+        }
+    }
+}
+
+#[test]
+fn register_transfers() {
+    // LDA #$80; TAX; LDA #$00; TAY; TXA
+    let (cpu, _) = run(&[0xa9, 0x80, 0xaa, 0xa9, 0x00, 0xa8, 0x8a]);
+    assert_eq!(
+        (cpu.register_a, cpu.register_x, cpu.register_y),
+        (0x80, 0x80, 0x00)
+    );
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), FLAG_NEGATIVE);
+
+    // LDY #$00; TYA
+    let (cpu, _) = run(&[0xa0, 0x00, 0x98]);
+    assert_eq!(cpu.register_a, 0x00);
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), FLAG_ZERO);
+
+    // TSX copies SP and sets N
+    let (cpu, _) = run(&[0xba]);
+    assert_eq!(cpu.register_x, 0xfd);
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), FLAG_NEGATIVE);
+
+    // TXS leaves the flags alone: LDX #$00; TXS keeps Z, LDX #$00; LDY #$01; TXS doesn't set it
+    let (cpu, _) = run(&[0xa2, 0x00, 0x9a]);
+    assert_eq!(cpu.stack_pointer, 0x00);
+    assert_eq!(cpu.status & FLAG_ZERO, FLAG_ZERO);
+    let (cpu, _) = run(&[0xa2, 0x00, 0xa0, 0x01, 0x9a]);
+    assert_eq!(cpu.stack_pointer, 0x00);
+    assert_eq!(cpu.status & FLAG_ZERO, 0);
+}
+
+#[test]
+fn register_increments_wrap() {
+    // LDX #$FF; INX; LDY #$7F; INY
+    let (cpu, _) = run(&[0xa2, 0xff, 0xe8, 0xa0, 0x7f, 0xc8]);
+    assert_eq!((cpu.register_x, cpu.register_y), (0x00, 0x80));
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), FLAG_NEGATIVE);
+
+    // LDX #$00; DEX
+    let (cpu, _) = run(&[0xa2, 0x00, 0xca]);
+    assert_eq!(cpu.register_x, 0xff);
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), FLAG_NEGATIVE);
+
+    // LDY #$01; DEY
+    let (cpu, _) = run(&[0xa0, 0x01, 0x88]);
+    assert_eq!(cpu.register_y, 0x00);
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), FLAG_ZERO);
+}
+
+#[test]
+fn flag_instructions() {
+    // (program, flag, expected)
+    let cases: [(&[u8], u8, bool); 7] = [
+        (&[0x38], FLAG_CARRY, true),                             // SEC
+        (&[0x38, 0x18], FLAG_CARRY, false),                      // SEC; CLC
+        (&[0x78], FLAG_INTERRUPT, true),                         // SEI
+        (&[0x58], FLAG_INTERRUPT, false),                        // CLI
+        (&[0xf8], FLAG_DECIMAL, true),                           // SED
+        (&[0xf8, 0xd8], FLAG_DECIMAL, false),                    // SED; CLD
+        (&[0xa9, 0x40, 0x69, 0x40, 0xb8], FLAG_OVERFLOW, false), // V set by ADC; CLV
+    ];
+    for (program, flag, set) in cases {
+        let (cpu, _) = run(program);
+        assert_eq!(cpu.status & flag != 0, set, "{:02X?}", program);
+        assert_eq!(cpu.status & FLAG_UNUSED, FLAG_UNUSED);
+    }
+}
+
+//
+// ---- Arithmetic ----
+//
+
+#[test]
+fn adc_binary_carry_and_overflow() {
+    // (A, C in, M, result, N V Z C)
+    let cases = [
+        (0x00, 0, 0x00, 0x00, FLAG_ZERO),
+        (0x01, 0, 0x01, 0x02, 0),
+        (0x01, 1, 0x01, 0x03, 0),
+        (0x7f, 0, 0x01, 0x80, FLAG_NEGATIVE | FLAG_OVERFLOW),
+        (0x7f, 1, 0x00, 0x80, FLAG_NEGATIVE | FLAG_OVERFLOW),
+        (0x50, 0, 0x50, 0xa0, FLAG_NEGATIVE | FLAG_OVERFLOW),
+        (0x80, 0, 0xff, 0x7f, FLAG_OVERFLOW | FLAG_CARRY),
+        (0xd0, 0, 0x90, 0x60, FLAG_OVERFLOW | FLAG_CARRY),
+        (0x50, 0, 0xd0, 0x20, FLAG_CARRY),
+        (0xff, 0, 0x01, 0x00, FLAG_ZERO | FLAG_CARRY),
+        (0xff, 1, 0xff, 0xff, FLAG_NEGATIVE | FLAG_CARRY),
+    ];
+    for opcode in opcodes("ADC") {
+        for (a, carry, m, result, flags) in cases {
+            let (cpu, _) = execute(opcode, m, |cpu| {
+                cpu.register_a = a;
+                cpu.status |= carry;
+            });
+            let case = format!("{}: ${:02X} + ${:02X} + {}", describe(opcode), a, m, carry);
+            assert_eq!(cpu.register_a, result, "{}", case);
+            assert_eq!(cpu.status, FLAG_UNUSED | flags, "{}", case);
+        }
+    }
+}
+
+#[test]
+fn sbc_binary_borrow_and_overflow() {
+    // (A, C in, M, result, N V Z C). C clear means borrow.
+    let cases = [
+        (0x05, 1, 0x03, 0x02, FLAG_CARRY),
+        (0x05, 1, 0x05, 0x00, FLAG_ZERO | FLAG_CARRY),
+        (0x05, 0, 0x05, 0xff, FLAG_NEGATIVE),
+        (0x05, 1, 0x06, 0xff, FLAG_NEGATIVE),
+        (0x00, 0, 0x00, 0xff, FLAG_NEGATIVE),
+        (0x80, 1, 0x01, 0x7f, FLAG_OVERFLOW | FLAG_CARRY),
+        (0x7f, 1, 0xff, 0x80, FLAG_NEGATIVE | FLAG_OVERFLOW),
+        (0x50, 1, 0xb0, 0xa0, FLAG_NEGATIVE | FLAG_OVERFLOW),
+        (0xd0, 1, 0x70, 0x60, FLAG_OVERFLOW | FLAG_CARRY),
+    ];
+    // Includes the undocumented SBC #imm ($EB)
+    for opcode in opcodes("SBC") {
+        for (a, carry, m, result, flags) in cases {
+            let (cpu, _) = execute(opcode, m, |cpu| {
+                cpu.register_a = a;
+                cpu.status |= carry;
+            });
+            let case = format!(
+                "{}: ${:02X} - ${:02X} - {}",
+                describe(opcode),
+                a,
+                m,
+                1 - carry
+            );
+            assert_eq!(cpu.register_a, result, "{}", case);
+            assert_eq!(cpu.status, FLAG_UNUSED | flags, "{}", case);
+        }
+    }
+}
+
+#[test]
+fn decimal_mode_on_nmos() {
+    // (opcode, A, C in, M, result, N V Z C)
+    let cases = [
+        (0x69, 0x09, 0, 0x01, 0x10, 0),
+        (0x69, 0x12, 0, 0x34, 0x46, 0),
+        (
+            0x69,
+            0x58,
+            1,
+            0x46,
+            0x05,
+            FLAG_NEGATIVE | FLAG_OVERFLOW | FLAG_CARRY,
+        ),
+        // N comes from the uncorrected high nibble ($A0), Z from the binary sum ($9A)
+        (0x69, 0x99, 0, 0x01, 0x00, FLAG_NEGATIVE | FLAG_CARRY),
+        // SBC flags are the binary ones
+        (0xe9, 0x46, 1, 0x12, 0x34, FLAG_CARRY),
+        (0xe9, 0x40, 1, 0x13, 0x27, FLAG_CARRY),
+        (0xe9, 0x32, 0, 0x02, 0x29, FLAG_CARRY),
+        (0xe9, 0x00, 1, 0x01, 0x99, FLAG_NEGATIVE),
+    ];
+    for (opcode, a, carry, m, result, flags) in cases {
+        let (cpu, _) = execute(opcode, m, |cpu| {
+            cpu.register_a = a;
+            cpu.status |= FLAG_DECIMAL | carry;
+        });
+        let case = format!("{} ${:02X}, ${:02X}, C={}", describe(opcode), a, m, carry);
+        assert_eq!(cpu.register_a, result, "{}", case);
+        assert_eq!(cpu.status, FLAG_UNUSED | FLAG_DECIMAL | flags, "{}", case);
+    }
+}
+
+#[test]
+fn ricoh_ignores_decimal_flag() {
+    // SED; LDA #$09; CLC; ADC #$01; SEC; SBC #$00
+    let program = [0xf8, 0xa9, 0x09, 0x18, 0x69, 0x01, 0x38, 0xe9, 0x00];
+    let mut bus = FlatBus::with_image(&program, ORIGIN);
+    bus.load(RESET_VECTOR, &ORIGIN.to_le_bytes());
+    let mut cpu = CPU::with_variant(CpuVariant::Ricoh2A03);
+    cpu.reset(&mut bus);
+    run_to(&mut cpu, &mut bus, ORIGIN + program.len() as u16);
+    assert_eq!(cpu.register_a, 0x0a);
+    assert_eq!(cpu.status & FLAG_DECIMAL, FLAG_DECIMAL);
+}
+
+#[test]
+fn compares() {
+    // (register, M, N Z C)
+    let cases = [
+        (0x40, 0x40, FLAG_ZERO | FLAG_CARRY),
+        (0x41, 0x40, FLAG_CARRY),
+        (0x40, 0x41, FLAG_NEGATIVE),
+        (0x00, 0xff, 0),
+        (0xff, 0x00, FLAG_NEGATIVE | FLAG_CARRY),
+    ];
+    for mnemonic in ["CMP", "CPX", "CPY"] {
+        for opcode in opcodes(mnemonic) {
+            for (register, m, flags) in cases {
+                // CPX and CPY have no indexed modes, so X and Y are free
+                let (cpu, _) = execute(opcode, m, |cpu| {
+                    cpu.status |= FLAG_OVERFLOW;
+                    match mnemonic {
+                        "CPX" => cpu.register_x = register,
+                        "CPY" => cpu.register_y = register,
+                        _ => cpu.register_a = register,
+                    }
+                });
+                assert_eq!(
+                    cpu.status,
+                    FLAG_UNUSED | FLAG_OVERFLOW | flags,
+                    "{}: ${:02X} vs ${:02X}",
+                    describe(opcode),
+                    register,
+                    m
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn logic_operations() {
+    // (mnemonic, A, M, result, N Z)
+    let cases = [
+        ("AND", 0xf0, 0x3c, 0x30, 0),
+        ("AND", 0x0f, 0xf0, 0x00, FLAG_ZERO),
+        ("AND", 0xff, 0x80, 0x80, FLAG_NEGATIVE),
+        ("ORA", 0xf0, 0x3c, 0xfc, FLAG_NEGATIVE),
+        ("ORA", 0x00, 0x00, 0x00, FLAG_ZERO),
+        ("ORA", 0x01, 0x02, 0x03, 0),
+        ("EOR", 0xf0, 0x3c, 0xcc, FLAG_NEGATIVE),
+        ("EOR", 0x5a, 0x5a, 0x00, FLAG_ZERO),
+        ("EOR", 0x0f, 0x01, 0x0e, 0),
+    ];
+    for (mnemonic, a, m, result, flags) in cases {
+        for opcode in opcodes(mnemonic) {
+            let (cpu, _) = execute(opcode, m, |cpu| {
+                cpu.register_a = a;
+                cpu.status |= FLAG_CARRY | FLAG_OVERFLOW;
+            });
+            let case = format!("{} ${:02X}, ${:02X}", describe(opcode), a, m);
+            assert_eq!(cpu.register_a, result, "{}", case);
+            assert_eq!(
+                cpu.status,
+                FLAG_UNUSED | FLAG_CARRY | FLAG_OVERFLOW | flags,
+                "{}",
+                case
+            );
+        }
+    }
+}
+
+#[test]
+fn bit_copies_n_v_and_tests_a() {
+    // (A, M, N V Z)
+    let cases = [
+        (0x0f, 0xc0, FLAG_NEGATIVE | FLAG_OVERFLOW | FLAG_ZERO),
+        (0xff, 0x01, 0),
+        (0x40, 0x40, FLAG_OVERFLOW),
+    ];
+    for opcode in opcodes("BIT") {
+        for (a, m, flags) in cases {
+            let (cpu, _) = execute(opcode, m, |cpu| {
+                cpu.register_a = a;
+                cpu.status |= FLAG_NEGATIVE | FLAG_CARRY;
+            });
+            assert_eq!(cpu.register_a, a);
+            assert_eq!(
+                cpu.status,
+                FLAG_UNUSED | FLAG_CARRY | flags,
+                "{} ${:02X}, ${:02X}",
+                describe(opcode),
+                a,
+                m
+            );
+        }
+    }
+}
+
+//
+// ---- Read-modify-write ----
+//
+
+#[test]
+fn shifts_and_rotates() {
+    // (mnemonic, value, C in, result, N Z C)
+    let cases = [
+        ("ASL", 0x81, 0, 0x02, FLAG_CARRY),
+        ("ASL", 0x40, 1, 0x80, FLAG_NEGATIVE),
+        ("ASL", 0x80, 0, 0x00, FLAG_ZERO | FLAG_CARRY),
+        ("LSR", 0x01, 0, 0x00, FLAG_ZERO | FLAG_CARRY),
+        ("LSR", 0x82, 1, 0x41, 0),
+        ("ROL", 0x80, 0, 0x00, FLAG_ZERO | FLAG_CARRY),
+        ("ROL", 0x40, 1, 0x81, FLAG_NEGATIVE),
+        ("ROR", 0x01, 0, 0x00, FLAG_ZERO | FLAG_CARRY),
+        ("ROR", 0x02, 1, 0x81, FLAG_NEGATIVE),
+    ];
+    for (mnemonic, value, carry, result, flags) in cases {
+        for opcode in opcodes(mnemonic) {
+            let (cpu, bus) = execute(opcode, value, |cpu| cpu.status |= carry);
+            let case = format!("{} ${:02X}, C={}", describe(opcode), value, carry);
+            assert_eq!(modified(opcode, &cpu, &bus), result, "{}", case);
+            assert_eq!(cpu.status, FLAG_UNUSED | flags, "{}", case);
+        }
+    }
+}
+
+#[test]
+fn memory_increments_wrap() {
+    // (mnemonic, value, result, N Z)
+    let cases = [
+        ("INC", 0xff, 0x00, FLAG_ZERO),
+        ("INC", 0x7f, 0x80, FLAG_NEGATIVE),
+        ("DEC", 0x01, 0x00, FLAG_ZERO),
+        ("DEC", 0x00, 0xff, FLAG_NEGATIVE),
+    ];
+    for (mnemonic, value, result, flags) in cases {
+        for opcode in opcodes(mnemonic) {
+            let (cpu, bus) = execute(opcode, value, |cpu| cpu.status |= FLAG_CARRY);
+            let case = format!("{} ${:02X}", describe(opcode), value);
+            assert_eq!(modified(opcode, &cpu, &bus), result, "{}", case);
+            assert_eq!(cpu.status, FLAG_UNUSED | FLAG_CARRY | flags, "{}", case);
+        }
+    }
+}
+
+#[test]
+fn undocumented_combined_operations() {
+    // (mnemonic, A, C in, M, memory after, A after, N V Z C)
+    let cases = [
+        ("SLO", 0x10, 0, 0x81, 0x02, 0x12, FLAG_CARRY),
+        ("RLA", 0xff, 1, 0x40, 0x81, 0x81, FLAG_NEGATIVE),
+        ("SRE", 0x80, 0, 0x03, 0x01, 0x81, FLAG_NEGATIVE | FLAG_CARRY),
+        // ROR carries 1 out, which ADC then adds
+        ("RRA", 0x10, 0, 0x03, 0x01, 0x12, 0),
+        ("DCP", 0x40, 0, 0x41, 0x40, 0x40, FLAG_ZERO | FLAG_CARRY),
+        ("ISC", 0x20, 1, 0x0f, 0x10, 0x10, FLAG_CARRY),
+    ];
+    for (mnemonic, a, carry, m, memory, result, flags) in cases {
+        for opcode in opcodes(mnemonic) {
+            let (cpu, bus) = execute(opcode, m, |cpu| {
+                cpu.register_a = a;
+                cpu.status |= carry;
+            });
+            let case = format!("{} ${:02X}, ${:02X}", describe(opcode), a, m);
+            assert_eq!(modified(opcode, &cpu, &bus), memory, "{}", case);
+            assert_eq!(cpu.register_a, result, "{}", case);
+            assert_eq!(cpu.status, FLAG_UNUSED | flags, "{}", case);
+        }
+    }
+}
+
+#[test]
+fn undocumented_immediate_operations() {
+    // (opcode, A, X, C in, M, A after, X after, N V Z C)
+    let cases = [
+        (
+            0x0b,
+            0xff,
+            0x00,
+            0,
+            0x80,
+            0x80,
+            0x00,
+            FLAG_NEGATIVE | FLAG_CARRY,
+        ), // ANC
+        (0x2b, 0xff, 0x00, 1, 0x7f, 0x7f, 0x00, 0), // ANC
+        (0x4b, 0xff, 0x00, 0, 0x03, 0x01, 0x00, FLAG_CARRY), // ALR
+        (
+            0x6b,
+            0xff,
+            0x00,
+            1,
+            0xff,
+            0xff,
+            0x00,
+            FLAG_NEGATIVE | FLAG_CARRY,
+        ), // ARR
+        (0x6b, 0xff, 0x00, 0, 0x40, 0x20, 0x00, FLAG_OVERFLOW), // ARR
+        (0xcb, 0x0f, 0xff, 0, 0x05, 0x0f, 0x0a, FLAG_CARRY), // AXS
+        (0xcb, 0x0f, 0x03, 1, 0x05, 0x0f, 0xfe, FLAG_NEGATIVE), // AXS
+    ];
+    for (opcode, a, x, carry, m, a_after, x_after, flags) in cases {
+        let (cpu, _) = execute(opcode, m, |cpu| {
+            cpu.register_a = a;
+            cpu.register_x = x;
+            cpu.status |= carry;
+        });
+        let case = format!("{} ${:02X}, ${:02X}", describe(opcode), a, m);
+        assert_eq!(
+            (cpu.register_a, cpu.register_x),
+            (a_after, x_after),
+            "{}",
+            case
+        );
+        assert_eq!(cpu.status, FLAG_UNUSED | flags, "{}", case);
+    }
+}
+
+#[test]
+fn unstable_opcodes() {
+    // Emulated with the default magic constant $EE
+    // LDA #$11; LDX #$0F; XAA #$FF -> ($11 | $EE) & $0F & $FF
+    let (cpu, _) = run(&[0xa9, 0x11, 0xa2, 0x0f, 0x8b, 0xff]);
+    assert_eq!(cpu.register_a, 0x0f);
+
+    // LDA #$00; LXA #$F0 -> A = X = ($00 | $EE) & $F0
+    let (cpu, _) = run(&[0xa9, 0x00, 0xab, 0xf0]);
+    assert_eq!((cpu.register_a, cpu.register_x), (0xe0, 0xe0));
+
+    // LDY #$00; LAS $0310,Y -> A = X = SP = $F3 & $FD
+    let (mut cpu, mut bus) = machine(&[0xa0, 0x00, 0xbb, 0x10, 0x03]);
+    bus.write(0x0310, 0xf3);
+    run_to(&mut cpu, &mut bus, ORIGIN + 5);
+    assert_eq!(
+        (cpu.register_a, cpu.register_x, cpu.stack_pointer),
+        (0xf1, 0xf1, 0xf1)
+    );
+
+    // LDX #$FF; LDY #$01; SHX $0310,Y stores X & ($03 + 1)
+    let (_, bus) = run(&[0xa2, 0xff, 0xa0, 0x01, 0x9e, 0x10, 0x03]);
+    assert_eq!(bus.peek(0x0311), 0x04);
+
+    // LDY #$FF; LDX #$01; SHY $0310,X stores Y & ($03 + 1)
+    let (_, bus) = run(&[0xa0, 0xff, 0xa2, 0x01, 0x9c, 0x10, 0x03]);
+    assert_eq!(bus.peek(0x0311), 0x04);
+
+    // LDA #$FF; LDX #$FF; LDY #$01; AHX $0310,Y
+    let (_, bus) = run(&[0xa9, 0xff, 0xa2, 0xff, 0xa0, 0x01, 0x9f, 0x10, 0x03]);
+    assert_eq!(bus.peek(0x0311), 0x04);
+
+    // LDA #$F7; LDX #$7F; LDY #$01; TAS $0310,Y -> SP = $77, stores $77 & $04
+    let (cpu, bus) = run(&[0xa9, 0xf7, 0xa2, 0x7f, 0xa0, 0x01, 0x9b, 0x10, 0x03]);
+    assert_eq!(cpu.stack_pointer, 0x77);
+    assert_eq!(bus.peek(0x0311), 0x04);
+
+    // Crossing a page replaces the high byte of the target with the stored value:
+    // LDX #$02; LDY #$90; SHX $0580,Y stores $02 & $06 at $0210 instead of $0610
+    let (_, bus) = run(&[0xa2, 0x02, 0xa0, 0x90, 0x9e, 0x80, 0x05]);
+    assert_eq!(bus.peek(0x0210), 0x02);
+    assert_eq!(bus.peek(0x0610), 0x00);
+}
+
+#[test]
+fn unstable_opcodes_as_nops_or_rejected() {
+    // LDA #$11; LDX #$0F; XAA #$FF
+    let program = [0xa9, 0x11, 0xa2, 0x0f, 0x8b, 0xff];
+
+    let (mut cpu, mut bus) = machine(&program);
+    cpu.unstable_opcodes = UnstableOpcodes::Nop;
+    run_to(&mut cpu, &mut bus, ORIGIN + program.len() as u16);
+    assert_eq!(cpu.register_a, 0x11);
+
+    let (mut cpu, mut bus) = machine(&program);
+    cpu.unstable_opcodes = UnstableOpcodes::Reject;
+    cpu.run_for_instructions(&mut bus, 2).unwrap();
+    let registers = (cpu.register_a, cpu.register_x, cpu.program_counter);
+    assert_eq!(
+        cpu.run_once(&mut bus),
+        Err(CpuError::UnimplementedOpcode {
+            pc: ORIGIN + 4,
+            opcode: 0x8b
+        })
+    );
+    assert_eq!(
+        (cpu.register_a, cpu.register_x, cpu.program_counter),
+        registers
+    );
+
+    // A rejected opcode isn't even fetched, so devices see no access
+    let mut bus = RecordingBus::with_memory([(ORIGIN, 0x8b), (ORIGIN + 1, 0xff)]);
+    let mut cpu = CPU::new();
+    cpu.program_counter = ORIGIN;
+    cpu.unstable_opcodes = UnstableOpcodes::Reject;
+    assert!(cpu.run_once(&mut bus).is_err());
+    bus.assert_accesses(&[]);
+    assert_eq!(cpu.cycles, 0);
+}
+
+#[test]
+fn nops_only_advance_pc() {
+    for opcode in opcodes("NOP") {
+        let (cpu, bus) = execute(opcode, 0x55, |cpu| {
+            cpu.register_a = 0x12;
+            cpu.status |= FLAG_CARRY;
+        });
+        let name = describe(opcode);
+        assert_eq!(
+            (cpu.register_a, cpu.register_x, cpu.register_y),
+            (0x12, INDEX, INDEX),
+            "{}",
+            name
+        );
+        assert_eq!(cpu.status, FLAG_UNUSED | FLAG_CARRY, "{}", name);
+        assert_eq!(cpu.stack_pointer, 0xfd, "{}", name);
+        assert_eq!(
+            (bus.peek(ZP as u16), bus.peek(ABS)),
+            (0x55, 0x55),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn kil_jams_until_reset() {
+    for opcode in opcodes("KIL") {
+        let (mut cpu, mut bus) = machine(&[opcode]);
+        let jam = Err(CpuError::Jammed { pc: ORIGIN, opcode });
+        assert_eq!(cpu.run_once(&mut bus), jam, "{}", describe(opcode));
+        assert!(cpu.jammed);
+        assert_eq!(cpu.program_counter, ORIGIN);
+
+        // Interrupts aren't serviced either
+        cpu.trigger_nmi();
+        assert_eq!(cpu.run_once(&mut bus), jam);
+        assert!(cpu.nmi_pending);
+
+        cpu.reset(&mut bus);
+        assert!(!cpu.jammed);
+    }
+}
+
+//
+// ---- Control flow ----
+//
+
+#[test]
+fn branches_taken_and_not_taken() {
+    // (opcode, flag, branch when set)
+    let branches = [
+        (0x10, FLAG_NEGATIVE, false), // BPL
+        (0x30, FLAG_NEGATIVE, true),  // BMI
+        (0x50, FLAG_OVERFLOW, false), // BVC
+        (0x70, FLAG_OVERFLOW, true),  // BVS
+        (0x90, FLAG_CARRY, false),    // BCC
+        (0xb0, FLAG_CARRY, true),     // BCS
+        (0xd0, FLAG_ZERO, false),     // BNE
+        (0xf0, FLAG_ZERO, true),      // BEQ
+    ];
+    for (opcode, flag, when_set) in branches {
+        for set in [false, true] {
+            let (mut cpu, mut bus) = machine(&[opcode, 0x10]);
+            cpu.status = FLAG_UNUSED | if set { flag } else { 0 };
+            let cycles = step(&mut cpu, &mut bus).cycles();
+            let (pc, expected_cycles) = if set == when_set {
+                (ORIGIN + 0x12, 3)
+            } else {
+                (ORIGIN + 2, 2)
+            };
+            let case = format!("${:02X} with flag {}", opcode, set);
+            assert_eq!(cpu.program_counter, pc, "{}", case);
+            assert_eq!(cycles, expected_cycles, "{}", case);
+        }
+    }
+
+    // Backwards into the previous page costs one more cycle: BNE -4 from $0600
+    let (mut cpu, mut bus) = machine(&[0xd0, 0xfc]);
+    cpu.status = FLAG_UNUSED;
+    assert_eq!(step(&mut cpu, &mut bus).cycles(), 4);
+    assert_eq!(cpu.program_counter, 0x05fe);
+}
+
+#[test]
+fn counting_loop() {
+    // LDX #$05; LDA #$00; loop: CLC; ADC #$03; DEX; BNE loop
+    let (cpu, _) = run(&[0xa2, 0x05, 0xa9, 0x00, 0x18, 0x69, 0x03, 0xca, 0xd0, 0xfa]);
+    assert_eq!((cpu.register_a, cpu.register_x), (15, 0));
+}
+
+#[test]
+fn jmp_absolute_and_indirect() {
+    // JMP $1234
+    let (mut cpu, mut bus) = machine(&[0x4c, 0x34, 0x12]);
+    assert_eq!(step(&mut cpu, &mut bus).cycles(), 3);
+    assert_eq!(cpu.program_counter, 0x1234);
+
+    // JMP ($0280)
+    let (mut cpu, mut bus) = machine(&[0x6c, 0x80, 0x02]);
+    bus.load(0x0280, &[0x78, 0x56]);
+    assert_eq!(step(&mut cpu, &mut bus).cycles(), 5);
+    assert_eq!(cpu.program_counter, 0x5678);
+}
+
+#[test]
+fn jmp_indirect_page_bug() {
+    // JMP ($02FF) takes the high byte from $0200, not $0300
+    let (mut cpu, mut bus) = machine(&[0x6c, 0xff, 0x02]);
+    bus.write(0x02ff, 0x00);
+    bus.write(0x0300, 0x40);
+    bus.write(0x0200, 0x50);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.program_counter, 0x5000);
+
+    let mut bus = RecordingBus::with_memory([(0x0600, 0x6c), (0x0601, 0xff), (0x0602, 0x02)]);
+    let mut cpu = CPU::new();
+    cpu.program_counter = ORIGIN;
+    step(&mut cpu, &mut bus);
+    let pointer_reads: Vec<u16> = bus.accesses()[3..]
+        .iter()
+        .map(|access| access.addr)
+        .collect();
+    assert_eq!(pointer_reads, [0x02ff, 0x0200]);
+}
+
+//
+// ---- Stack ----
+//
+
+#[test]
+fn pha_pla() {
+    // LDA #$80; PHA; LDA #$01; PHA; LDA #$00; PLA; PLA
+    let (mut cpu, mut bus) = machine(&[0xa9, 0x80, 0x48, 0xa9, 0x01, 0x48, 0xa9, 0x00, 0x68, 0x68]);
+    run_to(&mut cpu, &mut bus, ORIGIN + 6);
+    assert_eq!(cpu.stack_pointer, 0xfb);
+    assert_eq!((bus.peek(0x01fd), bus.peek(0x01fc)), (0x80, 0x01));
+
+    // LDA #$00 set Z, the first PLA clears it
+    run_to(&mut cpu, &mut bus, ORIGIN + 9);
+    assert_eq!(cpu.register_a, 0x01);
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), 0);
+
+    run_to(&mut cpu, &mut bus, ORIGIN + 10);
+    assert_eq!(cpu.register_a, 0x80);
+    assert_eq!(cpu.status & (FLAG_NEGATIVE | FLAG_ZERO), FLAG_NEGATIVE);
+    assert_eq!(cpu.stack_pointer, 0xfd);
+}
+
+#[test]
+fn stack_pointer_wraps_within_page_one() {
+    // LDX #$00; TXS; LDA #$42; PHA; PLA
+    let (mut cpu, mut bus) = machine(&[0xa2, 0x00, 0x9a, 0xa9, 0x42, 0x48, 0x68]);
+    run_to(&mut cpu, &mut bus, ORIGIN + 6);
+    assert_eq!(bus.peek(0x0100), 0x42);
+    assert_eq!(cpu.stack_pointer, 0xff);
+    run_to(&mut cpu, &mut bus, ORIGIN + 7);
+    assert_eq!(cpu.stack_pointer, 0x00);
+}
+
+#[test]
+fn php_pushes_break_and_unused_bits() {
+    for status in [0x00, FLAG_CARRY | FLAG_DECIMAL, 0xff] {
+        let (mut cpu, mut bus) = machine(&[0x08]);
+        cpu.status = status;
+        step(&mut cpu, &mut bus);
+        assert_eq!(bus.peek(0x01fd), status | FLAG_BREAK | FLAG_UNUSED);
+        // The register itself is left alone
+        assert_eq!(cpu.status, status);
+        assert_eq!(cpu.stack_pointer, 0xfc);
+    }
+}
+
+#[test]
+fn plp_ignores_break_and_keeps_unused() {
+    // (pulled, P after)
+    let cases = [(0xff, 0xef), (0x00, 0x20), (0x10, 0x20), (0xc3, 0xe3)];
+    for (pulled, status) in cases {
+        // LDA #pulled; PHA; PLP
+        let (cpu, _) = run(&[0xa9, pulled, 0x48, 0x28]);
+        assert_eq!(cpu.status, status, "PLP of ${:02X}", pulled);
+    }
+}
+
+#[test]
+fn jsr_pushes_last_byte_and_rts_returns_after_it() {
+    // JSR $0610; LDA #$01 ... $0610: LDX #$02; RTS
+    let mut program = vec![0x20, 0x10, 0x06, 0xa9, 0x01];
+    program.resize(0x10, 0xea);
+    program.extend([0xa2, 0x02, 0x60]);
+    let (mut cpu, mut bus) = machine(&program);
+
+    assert_eq!(step(&mut cpu, &mut bus).cycles(), 6);
+    assert_eq!(cpu.program_counter, 0x0610);
+    assert_eq!(cpu.stack_pointer, 0xfb);
+    // Return address - 1 ($0602), high byte first
+    assert_eq!((bus.peek(0x01fd), bus.peek(0x01fc)), (0x06, 0x02));
+
+    step(&mut cpu, &mut bus);
+    assert_eq!(step(&mut cpu, &mut bus).cycles(), 6);
+    assert_eq!(cpu.program_counter, ORIGIN + 3);
+    assert_eq!(cpu.stack_pointer, 0xfd);
+
+    run_to(&mut cpu, &mut bus, ORIGIN + 5);
+    assert_eq!((cpu.register_a, cpu.register_x), (0x01, 0x02));
+}
+
+#[test]
+fn brk_pushes_break_bit_and_rti_returns_past_padding() {
+    let (mut cpu, mut bus) = machine(&[0x00, 0xff]);
+    bus.write(IRQ_HANDLER, 0x40); // RTI
+    cpu.status = FLAG_UNUSED | FLAG_CARRY;
+
+    assert_eq!(
+        step(&mut cpu, &mut bus),
+        StepOutcome::Break {
+            pc: ORIGIN,
+            cycles: 7
+        }
+    );
+    assert_eq!(cpu.program_counter, IRQ_HANDLER);
+    assert_eq!(cpu.stack_pointer, 0xfa);
+    assert_eq!(
+        (bus.peek(0x01fd), bus.peek(0x01fc), bus.peek(0x01fb)),
+        (0x06, 0x02, FLAG_BREAK | FLAG_UNUSED | FLAG_CARRY)
+    );
+    assert_eq!(cpu.status, FLAG_UNUSED | FLAG_INTERRUPT | FLAG_CARRY);
+
+    // RTI restores P without B and returns to BRK + 2 (no +1 like RTS)
+    assert_eq!(step(&mut cpu, &mut bus).cycles(), 6);
+    assert_eq!(cpu.program_counter, ORIGIN + 2);
+    assert_eq!(cpu.status, FLAG_UNUSED | FLAG_CARRY);
+    assert_eq!(cpu.stack_pointer, 0xfd);
+}
+
+#[test]
+fn rti_ignores_break_and_keeps_unused() {
+    // Hand-made frame: P = $FF, return to $1234
+    let (mut cpu, mut bus) = machine(&[0x40]);
+    bus.load(0x01fb, &[0xff, 0x34, 0x12]);
+    cpu.stack_pointer = 0xfa;
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.program_counter, 0x1234);
+    assert_eq!(cpu.status, 0xef);
+
+    bus.load(0x01fb, &[0x00, 0x34, 0x12]);
+    cpu.stack_pointer = 0xfa;
+    cpu.program_counter = ORIGIN;
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.status, FLAG_UNUSED);
+}
+
+//
+// ---- Interrupts ----
+//
+
+#[test]
+fn irq_pushes_status_without_break_bit() {
+    let (mut cpu, mut bus) = machine(&[0xea]);
+    // B set in the register must not leak onto the stack
+    cpu.status = FLAG_UNUSED | FLAG_BREAK | FLAG_CARRY;
+    cpu.trigger_irq();
+
+    assert_eq!(
+        step(&mut cpu, &mut bus),
+        StepOutcome::Interrupt {
+            kind: Interrupt::Irq,
+            pc: ORIGIN,
+            cycles: 7
+        }
+    );
+    assert!(!cpu.irq_pending);
+    assert_eq!(cpu.program_counter, IRQ_HANDLER);
+    assert_eq!(cpu.status & FLAG_INTERRUPT, FLAG_INTERRUPT);
+    // The interrupted instruction hasn't run, so the return address is its own
+    assert_eq!(
+        (bus.peek(0x01fd), bus.peek(0x01fc), bus.peek(0x01fb)),
+        (0x06, 0x00, FLAG_UNUSED | FLAG_CARRY)
+    );
+}
+
+#[test]
+fn nmi_ignores_interrupt_disable() {
+    let (mut cpu, mut bus) = machine(&[0xea]);
+    bus.write(NMI_HANDLER, 0x40); // RTI
+    cpu.status = FLAG_UNUSED | FLAG_INTERRUPT;
+    cpu.trigger_nmi();
+
+    assert_eq!(
+        step(&mut cpu, &mut bus),
+        StepOutcome::Interrupt {
+            kind: Interrupt::Nmi,
+            pc: ORIGIN,
+            cycles: 7
+        }
+    );
+    assert_eq!(cpu.program_counter, NMI_HANDLER);
+    assert_eq!(bus.peek(0x01fb), FLAG_UNUSED | FLAG_INTERRUPT);
+
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.program_counter, ORIGIN);
+    assert_eq!(cpu.status, FLAG_UNUSED | FLAG_INTERRUPT);
+}
+
+#[test]
+fn irq_waits_while_masked() {
+    // NOP; CLI; NOP
+    let (mut cpu, mut bus) = machine(&[0xea, 0x58, 0xea]);
+    assert_eq!(cpu.status & FLAG_INTERRUPT, FLAG_INTERRUPT);
+    cpu.trigger_irq();
+    assert_eq!(cpu.pending_interrupt(), None);
+
+    assert!(matches!(
+        step(&mut cpu, &mut bus),
+        StepOutcome::Instruction { opcode: 0xea, .. }
+    ));
+    assert!(cpu.irq_pending);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pending_interrupt(), Some(Interrupt::Irq));
+    assert_eq!(
+        step(&mut cpu, &mut bus),
+        StepOutcome::Interrupt {
+            kind: Interrupt::Irq,
+            pc: ORIGIN + 2,
+            cycles: 7
+        }
+    );
+}
+
+#[test]
+fn nmi_wins_over_irq() {
+    let (mut cpu, mut bus) = machine(&[0xea]);
+    bus.write(NMI_HANDLER, 0x40); // RTI
+    cpu.status = FLAG_UNUSED;
+    cpu.trigger_irq();
+    cpu.trigger_nmi();
+    assert_eq!(cpu.pending_interrupt(), Some(Interrupt::Nmi));
+
+    let outcome = step(&mut cpu, &mut bus);
+    assert!(matches!(
+        outcome,
+        StepOutcome::Interrupt {
+            kind: Interrupt::Nmi,
+            ..
+        }
+    ));
+    assert!(cpu.irq_pending);
+
+    // The NMI entry set I, so the handler runs before the IRQ...
+    assert!(matches!(
+        step(&mut cpu, &mut bus),
+        StepOutcome::Instruction { opcode: 0x40, .. }
+    ));
+
+    // ...and RTI clears it again
+    assert_eq!(
+        step(&mut cpu, &mut bus),
+        StepOutcome::Interrupt {
+            kind: Interrupt::Irq,
+            pc: ORIGIN,
+            cycles: 7
+        }
+    );
+    assert_eq!(cpu.program_counter, IRQ_HANDLER);
+}
+
+//
+// ---- Addressing mode corner cases ----
+//
+
+#[test]
+fn zero_page_indexed_wraps() {
+    // LDX #$02; LDA $FF,X reads $0001, not $0101
+    let (mut cpu, mut bus) = machine(&[0xa2, 0x02, 0xb5, 0xff]);
+    bus.write(0x0001, 0x11);
+    bus.write(0x0101, 0x22);
+    run_to(&mut cpu, &mut bus, ORIGIN + 4);
+    assert_eq!(cpu.register_a, 0x11);
+
+    // LDY #$02; LDX $FF,Y
+    let (mut cpu, mut bus) = machine(&[0xa0, 0x02, 0xb6, 0xff]);
+    bus.write(0x0001, 0x11);
+    bus.write(0x0101, 0x22);
+    run_to(&mut cpu, &mut bus, ORIGIN + 4);
+    assert_eq!(cpu.register_x, 0x11);
+
+    // LDA #$33; LDX #$20; STA $F0,X writes $0010
+    let (_, bus) = run(&[0xa9, 0x33, 0xa2, 0x20, 0x95, 0xf0]);
+    assert_eq!(bus.peek(0x0010), 0x33);
+    assert_eq!(bus.peek(0x0110), 0x00);
+}
+
+#[test]
+fn indexed_indirect_wraps() {
+    // LDX #$90; LDA ($80,X) reads its pointer from $10/$11
+    let (mut cpu, mut bus) = machine(&[0xa2, 0x90, 0xa1, 0x80]);
+    bus.load(0x0010, &[0x34, 0x12]);
+    bus.write(0x1234, 0xaa);
+    run_to(&mut cpu, &mut bus, ORIGIN + 4);
+    assert_eq!(cpu.register_a, 0xaa);
+
+    // LDX #$01; LDA ($FE,X): pointer low byte at $FF, high byte at $00 (not $0100)
+    let (mut cpu, mut bus) = machine(&[0xa2, 0x01, 0xa1, 0xfe]);
+    bus.write(0x00ff, 0x34);
+    bus.write(0x0000, 0x12);
+    bus.write(0x0100, 0x56);
+    bus.write(0x1234, 0xaa);
+    bus.write(0x5634, 0xbb);
+    run_to(&mut cpu, &mut bus, ORIGIN + 4);
+    assert_eq!(cpu.register_a, 0xaa);
+}
+
+#[test]
+fn indirect_indexed_pointer_wraps() {
+    // LDY #$01; LDA ($FF),Y: pointer from $FF/$00, then + Y
+    let mut bus = RecordingBus::with_memory([
+        (0x00ff, 0x34),
+        (0x0000, 0x12),
+        (0x0100, 0x56),
+        (0x1235, 0xaa),
+    ]);
+    bus.load(ORIGIN, &[0xa0, 0x01, 0xb1, 0xff]);
+    let mut cpu = CPU::new();
+    cpu.program_counter = ORIGIN;
+    run_to(&mut cpu, &mut bus, ORIGIN + 4);
+    assert_eq!(cpu.register_a, 0xaa);
+    assert!(
+        bus.accesses().iter().all(|access| access.addr != 0x0100),
+        "page one was read"
+    );
+}
+
+#[test]
+fn page_crossing_costs_a_cycle_on_reads_only() {
+    // (program with X = Y = $10, cycles)
+    let cases: [(&[u8], u64); 6] = [
+        (&[0xbd, 0x00, 0x03], 4), // LDA $0300,X
+        (&[0xbd, 0xf8, 0x03], 5), // LDA $03F8,X
+        (&[0xb9, 0xf8, 0x03], 5), // LDA $03F8,Y
+        (&[0x9d, 0x00, 0x03], 5), // STA $0300,X
+        (&[0x9d, 0xf8, 0x03], 5), // STA $03F8,X
+        (&[0x1e, 0xf8, 0x03], 7), // ASL $03F8,X
+    ];
+    for (program, cycles) in cases {
+        let (mut cpu, mut bus) = machine(program);
+        cpu.register_x = 0x10;
+        cpu.register_y = 0x10;
+        assert_eq!(
+            step(&mut cpu, &mut bus).cycles(),
+            cycles,
+            "{:02X?}",
+            program
+        );
+    }
+
+    // LDY #$10; LDA ($30),Y with the pointer at $03F8
+    let (mut cpu, mut bus) = machine(&[0xa0, 0x10, 0xb1, 0x30]);
+    bus.load(0x0030, &[0xf8, 0x03]);
+    step(&mut cpu, &mut bus);
+    assert_eq!(step(&mut cpu, &mut bus).cycles(), 6);
+}
+
+#[test]
+fn store_accesses_on_recording_bus() {
+    // STA $10: opcode, operand, then the write
+    let mut bus = RecordingBus::with_memory([(0x0600, 0x85), (0x0601, 0x10)]);
+    let mut cpu = CPU::new();
+    cpu.program_counter = ORIGIN;
+    cpu.register_a = 0x42;
+    step(&mut cpu, &mut bus);
+    bus.assert_accesses(&[
+        (0x0600, 0x85, AccessKind::Read),
+        (0x0601, 0x10, AccessKind::Read),
+        (0x0010, 0x42, AccessKind::Write),
+    ]);
+}
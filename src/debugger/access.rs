@@ -0,0 +1,56 @@
+use crate::bus::Bus;
+use crate::bus::diagnostics::BusEvent;
+
+// The access records belong to the bus layer; re-exported for debugger users
+pub use crate::bus::access::{AccessKind, MemoryAccess};
+
+/// Bus wrapper that records every access while forwarding it to the real bus.
+/// The debugger runs each step through it to evaluate watchpoints and to
+/// be able to undo the step.
+pub struct TapBus<'a, B: Bus> {
+    inner: &'a mut B,
+    pub accesses: Vec<MemoryAccess>,
+
+    /// Address and previous value (as seen by `peek`) of every write, in order
+    pub overwritten: Vec<(u16, u8)>,
+}
+
+impl<'a, B: Bus> TapBus<'a, B> {
+    pub fn new(inner: &'a mut B) -> Self {
+        Self {
+            inner,
+            accesses: Vec::new(),
+            overwritten: Vec::new(),
+        }
+    }
+}
+
+impl<B: Bus> Bus for TapBus<'_, B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let value = self.inner.read(addr);
+        self.accesses.push(MemoryAccess {
+            kind: AccessKind::Read,
+            addr,
+            value,
+        });
+        value
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.inner.peek(addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.overwritten.push((addr, self.inner.peek(addr)));
+        self.accesses.push(MemoryAccess {
+            kind: AccessKind::Write,
+            addr,
+            value: data,
+        });
+        self.inner.write(addr, data);
+    }
+
+    fn take_break_event(&mut self) -> Option<BusEvent> {
+        self.inner.take_break_event()
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::bus::access::{AccessKind, MemoryAccess};
+use crate::bus::diagnostics::BusEvent;
+use crate::cpu::Interrupt;
+use crate::debugger::condition::Condition;
+
+/// Which memory accesses a watchpoint reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Both reads and writes.
+    Access,
+}
+
+impl WatchKind {
+    /// Returns true if an access of `kind` triggers this watchpoint.
+    pub fn matches(&self, kind: AccessKind) -> bool {
+        match self {
+            WatchKind::Read => kind == AccessKind::Read,
+            WatchKind::Write => kind == AccessKind::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+/// What a breakpoint reacts to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakpointKind {
+    /// An instruction is executed at an address in the range.
+    Execute(RangeInclusive<u16>),
+
+    /// The CPU reads and/or writes an address in the range.
+    /// Instruction fetches (opcode and operand bytes) do not count as reads.
+    Watch(WatchKind, RangeInclusive<u16>),
+
+    /// An instruction with this opcode byte is executed.
+    Opcode(u8),
+
+    /// A BRK instruction is executed.
+    Brk,
+
+    /// The CPU enters an IRQ or NMI handler.
+    Interrupt(Interrupt),
+
+    /// A push wraps the stack pointer from $00 to $FF.
+    StackOverflow,
+
+    /// A pull wraps the stack pointer from $FF to $00.
+    StackUnderflow,
+}
+
+impl fmt::Display for BreakpointKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn range(f: &mut fmt::Formatter<'_>, r: &RangeInclusive<u16>) -> fmt::Result {
+            if r.start() == r.end() {
+                write!(f, "${:04X}", r.start())
+            } else {
+                write!(f, "${:04X}-${:04X}", r.start(), r.end())
+            }
+        }
+
+        match self {
+            BreakpointKind::Execute(r) => {
+                f.write_str("exec ")?;
+                range(f, r)
+            }
+            BreakpointKind::Watch(kind, r) => {
+                let name = match kind {
+                    WatchKind::Read => "read ",
+                    WatchKind::Write => "write ",
+                    WatchKind::Access => "access ",
+                };
+                f.write_str(name)?;
+                range(f, r)
+            }
+            BreakpointKind::Opcode(opcode) => write!(f, "opcode ${:02X}", opcode),
+            BreakpointKind::Brk => f.write_str("brk"),
+            BreakpointKind::Interrupt(Interrupt::Irq) => f.write_str("irq"),
+            BreakpointKind::Interrupt(Interrupt::Nmi) => f.write_str("nmi"),
+            BreakpointKind::StackOverflow => f.write_str("stack overflow"),
+            BreakpointKind::StackUnderflow => f.write_str("stack underflow"),
+        }
+    }
+}
+
+/// A breakpoint or watchpoint registered with the `Debugger`.
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub kind: BreakpointKind,
+
+    /// Only counts as hit when this evaluates to true.
+    pub condition: Option<Condition>,
+
+    /// Disabled breakpoints are neither counted nor reported.
+    pub enabled: bool,
+
+    /// How many times the breakpoint was hit (condition true).
+    pub hit_count: u64,
+
+    /// Number of hits to let pass before actually breaking.
+    pub ignore_count: u64,
+}
+
+impl Breakpoint {
+    pub fn new(id: usize, kind: BreakpointKind) -> Self {
+        Self {
+            id,
+            kind,
+            condition: None,
+            enabled: true,
+            hit_count: 0,
+            ignore_count: 0,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {}", self.id, self.kind)?;
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        if !self.enabled {
+            f.write_str(" (disabled)")?;
+        }
+        write!(f, " hits={}", self.hit_count)?;
+        if self.ignore_count > 0 {
+            write!(f, " ignore={}", self.ignore_count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why the debugger stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// Breakpoint `id` fired. For watchpoints `access` is the triggering access.
+    Breakpoint {
+        id: usize,
+        access: Option<MemoryAccess>,
+    },
+
+    /// The bus reported an event whose policy is `EventPolicy::Break`.
+    BusEvent(BusEvent),
+
+    /// A run command executed `RunLimits::max_instructions` instructions.
+    InstructionLimit,
+
+    /// A run command used up `RunLimits::max_cycles` cycles.
+    CycleLimit,
+
+    /// The instruction at `pc` jumped to itself (e.g. `JMP *`) with no
+    /// interrupt pending, so execution would never leave it.
+    SelfLoop { pc: u16 },
+
+    /// The host raised the interrupt flag (e.g. on Ctrl-C).
+    Interrupted,
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BreakReason::Breakpoint { id, access: None } => write!(f, "breakpoint #{}", id),
+            BreakReason::Breakpoint {
+                id,
+                access: Some(access),
+            } => write!(f, "watchpoint #{}: {}", id, access),
+            BreakReason::BusEvent(event) => write!(f, "bus event: {}", event),
+            BreakReason::InstructionLimit => f.write_str("instruction limit reached"),
+            BreakReason::CycleLimit => f.write_str("cycle limit reached"),
+            BreakReason::SelfLoop { pc } => write!(f, "self-loop at ${:04X}", pc),
+            BreakReason::Interrupted => f.write_str("interrupted"),
+        }
+    }
+}
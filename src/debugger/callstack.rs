@@ -0,0 +1,565 @@
+use std::fmt;
+
+use crate::cpu::{Interrupt, StepOutcome};
+use crate::debugger::debuginfo::DebugInfo;
+
+const JSR_OPCODE: u8 = 0x20;
+const RTS_OPCODE: u8 = 0x60;
+const RTI_OPCODE: u8 = 0x40;
+
+/// Mismatches kept before the oldest are dropped
+const MAX_MISMATCHES: usize = 256;
+
+/// How a frame was entered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Jsr,
+    Brk,
+    Irq,
+    Nmi,
+}
+
+impl FrameKind {
+    /// True if the frame is left with RTI rather than RTS
+    pub fn is_interrupt(self) -> bool {
+        self != FrameKind::Jsr
+    }
+}
+
+impl fmt::Display for FrameKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FrameKind::Jsr => "JSR",
+            FrameKind::Brk => "BRK",
+            FrameKind::Irq => "IRQ",
+            FrameKind::Nmi => "NMI",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One active subroutine call or interrupt handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    pub kind: FrameKind,
+
+    /// Address of the JSR or BRK instruction, or where an IRQ/NMI
+    /// interrupted the program
+    pub caller: u16,
+
+    /// Subroutine or handler entry point
+    pub target: u16,
+
+    /// Stack pointer before the return address was pushed
+    pub sp: u8,
+}
+
+impl CallFrame {
+    /// Where the matching RTS or RTI returns to
+    pub fn return_addr(&self) -> u16 {
+        match self.kind {
+            FrameKind::Jsr => self.caller.wrapping_add(3),
+            // BRK skips a padding byte
+            FrameKind::Brk => self.caller.wrapping_add(2),
+            FrameKind::Irq | FrameKind::Nmi => self.caller,
+        }
+    }
+}
+
+/// Why a return didn't match the shadow stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MismatchKind {
+    /// No frame owns the stack position returned to, e.g. an RTS used to
+    /// jump through a pushed address, or a return with no call
+    Unmatched,
+
+    /// RTS from an interrupt frame, or RTI from a JSR frame
+    WrongInstruction(CallFrame),
+
+    /// The frame returned somewhere other than its return address
+    /// (the return address on the stack was changed)
+    WrongAddress(CallFrame),
+
+    /// Frames abandoned without returning, because the stack pointer was
+    /// moved past them (PLA/TXS games). Found by the next return, or by a
+    /// call or interrupt that overwrote their return addresses.
+    Discarded(Vec<CallFrame>),
+}
+
+/// A return that didn't match the call that should have made it, or frames
+/// a call found abandoned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Address of the RTS or RTI (or of the JSR, BRK or interrupted
+    /// instruction that discarded frames)
+    pub pc: u16,
+
+    /// Where it returned (or jumped) to
+    pub target: u16,
+
+    pub kind: MismatchKind,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${:04X} to ${:04X}: ", self.pc, self.target)?;
+        match &self.kind {
+            MismatchKind::Unmatched => write!(f, "no matching call"),
+            MismatchKind::WrongInstruction(frame) => write!(
+                f,
+                "{} frame from ${:04X} left with the wrong instruction",
+                frame.kind, frame.caller
+            ),
+            MismatchKind::WrongAddress(frame) => write!(
+                f,
+                "{} from ${:04X} should return to ${:04X}",
+                frame.kind,
+                frame.caller,
+                frame.return_addr()
+            ),
+            MismatchKind::Discarded(frames) => {
+                write!(f, "dropped {} frame(s) without returning", frames.len())
+            }
+        }
+    }
+}
+
+/// How one step changed a `CallStack`, kept so the step can be undone
+/// without copying the whole stack.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackChange {
+    /// A frame was pushed
+    pushed: bool,
+
+    /// Frames returned from or discarded, outermost first
+    removed: Vec<CallFrame>,
+
+    /// Mismatches flagged
+    flagged: usize,
+}
+
+/// Shadow call stack built from the calls, interrupts and returns the
+/// debugger executes. The real stack can't be walked reliably on a 6502,
+/// since code is free to push data or manipulate return addresses.
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+
+    /// Returns that didn't match, oldest first
+    mismatches: Vec<Mismatch>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Active frames, outermost first
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Forget all frames and mismatches (e.g. after a reset)
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.mismatches.clear();
+    }
+
+    /// Mismatched returns seen since the last call, oldest first
+    pub fn take_mismatches(&mut self) -> Vec<Mismatch> {
+        std::mem::take(&mut self.mismatches)
+    }
+
+    /// Updates the stack after a step. `pc` and `opcode` describe the
+    /// instruction the step started at, `sp_before` the stack pointer then,
+    /// and `sp_after`/`target` the stack pointer and PC after the step.
+    /// Returns what changed, for `undo`.
+    pub fn record(
+        &mut self,
+        outcome: &StepOutcome,
+        pc: u16,
+        opcode: u8,
+        sp_before: u8,
+        sp_after: u8,
+        target: u16,
+    ) -> StackChange {
+        let kind = match outcome {
+            StepOutcome::Break { .. } => FrameKind::Brk,
+            StepOutcome::Interrupt {
+                kind: Interrupt::Irq,
+                ..
+            } => FrameKind::Irq,
+            StepOutcome::Interrupt {
+                kind: Interrupt::Nmi,
+                ..
+            } => FrameKind::Nmi,
+            StepOutcome::Instruction { .. } => match opcode {
+                JSR_OPCODE => FrameKind::Jsr,
+                RTS_OPCODE | RTI_OPCODE => return self.leave(pc, opcode, sp_after, target),
+                _ => return StackChange::default(),
+            },
+        };
+
+        // Frames at or below the new return address were abandoned, e.g. by
+        // a TXS that reset the stack
+        let live = self.frames.partition_point(|frame| frame.sp > sp_before);
+        let removed: Vec<CallFrame> = self.frames.drain(live..).collect();
+        let mut flagged = 0;
+        if !removed.is_empty() {
+            self.flag(pc, target, MismatchKind::Discarded(removed.clone()));
+            flagged += 1;
+        }
+
+        self.frames.push(CallFrame {
+            kind,
+            caller: pc,
+            target,
+            sp: sp_before,
+        });
+        StackChange {
+            pushed: true,
+            removed,
+            flagged,
+        }
+    }
+
+    /// Reverts the change `record` returned. Changes must be undone newest
+    /// first; mismatches already taken stay taken.
+    pub fn undo(&mut self, change: StackChange) {
+        if change.pushed {
+            self.frames.pop();
+        }
+        self.frames.extend(change.removed);
+        let kept = self.mismatches.len().saturating_sub(change.flagged);
+        self.mismatches.truncate(kept);
+    }
+
+    /// Pops the frame an RTS/RTI at `pc` returned from
+    fn leave(&mut self, pc: u16, opcode: u8, sp_after: u8, target: u16) -> StackChange {
+        // The stack grows down, so frames below the new stack pointer are gone
+        let live = self.frames.partition_point(|frame| frame.sp >= sp_after);
+        let discarded: Vec<CallFrame> = self.frames.drain(live..).collect();
+
+        // The returning frame is the one whose return address was just pulled
+        let frame = match self.frames.last() {
+            Some(frame) if frame.sp == sp_after => self.frames.pop(),
+            _ => None,
+        };
+
+        let Some(frame) = frame else {
+            // Put back what was dropped: an unmatched return (e.g. an RTS
+            // jump table) doesn't end the frames around it
+            self.frames.extend(discarded);
+            self.flag(pc, target, MismatchKind::Unmatched);
+            return StackChange {
+                flagged: 1,
+                ..StackChange::default()
+            };
+        };
+
+        let mut change = StackChange {
+            removed: [frame]
+                .into_iter()
+                .chain(discarded.iter().copied())
+                .collect(),
+            ..StackChange::default()
+        };
+        if !discarded.is_empty() {
+            self.flag(pc, target, MismatchKind::Discarded(discarded));
+            change.flagged += 1;
+        }
+        if frame.kind.is_interrupt() != (opcode == RTI_OPCODE) {
+            self.flag(pc, target, MismatchKind::WrongInstruction(frame));
+            change.flagged += 1;
+        } else if frame.return_addr() != target {
+            self.flag(pc, target, MismatchKind::WrongAddress(frame));
+            change.flagged += 1;
+        }
+        change
+    }
+
+    fn flag(&mut self, pc: u16, target: u16, kind: MismatchKind) {
+        if self.mismatches.len() == MAX_MISMATCHES {
+            self.mismatches.remove(0);
+        }
+        self.mismatches.push(Mismatch { pc, target, kind });
+    }
+
+    /// GDB-style backtrace, innermost first: `pc` as frame #0, then the
+    /// call site of every active frame. Addresses are named after the
+    /// nearest label when `debug_info` has symbols.
+    pub fn backtrace(&self, pc: u16, debug_info: Option<&DebugInfo>) -> String {
+        let name = |addr: u16| match debug_info.and_then(|info| info.symbol_for(addr)) {
+            Some((symbol, 0)) => format!("${:04X} <{}>", addr, symbol),
+            Some((symbol, offset)) => format!("${:04X} <{}+{}>", addr, symbol, offset),
+            None => format!("${:04X}", addr),
+        };
+
+        let mut text = format!("#0  {}\n", name(pc));
+        for (depth, frame) in self.frames.iter().rev().enumerate() {
+            text += &format!(
+                "#{:<2} {}  {} {}  SP=${:02X}\n",
+                depth + 1,
+                name(frame.caller),
+                frame.kind,
+                name(frame.target),
+                frame.sp
+            );
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IRQ_HANDLER: u16 = 0x0700;
+    const NMI_HANDLER: u16 = 0x0780;
+
+    fn instruction(pc: u16, opcode: u8) -> StepOutcome {
+        StepOutcome::Instruction {
+            pc,
+            opcode,
+            cycles: 6,
+        }
+    }
+
+    /// JSR at `pc` to `target` with the stack pointer at `sp`
+    fn jsr(stack: &mut CallStack, pc: u16, target: u16, sp: u8) -> StackChange {
+        let outcome = instruction(pc, JSR_OPCODE);
+        stack.record(&outcome, pc, JSR_OPCODE, sp, sp - 2, target)
+    }
+
+    /// An IRQ or NMI taken in front of `pc` with the stack pointer at `sp`
+    fn interrupt(stack: &mut CallStack, kind: Interrupt, pc: u16, sp: u8) -> StackChange {
+        let handler = match kind {
+            Interrupt::Irq => IRQ_HANDLER,
+            Interrupt::Nmi => NMI_HANDLER,
+        };
+        let outcome = StepOutcome::Interrupt {
+            kind,
+            pc,
+            cycles: 7,
+        };
+        stack.record(&outcome, pc, 0xEA, sp, sp - 3, handler)
+    }
+
+    /// RTS or RTI at `pc` leaving the stack pointer at `sp` and returning
+    /// to `target`
+    fn ret(stack: &mut CallStack, opcode: u8, pc: u16, sp: u8, target: u16) -> StackChange {
+        let sp_before = sp - if opcode == RTI_OPCODE { 3 } else { 2 };
+        stack.record(&instruction(pc, opcode), pc, opcode, sp_before, sp, target)
+    }
+
+    fn kinds(stack: &CallStack) -> Vec<FrameKind> {
+        stack.frames().iter().map(|frame| frame.kind).collect()
+    }
+
+    fn frame(kind: FrameKind, caller: u16, target: u16, sp: u8) -> CallFrame {
+        CallFrame {
+            kind,
+            caller,
+            target,
+            sp,
+        }
+    }
+
+    #[test]
+    fn jsr_and_rts_pair_up() {
+        let mut stack = CallStack::new();
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+        jsr(&mut stack, 0x0612, 0x0620, 0xFB);
+        assert_eq!(
+            stack.frames(),
+            [
+                frame(FrameKind::Jsr, 0x0600, 0x0610, 0xFD),
+                frame(FrameKind::Jsr, 0x0612, 0x0620, 0xFB),
+            ]
+        );
+
+        ret(&mut stack, RTS_OPCODE, 0x0621, 0xFB, 0x0615);
+        ret(&mut stack, RTS_OPCODE, 0x0615, 0xFD, 0x0603);
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(stack.take_mismatches(), []);
+    }
+
+    #[test]
+    fn interrupts_and_brk_pair_up_with_rti() {
+        let mut stack = CallStack::new();
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+        interrupt(&mut stack, Interrupt::Irq, 0x0610, 0xFB);
+        interrupt(&mut stack, Interrupt::Nmi, IRQ_HANDLER, 0xF8);
+        let outcome = StepOutcome::Break {
+            pc: 0x0780,
+            cycles: 7,
+        };
+        stack.record(&outcome, NMI_HANDLER, 0x00, 0xF5, 0xF2, IRQ_HANDLER);
+        assert_eq!(
+            kinds(&stack),
+            [
+                FrameKind::Jsr,
+                FrameKind::Irq,
+                FrameKind::Nmi,
+                FrameKind::Brk
+            ]
+        );
+
+        // BRK returns past its padding byte, interrupts to where they hit
+        ret(&mut stack, RTI_OPCODE, IRQ_HANDLER, 0xF5, 0x0782);
+        ret(&mut stack, RTI_OPCODE, NMI_HANDLER, 0xF8, IRQ_HANDLER);
+        ret(&mut stack, RTI_OPCODE, IRQ_HANDLER, 0xFB, 0x0610);
+        assert_eq!(kinds(&stack), [FrameKind::Jsr]);
+        assert_eq!(stack.take_mismatches(), []);
+    }
+
+    #[test]
+    fn return_with_no_call_is_unmatched() {
+        let mut stack = CallStack::new();
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+
+        // An RTS jump table: push an address minus one, then RTS to it
+        ret(&mut stack, RTS_OPCODE, 0x0614, 0xFB, 0x0680);
+        assert_eq!(
+            stack.take_mismatches(),
+            [Mismatch {
+                pc: 0x0614,
+                target: 0x0680,
+                kind: MismatchKind::Unmatched,
+            }]
+        );
+        // The frame around it is still there and returns normally
+        ret(&mut stack, RTS_OPCODE, 0x0690, 0xFD, 0x0603);
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(stack.take_mismatches(), []);
+    }
+
+    #[test]
+    fn wrong_return_instruction() {
+        // A handler that pulls the status byte and leaves with RTS
+        let mut stack = CallStack::new();
+        interrupt(&mut stack, Interrupt::Irq, 0x0605, 0xFD);
+        ret(&mut stack, RTS_OPCODE, 0x0702, 0xFD, 0x0605);
+        let irq = frame(FrameKind::Irq, 0x0605, IRQ_HANDLER, 0xFD);
+        assert_eq!(
+            stack.take_mismatches()[0].kind,
+            MismatchKind::WrongInstruction(irq)
+        );
+
+        // A subroutine that pushes flags and leaves with RTI
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+        ret(&mut stack, RTI_OPCODE, 0x0611, 0xFD, 0x0603);
+        let call = frame(FrameKind::Jsr, 0x0600, 0x0610, 0xFD);
+        assert_eq!(
+            stack.take_mismatches()[0].kind,
+            MismatchKind::WrongInstruction(call)
+        );
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn changed_return_address() {
+        let mut stack = CallStack::new();
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+        ret(&mut stack, RTS_OPCODE, 0x0615, 0xFD, 0x0606);
+        let call = frame(FrameKind::Jsr, 0x0600, 0x0610, 0xFD);
+        let mismatch = stack.take_mismatches().remove(0);
+        assert_eq!(mismatch.kind, MismatchKind::WrongAddress(call));
+        assert_eq!(
+            mismatch.to_string(),
+            "$0615 to $0606: JSR from $0600 should return to $0603"
+        );
+    }
+
+    #[test]
+    fn pulled_return_address_discards_the_frame() {
+        // The inner subroutine drops its return address with PLA PLA and
+        // returns straight to the outer caller
+        let mut stack = CallStack::new();
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+        jsr(&mut stack, 0x0612, 0x0620, 0xFB);
+        ret(&mut stack, RTS_OPCODE, 0x0623, 0xFD, 0x0603);
+
+        let inner = frame(FrameKind::Jsr, 0x0612, 0x0620, 0xFB);
+        assert_eq!(
+            stack.take_mismatches(),
+            [Mismatch {
+                pc: 0x0623,
+                target: 0x0603,
+                kind: MismatchKind::Discarded(vec![inner]),
+            }]
+        );
+        assert_eq!(stack.depth(), 0);
+    }
+
+    #[test]
+    fn stack_reset_discards_frames() {
+        let mut stack = CallStack::new();
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+        jsr(&mut stack, 0x0612, 0x0620, 0xFB);
+        interrupt(&mut stack, Interrupt::Nmi, 0x0620, 0xF9);
+
+        // LDX #$FF, TXS in the handler, then back to the main loop's call
+        let change = jsr(&mut stack, 0x0600, 0x0610, 0xFF);
+        assert_eq!(
+            stack.frames(),
+            [frame(FrameKind::Jsr, 0x0600, 0x0610, 0xFF)]
+        );
+        let mismatches = stack.take_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        let MismatchKind::Discarded(frames) = &mismatches[0].kind else {
+            panic!("{:?}", mismatches[0]);
+        };
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            mismatches[0].to_string(),
+            "$0600 to $0610: dropped 3 frame(s) without returning"
+        );
+
+        // Undoing the call brings the abandoned frames back
+        stack.undo(change);
+        assert_eq!(
+            kinds(&stack),
+            [FrameKind::Jsr, FrameKind::Jsr, FrameKind::Nmi]
+        );
+    }
+
+    #[test]
+    fn undo_reverts_frames_and_mismatches() {
+        let mut stack = CallStack::new();
+        jsr(&mut stack, 0x0600, 0x0610, 0xFD);
+        jsr(&mut stack, 0x0612, 0x0620, 0xFB);
+        let before = stack.clone();
+
+        // A discard and a changed return address in one RTS
+        let change = ret(&mut stack, RTS_OPCODE, 0x0623, 0xFD, 0x0700);
+        assert_eq!(stack.mismatches.len(), 2);
+        stack.undo(change);
+        assert_eq!(stack.frames(), before.frames());
+        assert_eq!(stack.mismatches, []);
+
+        let change = ret(&mut stack, RTS_OPCODE, 0x0621, 0xFB, 0x0615);
+        assert_eq!(
+            change,
+            StackChange {
+                removed: vec![before.frames()[1]],
+                ..StackChange::default()
+            }
+        );
+        stack.undo(change);
+        assert_eq!(stack.frames(), before.frames());
+    }
+
+    #[test]
+    fn mismatch_log_is_bounded() {
+        let mut stack = CallStack::new();
+        for _ in 0..MAX_MISMATCHES + 10 {
+            ret(&mut stack, RTS_OPCODE, 0x0600, 0xFF, 0x0700);
+        }
+        assert_eq!(stack.take_mismatches().len(), MAX_MISMATCHES);
+    }
+}
@@ -0,0 +1,493 @@
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::cpu::{
+    CPU, FLAG_BREAK, FLAG_CARRY, FLAG_DECIMAL, FLAG_INTERRUPT, FLAG_NEGATIVE, FLAG_OVERFLOW,
+    FLAG_ZERO,
+};
+
+/// A breakpoint condition such as `A == $42 && X > 3`.
+///
+/// Syntax:
+/// - numbers: `$2A`, `0x2A`, `%101010` or `42`
+/// - registers: `A`, `X`, `Y`, `SP`, `P`, `PC`
+/// - flags (0 or 1): `N`, `V`, `B`, `D`, `I`, `Z`, `C`
+/// - memory byte: `[$0200]` (read with `peek`, so it has no side effects)
+/// - operators: `( )`, `!`, `&`, `|`, `== != < <= > >=`, `&&`, `||`
+///
+/// Any non-zero value counts as true.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+/// Error produced when a condition cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConditionError {
+    pub message: String,
+    /// Byte offset into the source where the problem was found.
+    pub position: usize,
+}
+
+impl fmt::Display for ConditionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ConditionError {}
+
+impl Condition {
+    /// Parses a condition expression.
+    pub fn parse(source: &str) -> Result<Self, ConditionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: source.len(),
+        };
+        let expr = parser.parse_or()?;
+        if let Some((token, position)) = parser.tokens.get(parser.pos) {
+            return Err(ConditionError {
+                message: format!("unexpected {:?}", token),
+                position: *position,
+            });
+        }
+        Ok(Self {
+            source: source.to_string(),
+            expr,
+        })
+    }
+
+    /// The text the condition was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluates the condition against the current machine state.
+    pub fn eval(&self, cpu: &CPU, bus: &impl Bus) -> bool {
+        self.value(cpu, bus) != 0
+    }
+
+    /// Evaluates the expression to a number (comparisons give 0 or 1),
+    /// e.g. to show `[$0200]` or `PC` in a watch window.
+    pub fn value(&self, cpu: &CPU, bus: &impl Bus) -> u32 {
+        self.expr.eval(cpu, bus)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    A,
+    X,
+    Y,
+    Sp,
+    P,
+    Pc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitAnd,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(u32),
+    Register(Register),
+    Flag(u8),
+    Memory(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, cpu: &CPU, bus: &impl Bus) -> u32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(register) => match register {
+                Register::A => cpu.register_a as u32,
+                Register::X => cpu.register_x as u32,
+                Register::Y => cpu.register_y as u32,
+                Register::Sp => cpu.stack_pointer as u32,
+                Register::P => cpu.status as u32,
+                Register::Pc => cpu.program_counter as u32,
+            },
+            Expr::Flag(mask) => ((cpu.status & mask) != 0) as u32,
+            Expr::Memory(addr) => bus.peek(addr.eval(cpu, bus) as u16) as u32,
+            Expr::Not(inner) => (inner.eval(cpu, bus) == 0) as u32,
+            Expr::Binary(op, lhs, rhs) => {
+                let l = lhs.eval(cpu, bus);
+                // && and || short-circuit, so `[addr]` reads are skipped when not needed
+                match op {
+                    BinaryOp::Or => (l != 0 || rhs.eval(cpu, bus) != 0) as u32,
+                    BinaryOp::And => (l != 0 && rhs.eval(cpu, bus) != 0) as u32,
+                    _ => {
+                        let r = rhs.eval(cpu, bus);
+                        match op {
+                            BinaryOp::Eq => (l == r) as u32,
+                            BinaryOp::Ne => (l != r) as u32,
+                            BinaryOp::Lt => (l < r) as u32,
+                            BinaryOp::Le => (l <= r) as u32,
+                            BinaryOp::Gt => (l > r) as u32,
+                            BinaryOp::Ge => (l >= r) as u32,
+                            BinaryOp::BitOr => l | r,
+                            BinaryOp::BitAnd => l & r,
+                            BinaryOp::Or | BinaryOp::And => unreachable!(),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u32),
+    Ident(String),
+    Op(&'static str),
+}
+
+/// Operators, longest first so `&&` is not read as two `&`.
+const OPERATORS: [&str; 15] = [
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "&", "|", "(", ")", "[", "]",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ConditionError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+
+        if let Some(op) = OPERATORS.iter().find(|op| source[i..].starts_with(**op)) {
+            tokens.push((Token::Op(op), start));
+            i += op.len();
+            continue;
+        }
+
+        let (radix, skip) = if c == '$' {
+            (16, 1)
+        } else if c == '%' {
+            (2, 1)
+        } else if source[i..].starts_with("0x") || source[i..].starts_with("0X") {
+            (16, 2)
+        } else if c.is_ascii_digit() {
+            (10, 0)
+        } else if c.is_ascii_alphabetic() {
+            while i < bytes.len() && (bytes[i] as char).is_ascii_alphanumeric() {
+                i += 1;
+            }
+            tokens.push((Token::Ident(source[start..i].to_ascii_uppercase()), start));
+            continue;
+        } else {
+            return Err(ConditionError {
+                message: format!("unexpected character '{}'", c),
+                position: start,
+            });
+        };
+
+        i += skip;
+        let digits_start = i;
+        while i < bytes.len() && (bytes[i] as char).is_digit(radix) {
+            i += 1;
+        }
+        let value =
+            u32::from_str_radix(&source[digits_start..i], radix).map_err(|_| ConditionError {
+                message: "invalid number".to_string(),
+                position: start,
+            })?;
+        tokens.push((Token::Number(value), start));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    /// Position reported for errors at the end of input.
+    end: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some((Token::Op(op), _)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ConditionError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", op)))
+        }
+    }
+
+    fn error(&self, message: String) -> ConditionError {
+        let position = self
+            .tokens
+            .get(self.pos)
+            .map(|(_, position)| *position)
+            .unwrap_or(self.end);
+        ConditionError { message, position }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ConditionError> {
+        let mut lhs = self.parse_and()?;
+        while self.eat("||") {
+            let rhs = self.parse_and()?;
+            lhs = Expr::Binary(BinaryOp::Or, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ConditionError> {
+        let mut lhs = self.parse_comparison()?;
+        while self.eat("&&") {
+            let rhs = self.parse_comparison()?;
+            lhs = Expr::Binary(BinaryOp::And, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, ConditionError> {
+        let lhs = self.parse_bit_or()?;
+        let op = match self.peek_op() {
+            Some("==") => BinaryOp::Eq,
+            Some("!=") => BinaryOp::Ne,
+            Some("<") => BinaryOp::Lt,
+            Some("<=") => BinaryOp::Le,
+            Some(">") => BinaryOp::Gt,
+            Some(">=") => BinaryOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        let rhs = self.parse_bit_or()?;
+        Ok(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))
+    }
+
+    fn parse_bit_or(&mut self) -> Result<Expr, ConditionError> {
+        let mut lhs = self.parse_bit_and()?;
+        while self.eat("|") {
+            let rhs = self.parse_bit_and()?;
+            lhs = Expr::Binary(BinaryOp::BitOr, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_bit_and(&mut self) -> Result<Expr, ConditionError> {
+        let mut lhs = self.parse_unary()?;
+        while self.eat("&") {
+            let rhs = self.parse_unary()?;
+            lhs = Expr::Binary(BinaryOp::BitAnd, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ConditionError> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ConditionError> {
+        if self.eat("(") {
+            let inner = self.parse_or()?;
+            self.expect(")")?;
+            return Ok(inner);
+        }
+        if self.eat("[") {
+            let addr = self.parse_or()?;
+            self.expect("]")?;
+            return Ok(Expr::Memory(Box::new(addr)));
+        }
+
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        let expr = match token {
+            Some(Token::Number(value)) => Expr::Number(value),
+            Some(Token::Ident(name)) => match name.as_str() {
+                "A" => Expr::Register(Register::A),
+                "X" => Expr::Register(Register::X),
+                "Y" => Expr::Register(Register::Y),
+                "SP" => Expr::Register(Register::Sp),
+                "P" => Expr::Register(Register::P),
+                "PC" => Expr::Register(Register::Pc),
+                "N" => Expr::Flag(FLAG_NEGATIVE),
+                "V" => Expr::Flag(FLAG_OVERFLOW),
+                "B" => Expr::Flag(FLAG_BREAK),
+                "D" => Expr::Flag(FLAG_DECIMAL),
+                "I" => Expr::Flag(FLAG_INTERRUPT),
+                "Z" => Expr::Flag(FLAG_ZERO),
+                "C" => Expr::Flag(FLAG_CARRY),
+                _ => return Err(self.error(format!("unknown register or flag '{}'", name))),
+            },
+            _ => return Err(self.error("expected a value".to_string())),
+        };
+        self.pos += 1;
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::flat::FlatBus;
+
+    /// A CPU with A=1, X=2, Y=0, SP=$FD, P=$24 (I set) and PC=$C000
+    fn cpu() -> CPU {
+        let mut cpu = CPU::new();
+        cpu.register_a = 1;
+        cpu.register_x = 2;
+        cpu.register_y = 0;
+        cpu.stack_pointer = 0xFD;
+        cpu.status = 0x24;
+        cpu.program_counter = 0xC000;
+        cpu
+    }
+
+    fn value(source: &str) -> u32 {
+        let mut bus = FlatBus::new();
+        bus.load(0x0200, &[0x42, 0x01]);
+        bus.load(0x1234, &[0x99]);
+        let condition = Condition::parse(source).unwrap_or_else(|err| panic!("{}", err));
+        condition.value(&cpu(), &bus)
+    }
+
+    fn error(source: &str) -> ConditionError {
+        Condition::parse(source).expect_err(source)
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(value("$2A"), 42);
+        assert_eq!(value("0x2a"), 42);
+        assert_eq!(value("0X2A"), 42);
+        assert_eq!(value("%101010"), 42);
+        assert_eq!(value("42"), 42);
+        assert_eq!(value("$FFFF"), 0xFFFF);
+    }
+
+    #[test]
+    fn registers_and_flags() {
+        assert_eq!(value("A"), 1);
+        assert_eq!(value("x"), 2);
+        assert_eq!(value("SP"), 0xFD);
+        assert_eq!(value("P"), 0x24);
+        assert_eq!(value("pc"), 0xC000);
+        assert_eq!(value("I"), 1);
+        assert_eq!(value("C"), 0);
+    }
+
+    #[test]
+    fn memory_reads() {
+        assert_eq!(value("[$0200]"), 0x42);
+        assert_eq!(value("[$0200] == $42"), 1);
+        // The address is itself an expression
+        assert_eq!(value("[$0200 | X]"), 0x00);
+        assert_eq!(value("[$0201 & $FFFF]"), 0x01);
+        assert_eq!(value("[[$0201] | $0200]"), 0x01);
+        assert_eq!(value("[$1234] > $80"), 1);
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        // Read as (A == 1 && X == 2) || Y
+        assert_eq!(value("A == 1 && X == 2 || Y"), 1);
+        assert_eq!(value("A == 0 && X == 2 || Y"), 0);
+        // Read as Y || (A == 1 && X == 3)
+        assert_eq!(value("Y || A == 1 && X == 3"), 0);
+        assert_eq!(value("(Y || A == 1) && X == 2"), 1);
+    }
+
+    #[test]
+    fn comparisons_bind_looser_than_bit_operators() {
+        // Read as (P & $04) == 4
+        assert_eq!(value("P & $04 == 4"), 1);
+        assert_eq!(value("A | X == 3"), 1);
+        assert_eq!(value("A | X & 0"), 1);
+        assert_eq!(value("!A"), 0);
+        assert_eq!(value("!!X"), 1);
+        assert_eq!(value("!C && I"), 1);
+        assert_eq!(value("X >= 2 && X <= 2 && X != 3 && A < X && X > A"), 1);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let err = error("A == @");
+        assert_eq!(err.message, "unexpected character '@'");
+        assert_eq!(err.position, 5);
+
+        let err = error("A == Q");
+        assert_eq!(err.message, "unknown register or flag 'Q'");
+        assert_eq!(err.position, 5);
+
+        // Missing operand at the end of input
+        let err = error("A ==");
+        assert_eq!(err.message, "expected a value");
+        assert_eq!(err.position, 4);
+
+        let err = error("(A == 1");
+        assert_eq!(err.message, "expected ')'");
+        assert_eq!(err.position, 7);
+
+        let err = error("[$0200 == 1");
+        assert_eq!(err.message, "expected ']'");
+
+        let err = error("A 1");
+        assert_eq!(err.message, "unexpected Number(1)");
+        assert_eq!(err.position, 2);
+
+        let err = error("$");
+        assert_eq!(err.message, "invalid number");
+        assert_eq!(err.position, 0);
+
+        let err = error("$FFFFFFFFF");
+        assert_eq!(err.message, "invalid number");
+
+        assert_eq!(err.to_string(), "invalid number (at column 1)");
+    }
+
+    #[test]
+    fn source_is_kept() {
+        let condition = Condition::parse("A == $42").unwrap();
+        assert_eq!(condition.source(), "A == $42");
+        assert_eq!(condition.to_string(), "A == $42");
+    }
+}
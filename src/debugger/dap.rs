@@ -0,0 +1,790 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use serde_json::{Value, json};
+
+use crate::bus::Bus;
+use crate::cpu::{
+    CpuError, FLAG_BREAK, FLAG_CARRY, FLAG_DECIMAL, FLAG_INTERRUPT, FLAG_NEGATIVE, FLAG_OVERFLOW,
+    FLAG_ZERO,
+};
+use crate::debugger::Debugger;
+use crate::debugger::breakpoint::{BreakReason, BreakpointKind};
+use crate::debugger::condition::Condition;
+use crate::debugger::debuginfo::{DebugInfo, SourceLine};
+
+/// The only thread we report
+const THREAD_ID: u64 = 1;
+
+/// Stop description when stepping back runs out of history
+const HISTORY_START: &str = "start of history";
+
+/// `variablesReference` values of the scopes
+const REGISTERS_REF: u64 = 1;
+const FLAGS_REF: u64 = 2;
+
+/// Flags in the order they appear in P, high bit first
+const FLAGS: [(&str, u8); 7] = [
+    ("N", FLAG_NEGATIVE),
+    ("V", FLAG_OVERFLOW),
+    ("B", FLAG_BREAK),
+    ("D", FLAG_DECIMAL),
+    ("I", FLAG_INTERRUPT),
+    ("Z", FLAG_ZERO),
+    ("C", FLAG_CARRY),
+];
+
+/// Builds a bus from the ROM image named in the `launch` request.
+pub type Loader<B> = Box<dyn FnMut(&[u8]) -> Result<B, String>>;
+
+/// Debug Adapter Protocol server driving a `Debugger`, for editors such as
+/// VS Code.
+///
+/// `launch` takes `program` (a ROM image, handed to the loader), and
+/// optionally `debugInfo` (an ld65 `.dbg` file, by default the program
+/// with a `.dbg` extension), `sourceRoot` (where relative source paths in
+/// the debug info are resolved, by default the debug info's directory)
+/// and `stopOnEntry`.
+pub struct DapServer<'d, 'a, B: Bus, W: Write> {
+    dbg: &'d mut Debugger<'a, B>,
+    loader: Loader<B>,
+    output: W,
+    requests: Receiver<Value>,
+    seq: u64,
+
+    debug_info: DebugInfo,
+    source_root: PathBuf,
+    stop_on_entry: bool,
+
+    /// Debugger breakpoint ids set for each source path
+    source_breakpoints: HashMap<String, Vec<usize>>,
+
+    /// Set by `disconnect` and `terminate` to end the session
+    done: bool,
+
+    /// The debugger's interrupt flag before the session, put back on drop
+    host_interrupt: Option<Arc<AtomicBool>>,
+}
+
+/// How to resume execution
+#[derive(Clone, Copy)]
+enum Resume {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+    StepBack,
+    ReverseContinue,
+}
+
+/// Serves one debug session: requests are read from `input` and
+/// responses and events written to `output` (usually stdin and stdout).
+pub fn serve<B: Bus>(
+    dbg: &mut Debugger<B>,
+    loader: Loader<B>,
+    input: impl Read + Send + 'static,
+    output: impl Write,
+) -> io::Result<()> {
+    DapServer::new(dbg, loader, input, output).run()
+}
+
+impl<'d, 'a, B: Bus, W: Write> DapServer<'d, 'a, B, W> {
+    /// Replaces the debugger's interrupt flag so `pause` can break into a
+    /// run; the previous flag is restored when the server is dropped.
+    pub fn new(
+        dbg: &'d mut Debugger<'a, B>,
+        loader: Loader<B>,
+        input: impl Read + Send + 'static,
+        output: W,
+    ) -> Self {
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let host_interrupt = dbg.set_interrupt_flag(Some(Arc::clone(&interrupt)));
+
+        // Requests are read on a separate thread so `pause` is seen while
+        // the CPU is running
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || read_messages(input, sender, interrupt));
+
+        Self {
+            dbg,
+            loader,
+            output,
+            requests,
+            seq: 0,
+            debug_info: DebugInfo::default(),
+            source_root: PathBuf::new(),
+            stop_on_entry: false,
+            source_breakpoints: HashMap::new(),
+            done: false,
+            host_interrupt,
+        }
+    }
+
+    /// Handles requests until the client disconnects or closes the input.
+    pub fn run(&mut self) -> io::Result<()> {
+        while !self.done {
+            let Ok(message) = self.requests.recv() else {
+                return Ok(());
+            };
+            if message["type"] == "request" {
+                self.handle(&message)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, request: &Value) -> io::Result<()> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsHitConditionalBreakpoints": true,
+                "supportsSetVariable": true,
+                "supportsReadMemoryRequest": true,
+                "supportsWriteMemoryRequest": true,
+                "supportsTerminateRequest": true,
+                "supportsStepBack": true,
+            })),
+            "launch" => self.launch(args),
+            "setBreakpoints" => self.set_breakpoints(args),
+            "setExceptionBreakpoints" => Ok(json!({})),
+            "configurationDone" => Ok(json!({})),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "6502" }] })),
+            "stackTrace" => Ok(self.stack_trace(args)),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS_REF, "expensive": false },
+                { "name": "Flags", "variablesReference": FLAGS_REF, "expensive": false },
+            ]})),
+            "variables" => Ok(self.variables(args)),
+            "setVariable" => self.set_variable(args),
+            "evaluate" => self.evaluate(args),
+            "readMemory" => self.read_memory(args),
+            "writeMemory" => self.write_memory(args),
+            "continue" => Ok(json!({ "allThreadsContinued": true })),
+            "next" | "stepIn" | "stepOut" | "stepBack" | "pause" => Ok(json!({})),
+            "reverseContinue" => Ok(json!({})),
+            "disconnect" | "terminate" => {
+                self.done = true;
+                Ok(json!({}))
+            }
+            other => Err(format!("unsupported request '{}'", other)),
+        };
+        self.respond(request, result)?;
+
+        // Follow-up events go out after the response
+        match command {
+            "launch" => self.send_event("initialized", json!({})),
+            "configurationDone" if self.stop_on_entry => self.send_stopped("entry", None),
+            "configurationDone" | "continue" => self.resume(Resume::Continue),
+            "next" => self.resume(Resume::StepOver),
+            "stepIn" => self.resume(Resume::StepIn),
+            "stepOut" => self.resume(Resume::StepOut),
+            "stepBack" => self.resume(Resume::StepBack),
+            "reverseContinue" => self.resume(Resume::ReverseContinue),
+            "terminate" => self.send_event("terminated", json!({})),
+            _ => Ok(()),
+        }
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let program = args["program"].as_str().ok_or("launch needs a 'program'")?;
+        let data = fs::read(program).map_err(|err| format!("{}: {}", program, err))?;
+        *self.dbg.bus = (self.loader)(&data)?;
+        self.dbg.cpu.reset(&mut *self.dbg.bus);
+        self.dbg.call_stack.clear();
+        self.dbg.history.clear();
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+
+        let debug_info = match args["debugInfo"].as_str() {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(Path::new(program).with_extension("dbg")).filter(|path| path.exists()),
+        };
+        if let Some(path) = debug_info {
+            let text =
+                fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            self.debug_info =
+                DebugInfo::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+            self.source_root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        }
+        if let Some(root) = args["sourceRoot"].as_str() {
+            self.source_root = PathBuf::from(root);
+        }
+        Ok(json!({}))
+    }
+
+    /// Replaces the breakpoints of one source file
+    fn set_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["source"]["path"]
+            .as_str()
+            .ok_or("setBreakpoints needs a source path")?
+            .to_string();
+
+        for id in self.source_breakpoints.remove(&path).unwrap_or_default() {
+            self.dbg.remove_breakpoint(id);
+        }
+
+        let mut ids = Vec::new();
+        let mut results = Vec::new();
+        for requested in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_u64().unwrap_or(0) as u32;
+            let result = self.add_source_breakpoint(&path, line, requested, &mut ids);
+            results.push(match result {
+                Ok((id, line)) => json!({ "id": id, "verified": true, "line": line }),
+                Err(message) => json!({ "verified": false, "line": line, "message": message }),
+            });
+        }
+
+        self.source_breakpoints.insert(path, ids);
+        Ok(json!({ "breakpoints": results }))
+    }
+
+    /// Adds execution breakpoints for every address generated by `line`.
+    /// Returns the first breakpoint id and the line actually used.
+    fn add_source_breakpoint(
+        &mut self,
+        path: &str,
+        line: u32,
+        requested: &Value,
+        ids: &mut Vec<usize>,
+    ) -> Result<(usize, u32), String> {
+        let condition = requested["condition"]
+            .as_str()
+            .filter(|text| !text.trim().is_empty())
+            .map(Condition::parse)
+            .transpose()
+            .map_err(|err| err.to_string())?;
+
+        // A hit condition of N breaks on the Nth hit
+        let ignore_count = match requested["hitCondition"].as_str() {
+            Some(text) if !text.trim().is_empty() => {
+                let hits: u64 = text
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("hit condition must be a number, got '{}'", text))?;
+                hits.saturating_sub(1)
+            }
+            _ => 0,
+        };
+
+        let (line, addrs) = self
+            .debug_info
+            .resolve_line(&self.source_path_key(path), line)
+            .ok_or("no code at this line")?;
+
+        let first = ids.len();
+        for addr in addrs {
+            let id = self
+                .dbg
+                .add_breakpoint_kind(BreakpointKind::Execute(addr..=addr));
+            let bp = self
+                .dbg
+                .breakpoint_mut(id)
+                .expect("breakpoint was just added");
+            bp.condition = condition.clone();
+            bp.ignore_count = ignore_count;
+            ids.push(id);
+        }
+        Ok((ids[first], line))
+    }
+
+    /// Path relative to the source root if possible, so it matches the
+    /// (usually relative) file names in the debug info
+    fn source_path_key(&self, path: &str) -> String {
+        Path::new(path)
+            .strip_prefix(&self.source_root)
+            .map(|relative| relative.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| path.to_string())
+    }
+
+    /// Innermost frame at PC, then one frame per active call or interrupt
+    fn stack_trace(&self, args: &Value) -> Value {
+        let calls = self.dbg.call_stack.frames();
+
+        // (location, entry point of the enclosing subroutine)
+        let mut locations = vec![(self.dbg.cpu.program_counter, calls.last().map(|f| f.target))];
+        for (depth, frame) in calls.iter().enumerate().rev() {
+            let entry = depth.checked_sub(1).map(|outer| calls[outer].target);
+            locations.push((frame.caller, entry));
+        }
+
+        let start = args["startFrame"].as_u64().unwrap_or(0) as usize;
+        let levels = match args["levels"].as_u64() {
+            Some(0) | None => locations.len(),
+            Some(levels) => levels as usize,
+        };
+
+        let frames: Vec<Value> = locations
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(levels)
+            .map(|(id, &(addr, entry))| {
+                let mut frame = json!({
+                    "id": id,
+                    "name": self.frame_name(addr, entry),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("0x{:04X}", addr),
+                });
+                if let Some(source) = self.debug_info.source_for(addr) {
+                    frame["source"] = self.source_json(source);
+                    frame["line"] = json!(source.line);
+                    frame["column"] = json!(1);
+                }
+                frame
+            })
+            .collect();
+
+        json!({ "stackFrames": frames, "totalFrames": locations.len() })
+    }
+
+    /// Names a frame after its subroutine's label, or the nearest label
+    fn frame_name(&self, addr: u16, entry: Option<u16>) -> String {
+        let symbol = match entry {
+            Some(entry) => self
+                .debug_info
+                .symbol_for(entry)
+                .filter(|&(_, offset)| offset == 0),
+            None => self.debug_info.symbol_for(addr),
+        };
+        match symbol {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{}+{}", name, offset),
+            None => format!("${:04X}", entry.unwrap_or(addr)),
+        }
+    }
+
+    fn source_json(&self, source: &SourceLine) -> Value {
+        let path = self.source_root.join(&source.file);
+        let name = Path::new(&source.file)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| source.file.clone());
+        json!({ "name": name, "path": path.to_string_lossy() })
+    }
+
+    fn variables(&self, args: &Value) -> Value {
+        let cpu = &*self.dbg.cpu;
+        let variables = match args["variablesReference"].as_u64() {
+            Some(REGISTERS_REF) => vec![
+                register("A", cpu.register_a, None),
+                register("X", cpu.register_x, None),
+                register("Y", cpu.register_y, None),
+                register(
+                    "SP",
+                    cpu.stack_pointer,
+                    Some(0x0100 | cpu.stack_pointer as u16),
+                ),
+                register("P", cpu.status, None),
+                json!({
+                    "name": "PC",
+                    "value": format!("${:04X}", cpu.program_counter),
+                    "variablesReference": 0,
+                    "memoryReference": format!("0x{:04X}", cpu.program_counter),
+                }),
+            ],
+            Some(FLAGS_REF) => FLAGS
+                .iter()
+                .map(|&(name, flag)| {
+                    json!({
+                        "name": name,
+                        "value": if cpu.status & flag != 0 { "1" } else { "0" },
+                        "variablesReference": 0,
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        json!({ "variables": variables })
+    }
+
+    /// Sets a register or flag. The value is an expression, e.g. `$42`.
+    fn set_variable(&mut self, args: &Value) -> Result<Value, String> {
+        let name = args["name"].as_str().unwrap_or_default();
+        let text = args["value"].as_str().unwrap_or_default();
+        let condition = Condition::parse(text).map_err(|err| err.to_string())?;
+        let value = condition.value(self.dbg.cpu, self.dbg.bus);
+
+        let cpu = &mut *self.dbg.cpu;
+        let shown = match (args["variablesReference"].as_u64(), name) {
+            (Some(REGISTERS_REF), "PC") => {
+                cpu.program_counter = value as u16;
+                format!("${:04X}", cpu.program_counter)
+            }
+            (Some(REGISTERS_REF), _) => {
+                let target = match name {
+                    "A" => &mut cpu.register_a,
+                    "X" => &mut cpu.register_x,
+                    "Y" => &mut cpu.register_y,
+                    "SP" => &mut cpu.stack_pointer,
+                    "P" => &mut cpu.status,
+                    _ => return Err(format!("unknown register '{}'", name)),
+                };
+                *target = value as u8;
+                format!("${:02X}", *target)
+            }
+            (Some(FLAGS_REF), _) => {
+                let &(_, flag) = FLAGS
+                    .iter()
+                    .find(|(flag_name, _)| *flag_name == name)
+                    .ok_or_else(|| format!("unknown flag '{}'", name))?;
+                if value != 0 {
+                    cpu.status |= flag;
+                } else {
+                    cpu.status &= !flag;
+                }
+                (if value != 0 { "1" } else { "0" }).to_string()
+            }
+            _ => return Err("unknown variable scope".into()),
+        };
+        Ok(json!({ "value": shown }))
+    }
+
+    /// Evaluates a condition-style expression (`A`, `[$0200]`, `X == 3`)
+    fn evaluate(&self, args: &Value) -> Result<Value, String> {
+        let text = args["expression"].as_str().unwrap_or_default();
+        let condition = Condition::parse(text).map_err(|err| err.to_string())?;
+        let value = condition.value(self.dbg.cpu, self.dbg.bus);
+
+        let mut result = json!({
+            "result": format!("${:X} ({})", value, value),
+            "variablesReference": 0,
+        });
+        if let Ok(addr) = u16::try_from(value) {
+            result["memoryReference"] = json!(format!("0x{:04X}", addr));
+        }
+        Ok(result)
+    }
+
+    /// Reads with `peek`, so viewing memory has no side effects
+    fn read_memory(&self, args: &Value) -> Result<Value, String> {
+        let start = memory_address(args)?;
+        let count = args["count"].as_u64().unwrap_or(0) as usize;
+
+        let readable = count.min(0x10000 - start as usize);
+        let bytes: Vec<u8> = (0..readable)
+            .map(|i| self.dbg.bus.peek(start.wrapping_add(i as u16)))
+            .collect();
+
+        Ok(json!({
+            "address": format!("0x{:04X}", start),
+            "data": encode_base64(&bytes),
+            "unreadableBytes": count - readable,
+        }))
+    }
+
+    /// Writes through the bus like the CPU would
+    fn write_memory(&mut self, args: &Value) -> Result<Value, String> {
+        let start = memory_address(args)?;
+        let data = args["data"].as_str().unwrap_or_default();
+        let bytes = decode_base64(data).ok_or("data is not valid base64")?;
+
+        let writable = bytes.len().min(0x10000 - start as usize);
+        for (i, byte) in bytes[..writable].iter().enumerate() {
+            self.dbg.bus.write(start.wrapping_add(i as u16), *byte);
+        }
+        Ok(json!({ "bytesWritten": writable }))
+    }
+
+    /// Runs the debugger and reports where it stopped
+    fn resume(&mut self, how: Resume) -> io::Result<()> {
+        let result = match how {
+            Resume::Continue => self.dbg.run_until_break(),
+            Resume::StepIn => self.dbg.step(),
+            Resume::StepOver => self.dbg.step_over(),
+            Resume::StepOut => self.dbg.step_out(),
+            Resume::StepBack => {
+                if self.dbg.reverse_step().is_none() {
+                    return self.send_stopped("pause", Some(HISTORY_START.into()));
+                }
+                return self.report_stop(Ok(()));
+            }
+            Resume::ReverseContinue => {
+                self.dbg.reverse_continue();
+                if self.dbg.last_break.is_none() {
+                    return self.send_stopped("pause", Some(HISTORY_START.into()));
+                }
+                return self.report_stop(Ok(()));
+            }
+        };
+        self.report_stop(result.map(|_| ()))
+    }
+
+    fn report_stop(&mut self, result: Result<(), CpuError>) -> io::Result<()> {
+        for mismatch in self.dbg.call_stack.take_mismatches() {
+            let output = format!("call stack: {}\n", mismatch);
+            self.send_event("output", json!({ "category": "console", "output": output }))?;
+        }
+
+        if let Err(err) = result {
+            return self.send_stopped("exception", Some(err.to_string()));
+        }
+
+        match self.dbg.last_break {
+            None => self.send_stopped("step", None),
+            Some(BreakReason::Breakpoint { access: None, .. }) => {
+                self.send_stopped("breakpoint", None)
+            }
+            Some(reason @ BreakReason::Breakpoint { .. }) => {
+                self.send_stopped("data breakpoint", Some(reason.to_string()))
+            }
+            Some(BreakReason::Interrupted) => self.send_stopped("pause", None),
+            Some(reason) => self.send_stopped("pause", Some(reason.to_string())),
+        }
+    }
+
+    fn send_stopped(&mut self, reason: &str, text: Option<String>) -> io::Result<()> {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(text) = text {
+            body["description"] = json!(text.clone());
+            body["text"] = json!(text);
+        }
+        if let Some(BreakReason::Breakpoint { id, .. }) = self.dbg.last_break {
+            body["hitBreakpointIds"] = json!([id]);
+        }
+        self.send_event("stopped", body)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+        });
+        match result {
+            Ok(body) => {
+                response["success"] = json!(true);
+                response["body"] = body;
+            }
+            Err(message) => {
+                response["success"] = json!(false);
+                response["message"] = json!(message);
+            }
+        }
+        self.send(response)
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    /// Frames a message with its `Content-Length` header
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        self.output.flush()
+    }
+}
+
+impl<B: Bus, W: Write> Drop for DapServer<'_, '_, B, W> {
+    fn drop(&mut self) {
+        self.dbg.set_interrupt_flag(self.host_interrupt.take());
+    }
+}
+
+/// Reader thread: splits the input into messages and raises the interrupt
+/// flag as soon as a `pause` request arrives
+fn read_messages(input: impl Read, sender: Sender<Value>, interrupt: Arc<AtomicBool>) {
+    let mut reader = BufReader::new(input);
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            match reader.read_line(&mut header) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+
+        let Some(length) = length else {
+            continue;
+        };
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        let Ok(message) = serde_json::from_slice::<Value>(&body) else {
+            continue;
+        };
+
+        if message["command"] == "pause" {
+            interrupt.store(true, Ordering::Relaxed);
+        }
+        if sender.send(message).is_err() {
+            return;
+        }
+    }
+}
+
+fn register(name: &str, value: u8, memory: Option<u16>) -> Value {
+    let mut variable = json!({
+        "name": name,
+        "value": format!("${:02X}", value),
+        "variablesReference": 0,
+    });
+    if let Some(addr) = memory {
+        variable["memoryReference"] = json!(format!("0x{:04X}", addr));
+    }
+    variable
+}
+
+/// `memoryReference` (`0x1234` or `$1234`) plus the optional `offset`
+fn memory_address(args: &Value) -> Result<u16, String> {
+    let reference = args["memoryReference"].as_str().unwrap_or_default();
+    let digits = reference
+        .strip_prefix("0x")
+        .or_else(|| reference.strip_prefix('$'))
+        .unwrap_or(reference);
+    let base = i64::from_str_radix(digits, 16)
+        .map_err(|_| format!("bad memory reference '{}'", reference))?;
+    let addr = base + args["offset"].as_i64().unwrap_or(0);
+    u16::try_from(addr).map_err(|_| {
+        let sign = if addr < 0 { "-" } else { "" };
+        format!(
+            "address {}{:#X} is outside the 64K space",
+            sign,
+            addr.unsigned_abs()
+        )
+    })
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut group = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|&c| c != b'=') {
+        let value = BASE64.iter().position(|&digit| digit == c)? as u32;
+        group = group << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+            group &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (&[0x00, 0xFF, 0xFE, 0x3F], "AP/+Pw=="),
+        ];
+        for (bytes, text) in cases {
+            assert_eq!(encode_base64(bytes), text);
+            assert_eq!(decode_base64(text).as_deref(), Some(bytes));
+        }
+
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode_base64(&encode_base64(&all)), Some(all));
+        // Padding is optional
+        assert_eq!(decode_base64("Zg"), Some(b"f".to_vec()));
+        assert_eq!(decode_base64("Zm9v!"), None);
+    }
+
+    #[test]
+    fn memory_references() {
+        let address = |args: Value| memory_address(&args);
+        assert_eq!(address(json!({ "memoryReference": "0x8000" })), Ok(0x8000));
+        assert_eq!(address(json!({ "memoryReference": "$c000" })), Ok(0xC000));
+        assert_eq!(address(json!({ "memoryReference": "200" })), Ok(0x0200));
+        assert_eq!(
+            address(json!({ "memoryReference": "0x8000", "offset": -16 })),
+            Ok(0x7FF0)
+        );
+        assert_eq!(
+            address(json!({ "memoryReference": "0xFFFF", "offset": 1 })),
+            Err("address 0x10000 is outside the 64K space".to_string())
+        );
+        assert_eq!(
+            address(json!({ "memoryReference": "0x0", "offset": -1 })),
+            Err("address -0x1 is outside the 64K space".to_string())
+        );
+        assert_eq!(
+            address(json!({ "memoryReference": "pc" })),
+            Err("bad memory reference 'pc'".to_string())
+        );
+    }
+
+    #[test]
+    fn messages_are_split_on_content_length() {
+        let mut input = frame(r#"{"seq":1,"type":"request","command":"initialize"}"#);
+        // Other headers are ignored, a header block without a length skipped
+        input.push_str("Content-Type: application/json\r\n");
+        input.push_str(&frame(r#"{"seq":2,"type":"request","command":"pause"}"#));
+        input.push_str("X-Junk: 1\r\n\r\n");
+        input.push_str(&frame("not json"));
+        input.push_str(&frame(r#"{"seq":3,"type":"request","command":"threads"}"#));
+        // Truncated at the end
+        input.push_str("Content-Length: 50\r\n\r\n{}");
+
+        let (sender, receiver) = mpsc::channel();
+        let interrupt = Arc::new(AtomicBool::new(false));
+        read_messages(input.as_bytes(), sender, Arc::clone(&interrupt));
+
+        let commands: Vec<Value> = receiver.iter().map(|m| m["command"].clone()).collect();
+        assert_eq!(
+            commands,
+            [json!("initialize"), json!("pause"), json!("threads")]
+        );
+        assert!(interrupt.load(Ordering::Relaxed));
+    }
+}
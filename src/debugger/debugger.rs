@@ -1,8 +1,40 @@
-use crate::cpu::CPU;
+pub mod access;
+pub mod breakpoint;
+pub mod callstack;
+pub mod condition;
+pub mod dap;
+pub mod debuginfo;
+pub mod gdb;
+pub mod history;
+pub mod trace;
+
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use crate::bus::Bus;
-use crate::disassembler::disassemble;
+use crate::bus::access::{AccessKind, MemoryAccess};
+use crate::cpu::{CPU, CpuError, StepOutcome};
+use crate::debugger::access::TapBus;
+use crate::debugger::breakpoint::{BreakReason, Breakpoint, BreakpointKind, WatchKind};
+use crate::debugger::callstack::{CallStack, StackChange};
+use crate::debugger::history::{History, UndoRecord};
+use crate::debugger::trace::TraceWriter;
+use crate::disassembler::{OPCODES, disassemble};
+
+/// Opcodes that push onto the stack (PHA, PHP, JSR, BRK)
+const PUSH_OPCODES: [u8; 4] = [0x48, 0x08, 0x20, 0x00];
+
+/// Opcodes that pull from the stack (PLA, PLP, RTS, RTI)
+const PULL_OPCODES: [u8; 4] = [0x68, 0x28, 0x60, 0x40];
+
+const JSR_OPCODE: u8 = 0x20;
+const RTS_OPCODE: u8 = 0x60;
+const RTI_OPCODE: u8 = 0x40;
 
 /// Represents one snapshot of CPU state
+#[derive(Clone, Debug)]
 pub struct DebugState {
     pub pc: u16,
     pub a: u8,
@@ -13,13 +45,58 @@ pub struct DebugState {
     pub disasm: String,
 }
 
+/// Limits that stop the run commands (`run_until_break`, `step_over`,
+/// `step_out`, `run_to`) when nothing else does. Counted per command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RunLimits {
+    /// Stop after this many steps
+    pub max_instructions: Option<u64>,
+
+    /// Stop once this many cycles have elapsed
+    pub max_cycles: Option<u64>,
+
+    /// Stop when an instruction jumps to itself (`JMP *`, a branch to
+    /// itself) and no interrupt is pending to get out of it
+    pub stop_on_self_loop: bool,
+}
+
+impl Default for RunLimits {
+    fn default() -> Self {
+        Self {
+            max_instructions: None,
+            max_cycles: None,
+            stop_on_self_loop: true,
+        }
+    }
+}
+
 /// The main debugger wrapper
 pub struct Debugger<'a, B: Bus> {
     pub cpu: &'a mut CPU,
     pub bus: &'a mut B,
-    breakpoints: Vec<u16>,
+    breakpoints: Vec<Breakpoint>,
+    next_breakpoint_id: usize,
     tracing: bool,
     trace_log: Vec<DebugState>,
+
+    /// Streaming trace, written before every instruction
+    trace_output: Option<TraceWriter<'a>>,
+
+    /// Why the last step requested a break, if it did
+    pub last_break: Option<BreakReason>,
+
+    /// Limits for the run commands
+    pub limits: RunLimits,
+
+    /// Shadow call stack of subroutine calls and interrupts, updated on
+    /// every step
+    pub call_stack: CallStack,
+
+    /// Undo records for reverse execution (see `reverse_step`)
+    pub history: History,
+
+    /// Raised by the host (e.g. a Ctrl-C handler) to break into a run
+    interrupt: Option<Arc<AtomicBool>>,
 }
 
 impl<'a, B: Bus> Debugger<'a, B> {
@@ -29,21 +106,200 @@ impl<'a, B: Bus> Debugger<'a, B> {
             cpu,
             bus,
             breakpoints: Vec::new(),
+            next_breakpoint_id: 1,
             tracing: false,
             trace_log: Vec::new(),
+            trace_output: None,
+            last_break: None,
+            limits: RunLimits::default(),
+            call_stack: CallStack::new(),
+            history: History::default(),
+            interrupt: None,
+        }
+    }
+
+    /// Share a flag the host can raise from anywhere (a signal handler,
+    /// another thread) to stop the current run with `BreakReason::Interrupted`
+    /// (`None` removes it). Returns the previous flag, so a temporary owner
+    /// such as a GDB session can put it back.
+    pub fn set_interrupt_flag(&mut self, flag: Option<Arc<AtomicBool>>) -> Option<Arc<AtomicBool>> {
+        std::mem::replace(&mut self.interrupt, flag)
+    }
+
+    /// Add a breakpoint on an instruction address. Returns its id
+    /// (the existing one if there already is a plain breakpoint there).
+    pub fn add_breakpoint(&mut self, addr: u16) -> usize {
+        let kind = BreakpointKind::Execute(addr..=addr);
+        if let Some(existing) = self
+            .breakpoints
+            .iter()
+            .find(|bp| bp.kind == kind && bp.condition.is_none())
+        {
+            return existing.id;
+        }
+        self.add_breakpoint_kind(kind)
+    }
+
+    /// Add a read/write/access watchpoint on an address range. Returns its id.
+    pub fn add_watchpoint(&mut self, kind: WatchKind, range: RangeInclusive<u16>) -> usize {
+        self.add_breakpoint_kind(BreakpointKind::Watch(kind, range))
+    }
+
+    /// Add a breakpoint of any kind. Returns its id.
+    pub fn add_breakpoint_kind(&mut self, kind: BreakpointKind) -> usize {
+        let id = self.next_breakpoint_id;
+        self.next_breakpoint_id += 1;
+        self.breakpoints.push(Breakpoint::new(id, kind));
+        id
+    }
+
+    /// Remove a breakpoint. Returns false if the id is unknown.
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|bp| bp.id != id);
+        self.breakpoints.len() != before
+    }
+
+    /// Enable or disable a breakpoint. Returns false if the id is unknown.
+    pub fn set_breakpoint_enabled(&mut self, id: usize, enabled: bool) -> bool {
+        match self.breakpoint_mut(id) {
+            Some(bp) => {
+                bp.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Access a breakpoint to change its condition or ignore count.
+    pub fn breakpoint_mut(&mut self, id: usize) -> Option<&mut Breakpoint> {
+        self.breakpoints.iter_mut().find(|bp| bp.id == id)
+    }
+
+    /// All registered breakpoints, in creation order
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Counts a hit on the breakpoint at `index`.
+    /// Returns true if it should break (its ignore count is used up).
+    fn register_hit(&mut self, index: usize) -> bool {
+        let bp = &mut self.breakpoints[index];
+        bp.hit_count += 1;
+        bp.hit_count > bp.ignore_count
+    }
+
+    /// Breakpoints that depend on the instruction about to execute
+    /// (execute, opcode, BRK). Conditions see the state before execution.
+    /// Returns the indices of the matching breakpoints.
+    fn match_before(&self, pc: u16, opcode: u8) -> Vec<usize> {
+        let mut matches = Vec::new();
+        for (index, bp) in self.breakpoints.iter().enumerate() {
+            if !bp.enabled {
+                continue;
+            }
+            let hit = match &bp.kind {
+                BreakpointKind::Execute(range) => range.contains(&pc),
+                BreakpointKind::Opcode(op) => *op == opcode,
+                BreakpointKind::Brk => opcode == 0x00,
+                _ => false,
+            };
+            if hit && self.condition_holds(bp) {
+                matches.push(index);
+            }
+        }
+        matches
+    }
+
+    /// Breakpoints that depend on what the step did (watchpoints, interrupt
+    /// entry, stack wrap). Conditions see the state after execution.
+    /// `pc` and `opcode` describe the instruction the step started at.
+    /// Returns the indices of matching breakpoints with the triggering access.
+    fn match_after(
+        &self,
+        outcome: &StepOutcome,
+        accesses: &[MemoryAccess],
+        pc: u16,
+        opcode: u8,
+        sp_before: u8,
+    ) -> Vec<(usize, Option<MemoryAccess>)> {
+        let interrupt = matches!(outcome, StepOutcome::Interrupt { .. });
+
+        // Bytes of the executed instruction; fetching them is not a data read
+        let size = OPCODES[opcode as usize].size as u16;
+        let is_fetch = |access: &MemoryAccess| {
+            !interrupt && access.kind == AccessKind::Read && access.addr.wrapping_sub(pc) < size
+        };
+
+        let (pushed, pulled) = if interrupt {
+            (true, false)
+        } else {
+            (
+                PUSH_OPCODES.contains(&opcode),
+                PULL_OPCODES.contains(&opcode),
+            )
+        };
+        let sp_after = self.cpu.stack_pointer;
+
+        let mut matches = Vec::new();
+        for (index, bp) in self.breakpoints.iter().enumerate() {
+            if !bp.enabled {
+                continue;
+            }
+            let hit = match &bp.kind {
+                BreakpointKind::Watch(kind, range) => accesses
+                    .iter()
+                    .find(|a| kind.matches(a.kind) && range.contains(&a.addr) && !is_fetch(a))
+                    .map(|a| Some(*a)),
+                BreakpointKind::Interrupt(interrupt) => match *outcome {
+                    StepOutcome::Interrupt { kind, .. } if kind == *interrupt => Some(None),
+                    _ => None,
+                },
+                BreakpointKind::StackOverflow if pushed && sp_after > sp_before => Some(None),
+                BreakpointKind::StackUnderflow if pulled && sp_after < sp_before => Some(None),
+                _ => None,
+            };
+            if let Some(access) = hit
+                && self.condition_holds(bp)
+            {
+                matches.push((index, access));
+            }
         }
+        matches
     }
 
-    /// Add a breakpoint
-    pub fn add_breakpoint(&mut self, addr: u16) {
-        if !self.breakpoints.contains(&addr) {
-            self.breakpoints.push(addr);
+    fn condition_holds(&self, bp: &Breakpoint) -> bool {
+        match &bp.condition {
+            Some(condition) => condition.eval(self.cpu, self.bus),
+            None => true,
         }
     }
 
-    /// Step one instruction
-    pub fn step(&mut self) -> DebugState {
-        let (line, next_pc) = disassemble(self.bus, self.cpu.program_counter);
+    /// Execution breakpoints (execute, opcode, BRK) for the instruction at PC,
+    /// checked before it runs. Sets `last_break` and returns true if one fires.
+    /// Nothing fires if the next step will service an interrupt instead.
+    pub fn check_before(&mut self) -> bool {
+        if self.cpu.jammed || self.cpu.pending_interrupt().is_some() {
+            return false;
+        }
+
+        let pc = self.cpu.program_counter;
+        let opcode = self.bus.peek(pc);
+        for index in self.match_before(pc, opcode) {
+            if self.register_hit(index) && self.last_break.is_none() {
+                let id = self.breakpoints[index].id;
+                self.last_break = Some(BreakReason::Breakpoint { id, access: None });
+            }
+        }
+        self.last_break.is_some()
+    }
+
+    /// Step one instruction, unconditionally: execution breakpoints on it are
+    /// not checked (the run commands check them before stepping). Watchpoints
+    /// and other breakpoints triggered by the step are reported in `last_break`.
+    /// CPU errors (e.g. a JAM) are returned instead of aborting the session.
+    pub fn step(&mut self) -> Result<DebugState, CpuError> {
+        let line = disassemble(self.bus, self.cpu.program_counter);
 
         // Capture CPU state BEFORE execution
         let state = DebugState {
@@ -56,43 +312,344 @@ impl<'a, B: Bus> Debugger<'a, B> {
             disasm: line.pretty(),
         };
 
-        self.cpu.run_once(self.bus);
+        let pc = self.cpu.program_counter;
+        let opcode = self.bus.peek(pc);
+        let sp_before = self.cpu.stack_pointer;
+
+        if let Some(output) = &mut self.trace_output
+            && self.cpu.pending_interrupt().is_none()
+        {
+            output.write(self.cpu, self.bus);
+        }
+
+        // Undo information, captured before the step changes anything
+        let undo = (self.history.limit() > 0).then(|| UndoRecord {
+            state: state.clone(),
+            cycles: self.cpu.cycles,
+            nmi_pending: self.cpu.nmi_pending,
+            irq_pending: self.cpu.irq_pending,
+            jammed: self.cpu.jammed,
+            overwritten: Vec::new(),
+            call_stack: StackChange::default(),
+        });
+
+        // Cleared before running, so a failed step leaves no stale reason
+        self.last_break = None;
+
+        // Run the step through a tap so watchpoints can see every access
+        let mut tap = TapBus::new(&mut *self.bus);
+        let result = self.cpu.run_once(&mut tap);
+        let accesses = tap.accesses;
+        let undo = undo.map(|undo| UndoRecord {
+            overwritten: tap.overwritten,
+            ..undo
+        });
+        let outcome = match result {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                // A failed step may still have changed registers or memory
+                if let Some(undo) = undo {
+                    self.history.push(undo);
+                }
+                return Err(err);
+            }
+        };
+
+        let change = self.call_stack.record(
+            &outcome,
+            pc,
+            opcode,
+            sp_before,
+            self.cpu.stack_pointer,
+            self.cpu.program_counter,
+        );
+        if let Some(mut undo) = undo {
+            undo.call_stack = change;
+            self.history.push(undo);
+        }
+
+        for (index, access) in self.match_after(&outcome, &accesses, pc, opcode, sp_before) {
+            if self.register_hit(index) && self.last_break.is_none() {
+                let id = self.breakpoints[index].id;
+                self.last_break = Some(BreakReason::Breakpoint { id, access });
+            }
+        }
+
+        if let Some(event) = self.bus.take_break_event() {
+            self.last_break.get_or_insert(BreakReason::BusEvent(event));
+        }
 
         // Store trace if enabled
         if self.tracing {
             self.trace_log.push(state.clone());
         }
 
-        state
+        Ok(state)
+    }
+
+    /// Run until a breakpoint or watchpoint fires, a bus event requests
+    /// a break, a run limit is reached, the program settles in a self-loop
+    /// or the host raises the interrupt flag. The reason is in `last_break`.
+    ///
+    /// Execution breakpoints stop *before* their instruction runs; the
+    /// instruction at the starting PC always runs, so continuing from a
+    /// breakpoint doesn't stop on it again. Returns the last executed state.
+    pub fn run_until_break(&mut self) -> Result<DebugState, CpuError> {
+        self.run_while(|_, _, _| true)
+    }
+
+    /// Step one instruction, treating a JSR and everything it calls as a
+    /// single step: runs until the matching RTS returns to the next
+    /// instruction at the same stack depth.
+    /// Stops early like `run_until_break` (see `last_break`).
+    pub fn step_over(&mut self) -> Result<DebugState, CpuError> {
+        let pc = self.cpu.program_counter;
+        if self.bus.peek(pc) != JSR_OPCODE {
+            return self.step();
+        }
+
+        let return_addr = pc.wrapping_add(3);
+        let sp = self.cpu.stack_pointer;
+        self.run_while(|cpu, _, _| !(cpu.program_counter == return_addr && cpu.stack_pointer == sp))
     }
 
-    /// Run until a breakpoint is reached
-    pub fn run_until_break(&mut self) -> DebugState {
+    /// Run until the current subroutine (or interrupt handler) returns,
+    /// i.e. until an RTS or RTI pops the stack above its current depth.
+    /// Stops early like `run_until_break` (see `last_break`).
+    pub fn step_out(&mut self) -> Result<DebugState, CpuError> {
+        let sp = self.cpu.stack_pointer;
+        self.run_while(|cpu, bus, state| {
+            let opcode = bus.peek(state.pc);
+            let returned = (opcode == RTS_OPCODE || opcode == RTI_OPCODE)
+                && (cpu.stack_pointer.wrapping_sub(sp) as i8) > 0;
+            !returned
+        })
+    }
+
+    /// Run until PC reaches `addr` (run to cursor). At least one
+    /// instruction is executed, so this also works from inside a loop.
+    /// Stops early like `run_until_break` (see `last_break`).
+    pub fn run_to(&mut self, addr: u16) -> Result<DebugState, CpuError> {
+        self.run_while(|cpu, _, _| cpu.program_counter != addr)
+    }
+
+    /// Step while `keep_going` holds after each step (it sees the CPU, the bus
+    /// and the state the step started from), stopping early on breakpoints,
+    /// run limits, self-loops and the interrupt flag.
+    fn run_while(
+        &mut self,
+        mut keep_going: impl FnMut(&CPU, &B, &DebugState) -> bool,
+    ) -> Result<DebugState, CpuError> {
+        let start_cycles = self.cpu.cycles;
+        let mut executed = 0;
+        let mut last = None;
+
+        // Discard a stale interrupt request from before this run
+        self.interrupt_requested();
+
         loop {
-            let state = self.step();
+            if let Some(state) = last.take()
+                && self.check_before()
+            {
+                return Ok(state);
+            }
 
-            if self.breakpoints.contains(&state.pc) {
-                return state;
+            let state = self.step()?;
+            executed += 1;
+
+            if self.last_break.is_none() {
+                let cycles = self.cpu.cycles.wrapping_sub(start_cycles);
+                let reason = if !keep_going(self.cpu, self.bus, &state) {
+                    return Ok(state);
+                } else if self.interrupt_requested() {
+                    Some(BreakReason::Interrupted)
+                } else if self.limits.stop_on_self_loop
+                    && self.cpu.program_counter == state.pc
+                    && self.cpu.pending_interrupt().is_none()
+                {
+                    Some(BreakReason::SelfLoop { pc: state.pc })
+                } else if self
+                    .limits
+                    .max_instructions
+                    .is_some_and(|max| executed >= max)
+                {
+                    Some(BreakReason::InstructionLimit)
+                } else if self.limits.max_cycles.is_some_and(|max| cycles >= max) {
+                    Some(BreakReason::CycleLimit)
+                } else {
+                    None
+                };
+                self.last_break = reason;
             }
+
+            if self.last_break.is_some() {
+                return Ok(state);
+            }
+            last = Some(state);
         }
     }
 
-    /// Trace N instructions
-    pub fn trace_next(&mut self, count: usize) -> Vec<DebugState> {
+    /// Checks and clears the host's interrupt flag
+    fn interrupt_requested(&self) -> bool {
+        self.interrupt
+            .as_ref()
+            .is_some_and(|flag| flag.swap(false, Ordering::Relaxed))
+    }
+
+    /// Undo the last step: registers, memory written through the bus and
+    /// the call stack go back to how they were before it. Returns the state
+    /// execution is back at, or `None` if the history is empty.
+    /// Side effects inside devices (e.g. I/O registers reacting to a read)
+    /// cannot be undone.
+    pub fn reverse_step(&mut self) -> Option<DebugState> {
+        let record = self.history.pop()?;
+        let state = record.state.clone();
+        self.undo(record);
+        self.last_break = None;
+        Some(state)
+    }
+
+    /// Step backwards until an execution breakpoint is reached, a step that
+    /// wrote to a watched address has been undone, the history runs out or
+    /// the host raises the interrupt flag. The reason is in `last_break`
+    /// (`None` when the history ran out). Hit and ignore counts are left alone.
+    /// Returns the state execution is back at, or `None` if the history is empty.
+    pub fn reverse_continue(&mut self) -> Option<DebugState> {
+        self.interrupt_requested();
+        self.last_break = None;
+
+        let mut last = None;
+        while let Some(record) = self.history.pop() {
+            // Values the step wrote, as they are before undoing it
+            let writes: Vec<MemoryAccess> = record
+                .overwritten
+                .iter()
+                .map(|&(addr, _)| MemoryAccess {
+                    kind: AccessKind::Write,
+                    addr,
+                    value: self.bus.peek(addr),
+                })
+                .collect();
+
+            last = Some(record.state.clone());
+            self.undo(record);
+
+            self.last_break = self.reverse_break(&writes);
+            if self.last_break.is_none() && self.interrupt_requested() {
+                self.last_break = Some(BreakReason::Interrupted);
+            }
+            if self.last_break.is_some() {
+                break;
+            }
+        }
+        last
+    }
+
+    /// Breakpoint that stops `reverse_continue` after undoing a step that
+    /// made `writes`
+    fn reverse_break(&self, writes: &[MemoryAccess]) -> Option<BreakReason> {
+        for bp in self.breakpoints.iter().filter(|bp| bp.enabled) {
+            if let BreakpointKind::Watch(kind, range) = &bp.kind
+                && kind.matches(AccessKind::Write)
+                && let Some(access) = writes.iter().find(|a| range.contains(&a.addr))
+                && self.condition_holds(bp)
+            {
+                let access = Some(*access);
+                return Some(BreakReason::Breakpoint { id: bp.id, access });
+            }
+        }
+
+        // Back in front of an instruction with an execution breakpoint
+        // (unless the undone step was an interrupt entry)
+        if self.cpu.jammed || self.cpu.pending_interrupt().is_some() {
+            return None;
+        }
+        let pc = self.cpu.program_counter;
+        let index = *self.match_before(pc, self.bus.peek(pc)).first()?;
+        let id = self.breakpoints[index].id;
+        Some(BreakReason::Breakpoint { id, access: None })
+    }
+
+    /// Puts the CPU, memory and call stack back to before a recorded step
+    fn undo(&mut self, record: UndoRecord) {
+        for &(addr, value) in record.overwritten.iter().rev() {
+            // Skip bytes the write didn't change (e.g. ignored writes to ROM)
+            if self.bus.peek(addr) != value {
+                self.bus.write(addr, value);
+            }
+        }
+        // Restoring memory is not something the program did
+        self.bus.take_break_event();
+
+        let state = record.state;
+        self.cpu.program_counter = state.pc;
+        self.cpu.register_a = state.a;
+        self.cpu.register_x = state.x;
+        self.cpu.register_y = state.y;
+        self.cpu.stack_pointer = state.sp;
+        self.cpu.status = state.status;
+        self.cpu.cycles = record.cycles;
+        self.cpu.nmi_pending = record.nmi_pending;
+        self.cpu.irq_pending = record.irq_pending;
+        self.cpu.jammed = record.jammed;
+        self.call_stack.undo(record.call_stack);
+    }
+
+    /// Trace N instructions (stops early if the CPU reports an error)
+    pub fn trace_next(&mut self, count: usize) -> Result<Vec<DebugState>, CpuError> {
+        let was_tracing = self.tracing;
         self.trace_log.clear();
         self.tracing = true;
 
+        let mut result = Ok(());
         for _ in 0..count {
-            self.step();
+            if let Err(err) = self.step() {
+                result = Err(err);
+                break;
+            }
         }
 
-        self.tracing = false;
-        self.trace_log.clone()
+        self.tracing = was_tracing;
+        result.map(|_| std::mem::take(&mut self.trace_log))
+    }
+
+    /// Turn recording of every executed step on or off.
+    /// Recorded states are collected with `take_trace`.
+    pub fn set_tracing(&mut self, on: bool) {
+        self.tracing = on;
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.tracing
+    }
+
+    /// Stream a line per executed instruction to a writer (`None` stops).
+    /// Interrupt entries aren't instructions and get no line, as in
+    /// nestest.log. Returns the previous writer; its `finish` flushes it and
+    /// reports write errors.
+    pub fn set_trace_output(&mut self, output: Option<TraceWriter<'a>>) -> Option<TraceWriter<'a>> {
+        std::mem::replace(&mut self.trace_output, output)
+    }
+
+    pub fn trace_output(&self) -> Option<&TraceWriter<'a>> {
+        self.trace_output.as_ref()
+    }
+
+    /// Hand out the states recorded since the last call
+    pub fn take_trace(&mut self) -> Vec<DebugState> {
+        std::mem::take(&mut self.trace_log)
     }
 
     /// Print registers
     pub fn dump_registers(&self) {
-        println!(
+        self.write_registers(&mut io::stdout())
+            .expect("failed printing to stdout");
+    }
+
+    /// Write the register line printed by `dump_registers`
+    pub fn write_registers(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
             "PC={:04X}  A={:02X}  X={:02X}  Y={:02X}  SP={:02X}  STATUS={:08b}",
             self.cpu.program_counter,
             self.cpu.register_a,
@@ -100,17 +657,26 @@ impl<'a, B: Bus> Debugger<'a, B> {
             self.cpu.register_y,
             self.cpu.stack_pointer,
             self.cpu.status,
-        );
+        )
     }
 
     /// Dump RAM region
     pub fn dump_ram(&self, addr: u16, len: usize) {
+        self.write_ram(&mut io::stdout(), addr, len)
+            .expect("failed printing to stdout");
+    }
+
+    /// Write the hex dump printed by `dump_ram`
+    pub fn write_ram(&self, out: &mut impl Write, addr: u16, len: usize) -> io::Result<()> {
         for i in 0..len {
             if i % 16 == 0 {
-                print!("\n{:04X}: ", addr + i as u16);
+                write!(out, "\n{:04X}: ", addr.wrapping_add(i as u16))?;
             }
-            print!("{:02X} ", self.bus.read(addr + i as u16));
+            write!(out, "{:02X} ", self.bus.peek(addr.wrapping_add(i as u16)))?;
         }
-        println!();
+        writeln!(out)
     }
 }
+
+#[cfg(test)]
+mod tests;
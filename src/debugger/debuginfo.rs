@@ -0,0 +1,414 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+
+/// Source line types in ld65 debug info (`line ... type=N`)
+const LINE_TYPE_ASM: u32 = 0;
+
+/// Error produced when a debug info file cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugInfoError {
+    pub message: String,
+    /// 1-based line in the debug info file.
+    pub line: usize,
+}
+
+impl fmt::Display for DebugInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (line {})", self.message, self.line)
+    }
+}
+
+impl std::error::Error for DebugInfoError {}
+
+/// A position in a source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine {
+    /// File name as the assembler saw it (often relative)
+    pub file: String,
+    /// 1-based line number
+    pub line: u32,
+}
+
+/// Code generated by one source line
+#[derive(Debug, Clone)]
+struct LineSpan {
+    start: u16,
+    end: u16,
+    source: SourceLine,
+    /// Plain assembler lines win over macro expansions
+    is_asm: bool,
+}
+
+/// Address <-> source line mapping and symbols from an assembler.
+///
+/// Reads the debug info written by the cc65 linker (`ld65 --dbgfile`),
+/// the format used by most NES homebrew tool chains.
+#[derive(Debug, Clone, Default)]
+pub struct DebugInfo {
+    /// Line spans by start address
+    spans: BTreeMap<u16, Vec<LineSpan>>,
+
+    /// Labels sorted by address
+    symbols: Vec<(u16, String)>,
+}
+
+/// One `type key=value,...` record of an ld65 debug file
+struct Record<'a> {
+    kind: &'a str,
+    fields: HashMap<&'a str, &'a str>,
+    line: usize,
+}
+
+impl<'a> Record<'a> {
+    fn parse(text: &'a str, line: usize) -> Option<Self> {
+        let (kind, rest) = text.split_once(|c: char| c.is_whitespace())?;
+        let mut fields = HashMap::new();
+        for field in split_fields(rest.trim()) {
+            if let Some((key, value)) = field.split_once('=') {
+                fields.insert(key, value);
+            }
+        }
+        Some(Self { kind, fields, line })
+    }
+
+    fn error(&self, message: String) -> DebugInfoError {
+        DebugInfoError {
+            message,
+            line: self.line,
+        }
+    }
+
+    fn str(&self, key: &str) -> Result<&'a str, DebugInfoError> {
+        let value = self
+            .fields
+            .get(key)
+            .ok_or_else(|| self.error(format!("{} record without {}", self.kind, key)))?;
+        Ok(value.trim_matches('"'))
+    }
+
+    fn num(&self, key: &str) -> Result<u32, DebugInfoError> {
+        let value = self.str(key)?;
+        parse_number(value).ok_or_else(|| self.error(format!("bad number {}={}", key, value)))
+    }
+
+    /// Optional `+`-separated list of ids
+    fn ids(&self, key: &str) -> Result<Vec<u32>, DebugInfoError> {
+        match self.fields.get(key) {
+            None => Ok(Vec::new()),
+            Some(list) => list
+                .split('+')
+                .map(|id| {
+                    parse_number(id).ok_or_else(|| self.error(format!("bad id list {}", list)))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl DebugInfo {
+    /// Parses the text of an ld65 debug info file.
+    pub fn parse(text: &str) -> Result<Self, DebugInfoError> {
+        let mut files = HashMap::new();
+        let mut segments = HashMap::new();
+        let mut spans = HashMap::new();
+        let mut lines = Vec::new();
+        let mut symbols = Vec::new();
+
+        for (index, text) in text.lines().enumerate() {
+            let Some(record) = Record::parse(text, index + 1) else {
+                continue;
+            };
+            match record.kind {
+                "file" => {
+                    files.insert(record.num("id")?, record.str("name")?.to_string());
+                }
+                "seg" => {
+                    segments.insert(record.num("id")?, record.num("start")?);
+                }
+                "span" => {
+                    let span = (
+                        record.num("seg")?,
+                        record.num("start")?,
+                        record.num("size")?,
+                    );
+                    spans.insert(record.num("id")?, span);
+                }
+                "line" => lines.push(record),
+                // Only labels with a value are useful for addresses
+                "sym"
+                    if record.fields.get("type") == Some(&"lab")
+                        && record.fields.contains_key("val") =>
+                {
+                    // Values past $FFFF can't be CPU addresses
+                    if let Ok(value) = u16::try_from(record.num("val")?) {
+                        symbols.push((value, record.str("name")?.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut info = DebugInfo::default();
+        for record in &lines {
+            let file_id = record.num("file")?;
+            let file = files
+                .get(&file_id)
+                .ok_or_else(|| record.error(format!("unknown file id {}", file_id)))?;
+            let is_asm = match record.fields.get("type") {
+                Some(_) => record.num("type")? == LINE_TYPE_ASM,
+                None => true,
+            };
+
+            for span_id in record.ids("span")? {
+                let &(segment, offset, size) = spans
+                    .get(&span_id)
+                    .ok_or_else(|| record.error(format!("unknown span id {}", span_id)))?;
+                let base = segments
+                    .get(&segment)
+                    .ok_or_else(|| record.error(format!("unknown segment id {}", segment)))?;
+                if size == 0 {
+                    continue;
+                }
+
+                // A span must lie inside the 64K address space
+                let start = *base as u64 + offset as u64;
+                let end = start + size as u64 - 1;
+                let (Ok(start), Ok(end)) = (u16::try_from(start), u16::try_from(end)) else {
+                    return Err(record.error(format!(
+                        "span {} (${:X}-${:X}) is outside the 64K address space",
+                        span_id, start, end
+                    )));
+                };
+                info.spans.entry(start).or_default().push(LineSpan {
+                    start,
+                    end,
+                    source: SourceLine {
+                        file: file.clone(),
+                        line: record.num("line")?,
+                    },
+                    is_asm,
+                });
+            }
+        }
+
+        symbols.sort();
+        info.symbols = symbols;
+        Ok(info)
+    }
+
+    /// The source line that generated the code at `addr`.
+    pub fn source_for(&self, addr: u16) -> Option<&SourceLine> {
+        let mut best: Option<&LineSpan> = None;
+        for span in self.spans.range(..=addr).rev().flat_map(|(_, spans)| spans) {
+            if addr > span.end {
+                continue;
+            }
+            // Prefer plain assembler lines, then the innermost (latest) span
+            match best {
+                Some(current) if current.is_asm || !span.is_asm => {}
+                _ => best = Some(span),
+            }
+            if span.is_asm {
+                break;
+            }
+        }
+        best.map(|span| &span.source)
+    }
+
+    /// Start addresses of the code generated by `line` of the file at
+    /// `path`, moving down to the next line with code if that line has none.
+    /// `path` may be absolute; it matches a file name if it ends with it.
+    /// Returns the line actually used together with its addresses.
+    pub fn resolve_line(&self, path: &str, line: u32) -> Option<(u32, Vec<u16>)> {
+        let mut best: Option<(u32, Vec<u16>)> = None;
+        for span in self.spans.values().flatten() {
+            if !span.is_asm || span.source.line < line || !path_matches(path, &span.source.file) {
+                continue;
+            }
+            match &mut best {
+                Some((best_line, addrs)) if *best_line == span.source.line => {
+                    addrs.push(span.start);
+                }
+                Some((best_line, _)) if *best_line < span.source.line => {}
+                _ => best = Some((span.source.line, vec![span.start])),
+            }
+        }
+        best
+    }
+
+    /// The closest label at or below `addr`, with the offset from it.
+    pub fn symbol_for(&self, addr: u16) -> Option<(&str, u16)> {
+        let index = self.symbols.partition_point(|(value, _)| *value <= addr);
+        let (value, name) = self.symbols.get(index.checked_sub(1)?)?;
+        Some((name, addr - value))
+    }
+
+    /// Address of a label.
+    pub fn symbol(&self, name: &str) -> Option<u16> {
+        self.symbols
+            .iter()
+            .find(|(_, symbol)| symbol == name)
+            .map(|(value, _)| *value)
+    }
+}
+
+/// True if `path` names `file`: equal, or `file` (usually relative) is a
+/// trailing part of `path`.
+fn path_matches(path: &str, file: &str) -> bool {
+    Path::new(path).ends_with(file) || Path::new(file).ends_with(path)
+}
+
+/// Splits `a=1,b="x,y",c=2` on commas outside of quotes
+fn split_fields(text: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                fields.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&text[start..]);
+    fields
+}
+
+/// Decimal or `0x` hex
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ld65 output for a reset routine counting X up forever:
+    ///
+    /// ```text
+    ///  4  reset:
+    ///  5      ldx #0          ; $8000
+    ///  6  loop: inx           ; $8002
+    ///  7      ; keep counting
+    ///  8      jmp loop        ; $8003
+    /// ```
+    ///
+    /// plus a macro expansion (line 20) covering the INX.
+    const DBG: &str = "\
+version\tmajor=2,minor=0
+info\tcsym=0,file=1,lib=0,line=4,mod=1,scope=1,seg=1,span=3,sym=2,type=4
+file\tid=0,name=\"src/main.s\",size=120,mtime=0x5F000000,mod=0
+mod\tid=0,name=\"main.o\",file=0
+seg\tid=0,name=\"CODE\",start=0x008000,size=0x0006,addrsize=absolute,type=ro,oname=\"game.bin\",ooffs=0
+span\tid=0,seg=0,start=0,size=2
+span\tid=1,seg=0,start=2,size=1
+span\tid=2,seg=0,start=3,size=3
+line\tid=0,file=0,line=5,span=0
+line\tid=1,file=0,line=6,span=1
+line\tid=2,file=0,line=8,span=2
+line\tid=3,file=0,line=20,type=2,span=1
+sym\tid=0,name=\"reset\",addrsize=absolute,scope=0,def=0,val=0x8000,seg=0,type=lab
+sym\tid=1,name=\"loop\",addrsize=absolute,scope=0,def=1,val=0x8002,seg=0,type=lab
+sym\tid=2,name=\"COUNT\",addrsize=zeropage,scope=0,def=2,val=0x10,type=equ
+";
+
+    fn info() -> DebugInfo {
+        DebugInfo::parse(DBG).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn line(file: &str, line: u32) -> SourceLine {
+        SourceLine {
+            file: file.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn source_for_maps_every_byte_of_a_span() {
+        let info = info();
+        assert_eq!(info.source_for(0x8000), Some(&line("src/main.s", 5)));
+        assert_eq!(info.source_for(0x8001), Some(&line("src/main.s", 5)));
+        assert_eq!(info.source_for(0x8005), Some(&line("src/main.s", 8)));
+        assert_eq!(info.source_for(0x8006), None);
+        assert_eq!(info.source_for(0x7FFF), None);
+    }
+
+    #[test]
+    fn source_for_prefers_assembler_lines_over_macros() {
+        assert_eq!(info().source_for(0x8002), Some(&line("src/main.s", 6)));
+    }
+
+    #[test]
+    fn resolve_line_moves_to_the_next_line_with_code() {
+        let info = info();
+        assert_eq!(info.resolve_line("src/main.s", 5), Some((5, vec![0x8000])));
+        // Lines 1-4 and 7 have no code
+        assert_eq!(info.resolve_line("src/main.s", 1), Some((5, vec![0x8000])));
+        assert_eq!(info.resolve_line("src/main.s", 7), Some((8, vec![0x8003])));
+        // Macro expansions are not breakpoint locations
+        assert_eq!(info.resolve_line("src/main.s", 9), None);
+    }
+
+    #[test]
+    fn resolve_line_matches_path_suffixes() {
+        let info = info();
+        assert_eq!(
+            info.resolve_line("/home/me/game/src/main.s", 6),
+            Some((6, vec![0x8002]))
+        );
+        assert_eq!(info.resolve_line("main.s", 6), Some((6, vec![0x8002])));
+        assert_eq!(info.resolve_line("other.s", 6), None);
+    }
+
+    #[test]
+    fn symbols_are_labels_with_values() {
+        let info = info();
+        assert_eq!(info.symbol_for(0x8000), Some(("reset", 0)));
+        assert_eq!(info.symbol_for(0x8001), Some(("reset", 1)));
+        assert_eq!(info.symbol_for(0x8004), Some(("loop", 2)));
+        assert_eq!(info.symbol_for(0x7FFF), None);
+        assert_eq!(info.symbol("loop"), Some(0x8002));
+        // An equate, not a label
+        assert_eq!(info.symbol("COUNT"), None);
+    }
+
+    #[test]
+    fn spans_outside_the_address_space_are_rejected() {
+        let text = DBG.replace("start=0x008000,size=0x0006", "start=0x00FFFE,size=0x0006");
+        let err = DebugInfo::parse(&text).unwrap_err();
+        assert_eq!(err.line, 10);
+        assert_eq!(
+            err.message,
+            "span 1 ($10000-$10000) is outside the 64K address space"
+        );
+
+        let text = DBG.replace("start=0x008000", "start=0x018000");
+        assert_eq!(DebugInfo::parse(&text).unwrap_err().line, 9);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let text = DBG.replace("line=8,span=2", "line=8,span=7");
+        let err = DebugInfo::parse(&text).unwrap_err();
+        assert_eq!(err.to_string(), "unknown span id 7 (line 11)");
+
+        let text = DBG.replace("file=0,line=5", "file=3,line=5");
+        assert_eq!(
+            DebugInfo::parse(&text).unwrap_err().message,
+            "unknown file id 3"
+        );
+
+        let text = DBG.replace("start=0,size=2", "start=zero,size=2");
+        assert_eq!(
+            DebugInfo::parse(&text).unwrap_err().message,
+            "bad number start=zero"
+        );
+    }
+}
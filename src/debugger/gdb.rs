@@ -0,0 +1,611 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::bus::Bus;
+use crate::cpu::CpuError;
+use crate::debugger::Debugger;
+use crate::debugger::breakpoint::{BreakReason, BreakpointKind, WatchKind};
+
+/// Target description sent to GDB (`qXfer:features:read:target.xml`).
+/// Register numbers follow this order: A, X, Y, SP, P, PC.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.mos6502.core">
+    <reg name="a" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// Largest packet we accept, advertised in `qSupported`
+const PACKET_SIZE: usize = 0x1000;
+
+/// Stop reply when reverse execution reaches the start of the history
+const REPLAY_BEGIN: &str = "T05replaylog:begin;";
+
+/// Byte GDB sends (outside of a packet) to interrupt a running target
+const INTERRUPT_BYTE: u8 = 0x03;
+
+/// Something the reader thread received from GDB
+enum Incoming {
+    /// A packet with a valid checksum (already unescaped)
+    Packet(Vec<u8>),
+
+    /// A packet whose checksum didn't match; GDB will resend it after a `-`
+    BadChecksum,
+
+    /// GDB didn't get our last reply right and wants it again
+    Nack,
+}
+
+/// GDB Remote Serial Protocol stub driving a `Debugger` over TCP.
+///
+/// Supports register and memory access, software/hardware breakpoints
+/// (`Z0`/`Z1`), write/read/access watchpoints (`Z2`-`Z4`), single-step,
+/// continue and interrupting a running target with Ctrl-C.
+pub struct GdbStub<'d, 'a, B: Bus> {
+    dbg: &'d mut Debugger<'a, B>,
+    stream: TcpStream,
+    packets: Receiver<Incoming>,
+    no_ack: bool,
+
+    /// Last packet sent, resent when GDB answers `-`
+    last_reply: String,
+
+    /// Reply to `?`: why the target is stopped
+    last_stop: String,
+
+    /// Breakpoints inserted by GDB, keyed by (type, address, length)
+    breakpoints: HashMap<(u8, u16, u16), usize>,
+
+    /// Set by `k` and `D` to end the session
+    done: bool,
+
+    /// The debugger's interrupt flag before the session, put back on drop
+    host_interrupt: Option<Arc<AtomicBool>>,
+}
+
+/// Waits for one GDB connection on `listener` and serves it until GDB
+/// detaches, kills the target or disconnects.
+pub fn serve<B: Bus>(dbg: &mut Debugger<B>, listener: &TcpListener) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    GdbStub::new(dbg, stream)?.run()
+}
+
+impl<'d, 'a, B: Bus> GdbStub<'d, 'a, B> {
+    /// Wraps an accepted connection. Replaces the debugger's interrupt flag
+    /// so GDB's Ctrl-C can break into `continue`; the previous flag is
+    /// restored when the stub is dropped.
+    pub fn new(dbg: &'d mut Debugger<'a, B>, stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let reader = stream.try_clone()?;
+
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let host_interrupt = dbg.set_interrupt_flag(Some(Arc::clone(&interrupt)));
+
+        // Packets are read on a separate thread so an interrupt byte is seen
+        // while the CPU is running
+        let (sender, packets) = mpsc::channel();
+        thread::spawn(move || read_packets(reader, sender, interrupt));
+
+        Ok(Self {
+            dbg,
+            stream,
+            packets,
+            no_ack: false,
+            last_reply: String::new(),
+            last_stop: "S05".into(),
+            breakpoints: HashMap::new(),
+            done: false,
+            host_interrupt,
+        })
+    }
+
+    /// Serves packets until the session ends. Breakpoints GDB inserted are
+    /// removed afterwards.
+    pub fn run(&mut self) -> io::Result<()> {
+        let result = self.serve_packets();
+
+        for (_, id) in self.breakpoints.drain() {
+            self.dbg.remove_breakpoint(id);
+        }
+        let _ = self.stream.shutdown(Shutdown::Both);
+        result
+    }
+
+    fn serve_packets(&mut self) -> io::Result<()> {
+        while !self.done {
+            let Ok(incoming) = self.packets.recv() else {
+                // GDB disconnected
+                return Ok(());
+            };
+
+            match incoming {
+                Incoming::Packet(data) => {
+                    if !self.no_ack {
+                        self.stream.write_all(b"+")?;
+                    }
+                    let packet = String::from_utf8_lossy(&data).into_owned();
+                    if let Some(reply) = self.handle(&packet) {
+                        self.send(reply)?;
+                    }
+                }
+                Incoming::BadChecksum => {
+                    if !self.no_ack {
+                        self.stream.write_all(b"-")?;
+                    }
+                }
+                Incoming::Nack => {
+                    let reply = std::mem::take(&mut self.last_reply);
+                    self.send(reply)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Frames and sends one reply packet
+    fn send(&mut self, reply: String) -> io::Result<()> {
+        let mut body = Vec::with_capacity(reply.len());
+        for &byte in reply.as_bytes() {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                body.extend_from_slice(&[b'}', byte ^ 0x20]);
+            } else {
+                body.push(byte);
+            }
+        }
+        let checksum = body.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+        let mut frame = Vec::with_capacity(body.len() + 4);
+        frame.push(b'$');
+        frame.extend_from_slice(&body);
+        frame.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+        self.stream.write_all(&frame)?;
+
+        self.last_reply = reply;
+        Ok(())
+    }
+
+    /// Handles one packet. Returns the reply, or `None` if there is none.
+    /// Unsupported packets get an empty reply, as the protocol requires.
+    fn handle(&mut self, packet: &str) -> Option<String> {
+        let command = packet.get(..1).unwrap_or_default();
+        let args = packet.get(1..).unwrap_or_default();
+        let reply = match command {
+            "?" => self.last_stop.clone(),
+            "g" => self.read_registers(),
+            "G" => ok_or_error(self.write_registers(args)),
+            "p" => self.read_register(args).unwrap_or_else(|| "E01".into()),
+            "P" => ok_or_error(self.write_register(args)),
+            "m" => self.read_memory(args).unwrap_or_else(|| "E01".into()),
+            "M" => ok_or_error(self.write_memory(args)),
+            "Z" => ok_or_error(self.insert_breakpoint(args)),
+            "z" => ok_or_error(self.remove_breakpoint(args)),
+            "c" | "s" => {
+                if !args.is_empty() {
+                    match u16::from_str_radix(args, 16) {
+                        Ok(addr) => self.dbg.cpu.program_counter = addr,
+                        Err(_) => return Some("E01".into()),
+                    }
+                }
+                let result = if command == "c" {
+                    self.dbg.run_until_break()
+                } else {
+                    self.dbg.step()
+                };
+                self.last_stop = self.stop_reply(result.map(|_| ()));
+                self.last_stop.clone()
+            }
+            // `bs` and `bc`: reverse step and continue
+            "b" => {
+                let undone = match args {
+                    "s" => self.dbg.reverse_step().is_some(),
+                    "c" => {
+                        self.dbg.reverse_continue();
+                        self.dbg.last_break.is_some()
+                    }
+                    _ => return Some(String::new()),
+                };
+                self.last_stop = if undone {
+                    self.stop_reply(Ok(()))
+                } else {
+                    REPLAY_BEGIN.into()
+                };
+                self.last_stop.clone()
+            }
+            "H" | "T" => "OK".into(),
+            "k" => {
+                self.done = true;
+                return None;
+            }
+            "D" => {
+                self.done = true;
+                "OK".into()
+            }
+            "q" | "Q" => self.handle_query(packet),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    /// General queries (`q...`) and settings (`Q...`)
+    fn handle_query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            format!(
+                "PacketSize={:x};QStartNoAckMode+;qXfer:features:read+;ReverseStep+;ReverseContinue+",
+                PACKET_SIZE
+            )
+        } else if packet == "QStartNoAckMode" {
+            self.no_ack = true;
+            "OK".into()
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            read_chunk(TARGET_XML, range).unwrap_or_else(|| "E01".into())
+        } else if let Some(command) = packet.strip_prefix("qRcmd,") {
+            // `monitor reset` in GDB
+            match decode_hex(command).as_deref() {
+                Some(b"reset") => {
+                    self.dbg.history.clear();
+                    self.dbg.call_stack.clear();
+                    self.dbg.cpu.reset(&mut *self.dbg.bus);
+                    "OK".into()
+                }
+                _ => String::new(),
+            }
+        } else {
+            match packet {
+                "qAttached" => "1".into(),
+                "qC" => "QC1".into(),
+                "qfThreadInfo" => "m1".into(),
+                "qsThreadInfo" => "l".into(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    /// Stop reply (`S`/`T` packet) for the result of a step or continue
+    fn stop_reply(&self, result: Result<(), CpuError>) -> String {
+        if result.is_err() {
+            // The CPU jammed or hit an opcode it won't execute
+            return "S04".into();
+        }
+
+        match self.dbg.last_break {
+            Some(BreakReason::Breakpoint {
+                id,
+                access: Some(access),
+            }) => {
+                let kind = self.dbg.breakpoints().iter().find_map(|bp| match bp.kind {
+                    BreakpointKind::Watch(kind, _) if bp.id == id => Some(kind),
+                    _ => None,
+                });
+                let name = match kind {
+                    Some(WatchKind::Write) => "watch",
+                    Some(WatchKind::Read) => "rwatch",
+                    _ => "awatch",
+                };
+                format!("T05{}:{:04x};", name, access.addr)
+            }
+            Some(BreakReason::Interrupted) => "S02".into(),
+            _ => "S05".into(),
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        let cpu = &*self.dbg.cpu;
+        let [lo, hi] = cpu.program_counter.to_le_bytes();
+        encode_hex(&[
+            cpu.register_a,
+            cpu.register_x,
+            cpu.register_y,
+            cpu.stack_pointer,
+            cpu.status,
+            lo,
+            hi,
+        ])
+    }
+
+    fn write_registers(&mut self, args: &str) -> Option<()> {
+        let bytes = decode_hex(args)?;
+        let [a, x, y, sp, p, lo, hi] = bytes[..] else {
+            return None;
+        };
+        let cpu = &mut *self.dbg.cpu;
+        cpu.register_a = a;
+        cpu.register_x = x;
+        cpu.register_y = y;
+        cpu.stack_pointer = sp;
+        cpu.status = p;
+        cpu.program_counter = u16::from_le_bytes([lo, hi]);
+        Some(())
+    }
+
+    fn read_register(&self, args: &str) -> Option<String> {
+        let cpu = &*self.dbg.cpu;
+        let value = match usize::from_str_radix(args, 16).ok()? {
+            0 => cpu.register_a,
+            1 => cpu.register_x,
+            2 => cpu.register_y,
+            3 => cpu.stack_pointer,
+            4 => cpu.status,
+            5 => return Some(encode_hex(&cpu.program_counter.to_le_bytes())),
+            _ => return None,
+        };
+        Some(encode_hex(&[value]))
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<()> {
+        let (number, value) = args.split_once('=')?;
+        let bytes = decode_hex(value)?;
+        let cpu = &mut *self.dbg.cpu;
+        match (usize::from_str_radix(number, 16).ok()?, &bytes[..]) {
+            (0, &[value]) => cpu.register_a = value,
+            (1, &[value]) => cpu.register_x = value,
+            (2, &[value]) => cpu.register_y = value,
+            (3, &[value]) => cpu.stack_pointer = value,
+            (4, &[value]) => cpu.status = value,
+            (5, &[lo, hi]) => cpu.program_counter = u16::from_le_bytes([lo, hi]),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// `m addr,length`: reads with `peek`, so it has no side effects
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (addr, len) = parse_addr_len(args)?;
+        let bytes: Vec<u8> = (0..len.min(PACKET_SIZE / 2))
+            .map(|i| self.dbg.bus.peek(addr.wrapping_add(i as u16)))
+            .collect();
+        Some(encode_hex(&bytes))
+    }
+
+    /// `M addr,length:XX...`: writes through the bus like the CPU would
+    fn write_memory(&mut self, args: &str) -> Option<()> {
+        let (target, data) = args.split_once(':')?;
+        let (addr, len) = parse_addr_len(target)?;
+        let bytes = decode_hex(data)?;
+        if bytes.len() != len {
+            return None;
+        }
+        for (i, byte) in bytes.into_iter().enumerate() {
+            self.dbg.bus.write(addr.wrapping_add(i as u16), byte);
+        }
+        Some(())
+    }
+
+    /// `Z type,addr,kind`. For watchpoints `kind` is the length in bytes.
+    fn insert_breakpoint(&mut self, args: &str) -> Option<()> {
+        let key = parse_breakpoint(args)?;
+        if self.breakpoints.contains_key(&key) {
+            return Some(());
+        }
+
+        let (kind, addr, len) = key;
+        let end = addr.saturating_add(len.max(1) - 1);
+        let kind = match kind {
+            0 | 1 => BreakpointKind::Execute(addr..=addr),
+            2 => BreakpointKind::Watch(WatchKind::Write, addr..=end),
+            3 => BreakpointKind::Watch(WatchKind::Read, addr..=end),
+            4 => BreakpointKind::Watch(WatchKind::Access, addr..=end),
+            _ => return None,
+        };
+        let id = self.dbg.add_breakpoint_kind(kind);
+        self.breakpoints.insert(key, id);
+        Some(())
+    }
+
+    fn remove_breakpoint(&mut self, args: &str) -> Option<()> {
+        let key = parse_breakpoint(args)?;
+        // Removing a breakpoint that isn't there is not an error
+        if let Some(id) = self.breakpoints.remove(&key) {
+            self.dbg.remove_breakpoint(id);
+        }
+        Some(())
+    }
+}
+
+impl<B: Bus> Drop for GdbStub<'_, '_, B> {
+    fn drop(&mut self) {
+        self.dbg.set_interrupt_flag(self.host_interrupt.take());
+    }
+}
+
+/// Reader thread: splits the byte stream into packets, acks and interrupts
+fn read_packets(stream: TcpStream, sender: Sender<Incoming>, interrupt: Arc<AtomicBool>) {
+    let mut bytes = BufReader::new(stream).bytes().map_while(Result::ok);
+
+    while let Some(byte) = bytes.next() {
+        let incoming = match byte {
+            b'$' => match read_packet_body(&mut bytes) {
+                Some(incoming) => incoming,
+                None => return,
+            },
+            b'-' => Incoming::Nack,
+            INTERRUPT_BYTE => {
+                interrupt.store(true, Ordering::Relaxed);
+                continue;
+            }
+            // `+` acks and line noise
+            _ => continue,
+        };
+        if sender.send(incoming).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads the rest of a packet after `$`. Returns `None` if the stream ends.
+fn read_packet_body(bytes: &mut impl Iterator<Item = u8>) -> Option<Incoming> {
+    let mut data = Vec::new();
+    let mut checksum = 0u8;
+    loop {
+        let byte = bytes.next()?;
+        if byte == b'#' {
+            break;
+        }
+        checksum = checksum.wrapping_add(byte);
+        if byte == b'}' {
+            let escaped = bytes.next()?;
+            checksum = checksum.wrapping_add(escaped);
+            data.push(escaped ^ 0x20);
+        } else {
+            data.push(byte);
+        }
+    }
+
+    let digits = [bytes.next()?, bytes.next()?];
+    let expected = std::str::from_utf8(&digits)
+        .ok()
+        .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+    if expected == Some(checksum) {
+        Some(Incoming::Packet(data))
+    } else {
+        Some(Incoming::BadChecksum)
+    }
+}
+
+/// `OK`, or a generic error for malformed or unsupported requests
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".into(),
+        None => "E01".into(),
+    }
+}
+
+/// Answers a `qXfer` read of `offset,length` from `document`
+fn read_chunk(document: &str, range: &str) -> Option<String> {
+    let (offset, len) = range.split_once(',')?;
+    let offset = usize::from_str_radix(offset, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+
+    let rest = document.get(offset.min(document.len())..)?;
+    if rest.len() <= len {
+        Some(format!("l{}", rest))
+    } else {
+        Some(format!("m{}", &rest[..len]))
+    }
+}
+
+/// Parses `addr,length` (both hex)
+fn parse_addr_len(args: &str) -> Option<(u16, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u16::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// Parses `type,addr,kind` from a `Z`/`z` packet (ignoring any conditions)
+fn parse_breakpoint(args: &str) -> Option<(u8, u16, u16)> {
+    let mut fields = args.split(';').next()?.split(',');
+    let kind = fields.next()?.parse().ok()?;
+    let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+    let len = u16::from_str_radix(fields.next()?, 16).ok()?;
+    Some((kind, addr, len))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    // `from_str_radix` alone would also accept a sign
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a packet from `frame`, which starts right after the `$`
+    fn packet(frame: &[u8]) -> Option<Incoming> {
+        read_packet_body(&mut frame.iter().copied())
+    }
+
+    #[test]
+    fn packet_with_valid_checksum() {
+        // 'g' is $67
+        match packet(b"g#67") {
+            Some(Incoming::Packet(data)) => assert_eq!(data, b"g"),
+            _ => panic!("expected a packet"),
+        }
+        match packet(b"#00") {
+            Some(Incoming::Packet(data)) => assert_eq!(data, b""),
+            _ => panic!("expected an empty packet"),
+        }
+        // Hex digits may be upper case
+        match packet(b"m0,1#FA") {
+            Some(Incoming::Packet(data)) => assert_eq!(data, b"m0,1"),
+            _ => panic!("expected a packet"),
+        }
+    }
+
+    #[test]
+    fn packet_with_bad_checksum() {
+        assert!(matches!(packet(b"g#68"), Some(Incoming::BadChecksum)));
+        assert!(matches!(packet(b"g#zz"), Some(Incoming::BadChecksum)));
+    }
+
+    #[test]
+    fn escaped_bytes_are_unescaped_and_checksummed_as_sent() {
+        // "}\x03" is an escaped '#'; the checksum covers '}' and $03
+        let sum = b'X'.wrapping_add(b'}').wrapping_add(0x03);
+        let mut frame = b"X}\x03#".to_vec();
+        frame.extend_from_slice(format!("{:02x}", sum).as_bytes());
+        match packet(&frame) {
+            Some(Incoming::Packet(data)) => assert_eq!(data, b"X#"),
+            _ => panic!("expected a packet"),
+        }
+    }
+
+    #[test]
+    fn truncated_packet_ends_the_stream() {
+        assert!(packet(b"g").is_none());
+        assert!(packet(b"g#6").is_none());
+        assert!(packet(b"}").is_none());
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(encode_hex(&[0x00, 0xAB, 0x7F]), "00ab7f");
+        assert_eq!(decode_hex("00ab7F"), Some(vec![0x00, 0xAB, 0x7F]));
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex("+1"), None);
+    }
+
+    #[test]
+    fn breakpoint_fields() {
+        assert_eq!(parse_breakpoint("0,c000,1"), Some((0, 0xC000, 1)));
+        assert_eq!(parse_breakpoint("2,200,4"), Some((2, 0x0200, 4)));
+        // Conditions and commands after ';' are ignored
+        assert_eq!(parse_breakpoint("1,8000,1;X1,0"), Some((1, 0x8000, 1)));
+        assert_eq!(parse_breakpoint("0,c000"), None);
+        assert_eq!(parse_breakpoint("0,10000,1"), None);
+        assert_eq!(parse_breakpoint("x,c000,1"), None);
+    }
+
+    #[test]
+    fn target_description_is_read_in_chunks() {
+        assert_eq!(read_chunk("abcdef", "0,4").as_deref(), Some("mabcd"));
+        assert_eq!(read_chunk("abcdef", "4,4").as_deref(), Some("lef"));
+        assert_eq!(read_chunk("abcdef", "10,4").as_deref(), Some("l"));
+        assert_eq!(read_chunk("abcdef", "0"), None);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+
+use crate::debugger::DebugState;
+use crate::debugger::callstack::StackChange;
+
+/// Default number of steps kept for reverse execution.
+pub const DEFAULT_HISTORY_LIMIT: usize = 10_000;
+
+/// Everything needed to undo one step.
+#[derive(Debug, Clone)]
+pub struct UndoRecord {
+    /// Registers before the step
+    pub state: DebugState,
+    pub cycles: u64,
+    pub nmi_pending: bool,
+    pub irq_pending: bool,
+    pub jammed: bool,
+
+    /// Previous values of the bytes the step wrote, in write order
+    pub overwritten: Vec<(u16, u8)>,
+
+    /// What the step changed in the shadow call stack
+    pub call_stack: StackChange,
+}
+
+/// Bounded ring buffer of undo records, oldest first.
+#[derive(Debug, Clone)]
+pub struct History {
+    records: VecDeque<UndoRecord>,
+    limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    /// A history keeping the last `limit` steps (0 disables recording).
+    pub fn new(limit: usize) -> Self {
+        Self {
+            records: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Changes the limit, dropping the oldest records if there are too many.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.records.len() > limit {
+            self.records.pop_front();
+        }
+    }
+
+    /// Adds a record, dropping the oldest one when full.
+    pub fn push(&mut self, record: UndoRecord) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.records.len() == self.limit {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Takes the most recent record.
+    pub fn pop(&mut self) -> Option<UndoRecord> {
+        self.records.pop_back()
+    }
+
+    /// Recorded steps, oldest first
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &UndoRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
@@ -0,0 +1,593 @@
+//! Unit tests of the debugger's breakpoints, run commands and reverse
+//! execution.
+//!
+//! Every case runs a small program from `ORIGIN` on a flat 64K bus.
+
+use super::*;
+use crate::bus::flat::FlatBus;
+use crate::debugger::callstack::{CallFrame, FrameKind};
+use crate::debugger::condition::Condition;
+use crate::fixture::{COUNT_TO_5, NMI_HANDLER, ORIGIN, machine};
+
+#[track_caller]
+fn run(debugger: &mut Debugger<FlatBus>) -> DebugState {
+    debugger
+        .run_until_break()
+        .unwrap_or_else(|err| panic!("{}", err))
+}
+
+fn breakpoint_hit(id: usize) -> Option<BreakReason> {
+    Some(BreakReason::Breakpoint { id, access: None })
+}
+
+fn watchpoint_hit(id: usize, kind: AccessKind, addr: u16, value: u8) -> Option<BreakReason> {
+    let access = Some(MemoryAccess { kind, addr, value });
+    Some(BreakReason::Breakpoint { id, access })
+}
+
+#[test]
+fn execution_breakpoint_stops_before_its_instruction() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let id = debugger.add_breakpoint(0x0605);
+
+    let state = run(&mut debugger);
+    assert_eq!(state.pc, 0x0603);
+    assert_eq!(debugger.cpu.program_counter, 0x0605);
+    assert_eq!(debugger.cpu.register_x, 1);
+    assert_eq!(debugger.last_break, breakpoint_hit(id));
+
+    // Continuing runs the instruction under the breakpoint
+    run(&mut debugger);
+    assert_eq!(debugger.cpu.program_counter, 0x0605);
+    assert_eq!(debugger.cpu.register_x, 2);
+    assert_eq!(debugger.breakpoints()[0].hit_count, 2);
+}
+
+#[test]
+fn adding_the_same_breakpoint_twice_returns_its_id() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    let id = debugger.add_breakpoint(0x0602);
+    assert_eq!(debugger.add_breakpoint(0x0602), id);
+    assert_ne!(debugger.add_breakpoint(0x0603), id);
+    assert_eq!(debugger.breakpoints().len(), 2);
+
+    assert!(debugger.remove_breakpoint(id));
+    assert!(!debugger.remove_breakpoint(id));
+    assert_eq!(debugger.breakpoints().len(), 1);
+}
+
+#[test]
+fn ignore_count_lets_hits_pass() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let id = debugger.add_breakpoint(0x0602);
+    debugger.breakpoint_mut(id).unwrap().ignore_count = 2;
+
+    // The hits with X = 0 and X = 1 are ignored, the third one breaks
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, breakpoint_hit(id));
+    assert_eq!(debugger.cpu.register_x, 2);
+    assert_eq!(debugger.breakpoints()[0].hit_count, 3);
+
+    // Once the ignore count is used up every hit breaks
+    run(&mut debugger);
+    assert_eq!(debugger.cpu.register_x, 3);
+    assert_eq!(debugger.breakpoints()[0].hit_count, 4);
+}
+
+#[test]
+fn disabled_breakpoints_are_neither_counted_nor_reported() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let id = debugger.add_breakpoint(0x0602);
+    assert!(debugger.set_breakpoint_enabled(id, false));
+    assert!(!debugger.set_breakpoint_enabled(id + 1, false));
+
+    run(&mut debugger);
+    assert_eq!(
+        debugger.last_break,
+        Some(BreakReason::SelfLoop { pc: 0x0607 })
+    );
+    assert_eq!(debugger.breakpoints()[0].hit_count, 0);
+    assert_eq!(
+        debugger.breakpoints()[0].to_string(),
+        "#1 exec $0602 (disabled) hits=0"
+    );
+}
+
+#[test]
+fn conditional_breakpoint_only_counts_when_true() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let id = debugger.add_breakpoint(0x0602);
+    debugger.breakpoint_mut(id).unwrap().condition = Some(Condition::parse("X == 3").unwrap());
+
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, breakpoint_hit(id));
+    assert_eq!(debugger.cpu.program_counter, 0x0602);
+    assert_eq!(debugger.cpu.register_x, 3);
+    assert_eq!(debugger.breakpoints()[0].hit_count, 1);
+
+    // A conditional breakpoint doesn't stand in for a plain one
+    assert_ne!(debugger.add_breakpoint(0x0602), id);
+}
+
+#[test]
+fn opcode_and_brk_breakpoints() {
+    let (mut cpu, mut bus) = machine(&[
+        0xEA, //       $0600 NOP
+        0x00, 0x00, // $0601 BRK
+        0xE8, //       $0603 INX
+        0x4C, 0x04, 0x06, // $0604 JMP $0604
+    ]);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let brk = debugger.add_breakpoint_kind(BreakpointKind::Brk);
+    let inx = debugger.add_breakpoint_kind(BreakpointKind::Opcode(0xE8));
+
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, breakpoint_hit(brk));
+    assert_eq!(debugger.cpu.program_counter, 0x0601);
+
+    // BRK, then the handler's RTI back to $0603
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, breakpoint_hit(inx));
+    assert_eq!(debugger.cpu.program_counter, 0x0603);
+}
+
+#[test]
+fn watchpoints_ignore_instruction_fetches() {
+    let (mut cpu, mut bus) = machine(&[
+        0xA9, 0x11, //       $0600 LDA #$11
+        0x8D, 0x40, 0x06, // $0602 STA $0640
+        0xAD, 0x01, 0x06, // $0605 LDA $0601
+        0x4C, 0x08, 0x06, // $0608 JMP $0608
+    ]);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    // Covers the whole program, including the bytes being fetched
+    let read = debugger.add_watchpoint(WatchKind::Read, 0x0600..=0x06FF);
+
+    // The fetches of LDA # and STA don't count, nor does the store
+    run(&mut debugger);
+    assert_eq!(
+        debugger.last_break,
+        watchpoint_hit(read, AccessKind::Read, 0x0601, 0x11)
+    );
+    assert_eq!(debugger.cpu.program_counter, 0x0608);
+    assert_eq!(
+        debugger.last_break.unwrap().to_string(),
+        "watchpoint #1: read $0601 = $11"
+    );
+}
+
+#[test]
+fn write_watchpoint_reports_the_written_value() {
+    let (mut cpu, mut bus) = machine(&[
+        0xAD, 0x40, 0x06, // $0600 LDA $0640
+        0xA9, 0x22, //       $0603 LDA #$22
+        0x8D, 0x40, 0x06, // $0605 STA $0640
+        0xEE, 0x40, 0x06, // $0608 INC $0640
+        0x4C, 0x0B, 0x06, // $060B JMP $060B
+    ]);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let write = debugger.add_watchpoint(WatchKind::Write, 0x0640..=0x0640);
+
+    run(&mut debugger);
+    assert_eq!(
+        debugger.last_break,
+        watchpoint_hit(write, AccessKind::Write, 0x0640, 0x22)
+    );
+    assert_eq!(debugger.cpu.program_counter, 0x0608);
+
+    run(&mut debugger);
+    assert_eq!(
+        debugger.last_break,
+        watchpoint_hit(write, AccessKind::Write, 0x0640, 0x23)
+    );
+    assert_eq!(debugger.breakpoints()[0].hit_count, 2);
+    assert_eq!(debugger.bus.memory()[0x0640], 0x23);
+}
+
+#[test]
+fn access_watchpoint_with_condition_sees_the_state_after_the_step() {
+    let (mut cpu, mut bus) = machine(&[
+        0xEE, 0x40, 0x06, // $0600 INC $0640
+        0x4C, 0x00, 0x06, // $0603 JMP $0600
+    ]);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let id = debugger.add_watchpoint(WatchKind::Access, 0x0640..=0x0640);
+    debugger.breakpoint_mut(id).unwrap().condition =
+        Some(Condition::parse("[$0640] == 3").unwrap());
+
+    run(&mut debugger);
+    assert_eq!(
+        debugger.last_break,
+        watchpoint_hit(id, AccessKind::Read, 0x0640, 0x02)
+    );
+    assert_eq!(debugger.bus.memory()[0x0640], 3);
+    assert_eq!(debugger.breakpoints()[0].hit_count, 1);
+}
+
+/// A subroutine at $0610 that calls another one at $0620
+fn nested_calls() -> (CPU, FlatBus) {
+    let (cpu, mut bus) = machine(&[
+        0x20, 0x10, 0x06, // $0600 JSR $0610
+        0xE8, //             $0603 INX
+        0x4C, 0x04, 0x06, // $0604 JMP $0604
+    ]);
+    bus.load(
+        0x0610,
+        &[
+            0xA9, 0x05, //       $0610 LDA #$05
+            0x20, 0x20, 0x06, // $0612 JSR $0620
+            0x60, //             $0615 RTS
+        ],
+    );
+    bus.load(
+        0x0620,
+        &[
+            0xC8, // $0620 INY
+            0x60, // $0621 RTS
+        ],
+    );
+    (cpu, bus)
+}
+
+#[test]
+fn step_over_runs_a_call_as_one_step() {
+    let (mut cpu, mut bus) = nested_calls();
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    let state = debugger.step_over().unwrap();
+    // The last instruction run is the subroutine's RTS
+    assert_eq!(state.pc, 0x0615);
+    assert_eq!(debugger.cpu.program_counter, 0x0603);
+    assert_eq!(debugger.cpu.stack_pointer, 0xFD);
+    assert_eq!(debugger.cpu.register_a, 0x05);
+    assert_eq!(debugger.cpu.register_y, 0x01);
+    assert_eq!(debugger.last_break, None);
+
+    // Anything but a JSR is a single step
+    let state = debugger.step_over().unwrap();
+    assert_eq!(state.pc, 0x0603);
+    assert_eq!(debugger.cpu.program_counter, 0x0604);
+}
+
+#[test]
+fn step_over_stops_at_a_breakpoint_inside_the_call() {
+    let (mut cpu, mut bus) = nested_calls();
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let id = debugger.add_breakpoint(0x0620);
+
+    debugger.step_over().unwrap();
+    assert_eq!(debugger.last_break, breakpoint_hit(id));
+    assert_eq!(debugger.cpu.program_counter, 0x0620);
+    assert_eq!(debugger.cpu.register_y, 0x00);
+}
+
+#[test]
+fn step_out_returns_to_the_caller() {
+    let (mut cpu, mut bus) = nested_calls();
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    debugger.run_to(0x0620).unwrap();
+    assert_eq!(debugger.cpu.stack_pointer, 0xF9);
+
+    let state = debugger.step_out().unwrap();
+    assert_eq!(state.pc, 0x0621);
+    assert_eq!(debugger.cpu.program_counter, 0x0615);
+    assert_eq!(debugger.cpu.stack_pointer, 0xFB);
+    assert_eq!(debugger.last_break, None);
+
+    // Already at the RTS: stepping out runs just that
+    let state = debugger.step_out().unwrap();
+    assert_eq!(state.pc, 0x0615);
+    assert_eq!(debugger.cpu.program_counter, 0x0603);
+}
+
+#[test]
+fn step_out_leaves_an_interrupt_handler() {
+    let (mut cpu, mut bus) = nested_calls();
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    debugger.run_to(0x0610).unwrap();
+    debugger.cpu.trigger_nmi();
+    debugger.step().unwrap();
+    assert_eq!(debugger.cpu.program_counter, NMI_HANDLER);
+
+    let state = debugger.step_out().unwrap();
+    assert_eq!(state.pc, NMI_HANDLER);
+    assert_eq!(debugger.cpu.program_counter, 0x0610);
+    assert_eq!(debugger.cpu.stack_pointer, 0xFB);
+}
+
+#[test]
+fn run_to_stops_at_the_address() {
+    let (mut cpu, mut bus) = nested_calls();
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    debugger.run_to(0x0615).unwrap();
+    assert_eq!(debugger.cpu.program_counter, 0x0615);
+    assert_eq!(debugger.cpu.register_y, 0x01);
+    assert_eq!(debugger.last_break, None);
+}
+
+#[test]
+fn run_to_the_current_address_goes_around_the_loop() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    debugger.run_to(0x0602).unwrap();
+    assert_eq!(debugger.cpu.register_x, 0);
+
+    debugger.run_to(0x0602).unwrap();
+    assert_eq!(debugger.cpu.program_counter, 0x0602);
+    assert_eq!(debugger.cpu.register_x, 1);
+}
+
+#[test]
+fn instruction_limit_stops_a_run() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    debugger.limits.max_instructions = Some(3);
+
+    let state = run(&mut debugger);
+    assert_eq!(debugger.last_break, Some(BreakReason::InstructionLimit));
+    assert_eq!(state.pc, 0x0603);
+    assert_eq!(debugger.cpu.program_counter, 0x0605);
+
+    // The limit counts per command
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, Some(BreakReason::InstructionLimit));
+    assert_eq!(debugger.cpu.program_counter, 0x0605);
+    assert_eq!(debugger.cpu.register_x, 2);
+}
+
+#[test]
+fn cycle_limit_stops_a_run() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let start = debugger.cpu.cycles;
+    debugger.limits.max_cycles = Some(5);
+
+    // LDX, INX and CPX take 2 cycles each
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, Some(BreakReason::CycleLimit));
+    assert_eq!(debugger.cpu.cycles - start, 6);
+    assert_eq!(debugger.cpu.program_counter, 0x0605);
+}
+
+#[test]
+fn self_loop_ends_a_run() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    run(&mut debugger);
+    assert_eq!(
+        debugger.last_break,
+        Some(BreakReason::SelfLoop { pc: 0x0607 })
+    );
+    assert_eq!(debugger.cpu.register_x, 5);
+}
+
+#[test]
+fn self_loop_detection_can_be_turned_off() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    debugger.limits.stop_on_self_loop = false;
+    debugger.limits.max_instructions = Some(100);
+
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, Some(BreakReason::InstructionLimit));
+    assert_eq!(debugger.cpu.program_counter, 0x0607);
+}
+
+#[test]
+fn self_loop_waiting_for_an_interrupt_is_not_reported() {
+    let (mut cpu, mut bus) = machine(&[0x4C, 0x00, 0x06]); // $0600 JMP $0600
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    debugger.limits.max_instructions = Some(1);
+    debugger.cpu.trigger_nmi();
+
+    // Entering the NMI handler is the step; the loop is left for it
+    run(&mut debugger);
+    assert_eq!(debugger.last_break, Some(BreakReason::InstructionLimit));
+    assert_eq!(debugger.cpu.program_counter, NMI_HANDLER);
+
+    debugger.limits.max_instructions = None;
+    run(&mut debugger);
+    assert_eq!(
+        debugger.last_break,
+        Some(BreakReason::SelfLoop { pc: 0x0600 })
+    );
+}
+
+#[test]
+fn interrupt_flag_breaks_into_a_run() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let flag = Arc::new(AtomicBool::new(false));
+    debugger.set_interrupt_flag(Some(Arc::clone(&flag)));
+    debugger.limits.stop_on_self_loop = false;
+    // Only a safety net in case the flag is never seen
+    debugger.limits.max_instructions = Some(100_000_000);
+
+    // A request from before the run is discarded
+    flag.store(true, Ordering::Relaxed);
+    let raiser = std::thread::spawn({
+        let flag = Arc::clone(&flag);
+        move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            flag.store(true, Ordering::Relaxed);
+        }
+    });
+    run(&mut debugger);
+    raiser.join().unwrap();
+
+    assert_eq!(debugger.last_break, Some(BreakReason::Interrupted));
+    assert_eq!(debugger.cpu.program_counter, 0x0607);
+    assert!(!flag.load(Ordering::Relaxed));
+}
+
+#[test]
+fn execution_breakpoint_leaves_its_instruction_unexecuted() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let id = debugger.add_breakpoint(0x0602);
+    let cycles = debugger.cpu.cycles;
+
+    let state = run(&mut debugger);
+    assert_eq!(debugger.last_break, breakpoint_hit(id));
+    assert_eq!(state.pc, 0x0600);
+    assert_eq!(debugger.cpu.program_counter, 0x0602);
+    assert_eq!(debugger.cpu.register_x, 0);
+    assert_eq!(debugger.cpu.cycles - cycles, 2);
+}
+
+#[test]
+fn failed_step_clears_the_last_break() {
+    let (mut cpu, mut bus) = machine(&[
+        0x8D, 0x40, 0x06, // $0600 STA $0640
+        0x02, //             $0603 JAM
+    ]);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    debugger.add_watchpoint(WatchKind::Write, 0x0640..=0x0640);
+
+    debugger.step().unwrap();
+    assert!(debugger.last_break.is_some());
+
+    assert!(debugger.step().is_err());
+    assert_eq!(debugger.last_break, None);
+}
+
+/// Everything stepping back has to restore
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    registers: [u8; 5],
+    pc: u16,
+    cycles: u64,
+    nmi_pending: bool,
+    irq_pending: bool,
+    frames: Vec<CallFrame>,
+    memory: Vec<u8>,
+}
+
+fn snapshot(debugger: &Debugger<FlatBus>) -> Snapshot {
+    let cpu = &debugger.cpu;
+    Snapshot {
+        registers: [
+            cpu.register_a,
+            cpu.register_x,
+            cpu.register_y,
+            cpu.stack_pointer,
+            cpu.status,
+        ],
+        pc: cpu.program_counter,
+        cycles: cpu.cycles,
+        nmi_pending: cpu.nmi_pending,
+        irq_pending: cpu.irq_pending,
+        frames: debugger.call_stack.frames().to_vec(),
+        memory: debugger.bus.memory().to_vec(),
+    }
+}
+
+#[test]
+fn reverse_step_restores_every_step() {
+    let (mut cpu, mut bus) = machine(&[
+        0xA9, 0x42, //       $0600 LDA #$42
+        0x8D, 0x40, 0x06, // $0602 STA $0640
+        0x20, 0x10, 0x06, // $0605 JSR $0610
+        0x58, //             $0608 CLI
+        0x4C, 0x09, 0x06, // $0609 JMP $0609
+    ]);
+    bus.load(
+        0x0610,
+        &[
+            0xE8, // $0610 INX
+            0x60, // $0611 RTS
+        ],
+    );
+    bus.load(0x0640, &[0x11]);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+
+    let mut snapshots = Vec::new();
+    for step in 0..10 {
+        match step {
+            // Masked until the CLI, so it stays pending
+            2 => debugger.cpu.trigger_irq(),
+            // Taken inside the subroutine
+            4 => debugger.cpu.trigger_nmi(),
+            _ => {}
+        }
+        snapshots.push(snapshot(&debugger));
+        debugger.step().unwrap();
+    }
+    // The NMI arrived inside the call, the IRQ after the CLI
+    let stacks: Vec<Vec<FrameKind>> = snapshots
+        .iter()
+        .map(|snapshot| snapshot.frames.iter().map(|frame| frame.kind).collect())
+        .collect();
+    assert!(stacks.contains(&vec![FrameKind::Jsr, FrameKind::Nmi]));
+    assert!(stacks.contains(&vec![FrameKind::Irq]));
+    assert_eq!(debugger.bus.memory()[0x0640], 0x42);
+
+    while let Some(expected) = snapshots.pop() {
+        let state = debugger.reverse_step().expect("history ran out");
+        assert_eq!(state.pc, expected.pc);
+        assert_eq!(
+            snapshot(&debugger),
+            expected,
+            "{} steps in",
+            snapshots.len()
+        );
+    }
+    assert!(debugger.reverse_step().is_none());
+}
+
+#[test]
+fn reverse_continue_stops_at_a_breakpoint() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    run(&mut debugger);
+    assert_eq!(debugger.cpu.program_counter, 0x0607);
+
+    let id = debugger.add_breakpoint(0x0603);
+    let state = debugger.reverse_continue().unwrap();
+    assert_eq!(debugger.last_break, breakpoint_hit(id));
+    // Back in front of the last CPX, after the INX that made X 5
+    assert_eq!(state.pc, 0x0603);
+    assert_eq!(debugger.cpu.program_counter, 0x0603);
+    assert_eq!(debugger.cpu.register_x, 5);
+
+    debugger.reverse_continue().unwrap();
+    assert_eq!(debugger.last_break, breakpoint_hit(id));
+    assert_eq!(debugger.cpu.register_x, 4);
+    assert_eq!(debugger.breakpoints()[0].hit_count, 0);
+}
+
+#[test]
+fn reverse_continue_stops_at_the_start_of_history() {
+    let (mut cpu, mut bus) = machine(&COUNT_TO_5);
+    let mut debugger = Debugger::new(&mut cpu, &mut bus);
+    let start = snapshot(&debugger);
+    run(&mut debugger);
+
+    let state = debugger.reverse_continue().unwrap();
+    assert_eq!(debugger.last_break, None);
+    assert_eq!(state.pc, ORIGIN);
+    assert_eq!(snapshot(&debugger), start);
+    assert!(debugger.history.is_empty());
+    assert!(debugger.reverse_continue().is_none());
+
+    // With a shorter history it stops as far back as it can go: before
+    // the last CPX, BNE and the JMP that was seen looping
+    debugger.history.set_limit(3);
+    run(&mut debugger);
+    debugger.reverse_continue().unwrap();
+    assert_eq!(debugger.last_break, None);
+    assert_eq!(debugger.cpu.program_counter, 0x0603);
+    assert_eq!(debugger.cpu.register_x, 5);
+    assert!(debugger.history.is_empty());
+}
@@ -0,0 +1,342 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::disassembler::{AddrMode, OPCODES, OpcodeInfo};
+
+const JMP_ABS_OPCODE: u8 = 0x4C;
+const JSR_OPCODE: u8 = 0x20;
+const NOP_OPCODE: u8 = 0xEA;
+
+/// PPU dots per CPU cycle, per scanline and scanlines per frame (NTSC)
+const DOTS_PER_CYCLE: u64 = 3;
+const DOTS_PER_SCANLINE: u64 = 341;
+const SCANLINES_PER_FRAME: u64 = 262;
+
+/// Mnemonics of the documented instructions; nestest marks the rest with `*`
+const DOCUMENTED: [&str; 56] = [
+    "ADC", "AND", "ASL", "BCC", "BCS", "BEQ", "BIT", "BMI", "BNE", "BPL", "BRK", "BVC", "BVS",
+    "CLC", "CLD", "CLI", "CLV", "CMP", "CPX", "CPY", "DEC", "DEX", "DEY", "EOR", "INC", "INX",
+    "INY", "JMP", "JSR", "LDA", "LDX", "LDY", "LSR", "NOP", "ORA", "PHA", "PHP", "PLA", "PLP",
+    "ROL", "ROR", "RTI", "RTS", "SBC", "SEC", "SED", "SEI", "STA", "STX", "STY", "TAX", "TAY",
+    "TSX", "TXA", "TXS", "TYA",
+];
+
+/// Layout of a trace line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraceFormat {
+    /// The nestest.log / Nintendulator layout, with effective addresses and
+    /// the values there:
+    ///
+    /// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+    ///
+    /// The PPU position is derived from the cycle count (NTSC timing,
+    /// rendering off), as there is no PPU yet.
+    #[default]
+    Nestest,
+
+    /// Registers and instruction bytes only, without disassembly or
+    /// memory reads. For traces of millions of lines:
+    ///
+    /// `C000 4C F5 C5 A:00 X:00 Y:00 P:24 SP:FD CYC:7`
+    Compact,
+}
+
+/// Formats the instruction at PC, and the registers before it executes.
+/// Memory is read with `peek`, so tracing has no side effects.
+pub fn trace_line(format: TraceFormat, cpu: &CPU, bus: &impl Bus) -> String {
+    let pc = cpu.program_counter;
+    let opcode = bus.peek(pc);
+    let info = OPCODES[opcode as usize];
+    let bytes: Vec<String> = (0..info.size as u16)
+        .map(|i| format!("{:02X}", bus.peek(pc.wrapping_add(i))))
+        .collect();
+    let bytes = bytes.join(" ");
+    let registers = format!(
+        "A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+        cpu.register_a, cpu.register_x, cpu.register_y, cpu.status, cpu.stack_pointer
+    );
+
+    match format {
+        TraceFormat::Compact => format!("{:04X} {} {} CYC:{}", pc, bytes, registers, cpu.cycles),
+        TraceFormat::Nestest => {
+            let marker = if is_documented(opcode, &info) {
+                ' '
+            } else {
+                '*'
+            };
+            let operand = nestest_operand(cpu, bus, opcode, &info);
+            let instruction = format!("{} {}", nestest_mnemonic(&info), operand);
+
+            let dots = cpu.cycles * DOTS_PER_CYCLE;
+            let scanline = dots / DOTS_PER_SCANLINE % SCANLINES_PER_FRAME;
+            let dot = dots % DOTS_PER_SCANLINE;
+            format!(
+                "{:04X}  {:<8} {}{:<32}{} PPU:{:>3},{:>3} CYC:{}",
+                pc, bytes, marker, instruction, registers, scanline, dot, cpu.cycles
+            )
+        }
+    }
+}
+
+/// True for the 151 documented opcodes
+fn is_documented(opcode: u8, info: &OpcodeInfo) -> bool {
+    match info.mnemonic {
+        // Only $EA is the real NOP, and $E9 the real SBC #imm ($EB mirrors it)
+        "NOP" => opcode == NOP_OPCODE,
+        "SBC" => opcode != 0xEB,
+        mnemonic => DOCUMENTED.contains(&mnemonic),
+    }
+}
+
+/// nestest spells a few undocumented mnemonics differently
+fn nestest_mnemonic(info: &OpcodeInfo) -> &'static str {
+    match info.mnemonic {
+        "ISC" => "ISB",
+        mnemonic => mnemonic,
+    }
+}
+
+/// Operand with the effective address and the value there, as nestest
+/// prints it (`$33,X @ 34 = 00`, `($89),Y = 0300 @ 0300 = 89`, ...)
+fn nestest_operand(cpu: &CPU, bus: &impl Bus, opcode: u8, info: &OpcodeInfo) -> String {
+    let pc = cpu.program_counter;
+    let byte = bus.peek(pc.wrapping_add(1));
+    let word = u16::from_le_bytes([byte, bus.peek(pc.wrapping_add(2))]);
+    let zp_word =
+        |zp: u8| u16::from_le_bytes([bus.peek(zp as u16), bus.peek(zp.wrapping_add(1) as u16)]);
+
+    match info.mode {
+        AddrMode::Imp => String::new(),
+        AddrMode::Acc => "A".into(),
+        AddrMode::Imm => format!("#${:02X}", byte),
+        AddrMode::Zp => format!("${:02X} = {:02X}", byte, bus.peek(byte as u16)),
+        AddrMode::ZpX | AddrMode::ZpY => {
+            let (name, index) = index_register(cpu, info.mode);
+            let addr = byte.wrapping_add(index);
+            let value = bus.peek(addr as u16);
+            format!("${:02X},{} @ {:02X} = {:02X}", byte, name, addr, value)
+        }
+        AddrMode::Abs if matches!(opcode, JMP_ABS_OPCODE | JSR_OPCODE) => {
+            format!("${:04X}", word)
+        }
+        AddrMode::Abs => format!("${:04X} = {:02X}", word, bus.peek(word)),
+        AddrMode::AbsX | AddrMode::AbsY => {
+            let (name, index) = index_register(cpu, info.mode);
+            let addr = word.wrapping_add(index as u16);
+            let value = bus.peek(addr);
+            format!("${:04X},{} @ {:04X} = {:02X}", word, name, addr, value)
+        }
+        AddrMode::Ind => {
+            // JMP ($xxFF) reads the high byte from $xx00
+            let hi_addr = (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF);
+            let target = u16::from_le_bytes([bus.peek(word), bus.peek(hi_addr)]);
+            format!("(${:04X}) = {:04X}", word, target)
+        }
+        AddrMode::XInd => {
+            let pointer = byte.wrapping_add(cpu.register_x);
+            let addr = zp_word(pointer);
+            let value = bus.peek(addr);
+            format!(
+                "(${:02X},X) @ {:02X} = {:04X} = {:02X}",
+                byte, pointer, addr, value
+            )
+        }
+        AddrMode::IndY => {
+            let base = zp_word(byte);
+            let addr = base.wrapping_add(cpu.register_y as u16);
+            let value = bus.peek(addr);
+            format!(
+                "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
+                byte, base, addr, value
+            )
+        }
+        AddrMode::Rel => {
+            let target = pc.wrapping_add(2).wrapping_add(byte as i8 as u16);
+            format!("${:04X}", target)
+        }
+    }
+}
+
+fn index_register(cpu: &CPU, mode: AddrMode) -> (char, u8) {
+    match mode {
+        AddrMode::ZpY | AddrMode::AbsY => ('Y', cpu.register_y),
+        _ => ('X', cpu.register_x),
+    }
+}
+
+/// Streams trace lines to a writer (a file, stdout, a `&mut Vec<u8>`...).
+///
+/// Write errors don't interrupt execution: the first one stops the trace
+/// and is returned by `finish`.
+pub struct TraceWriter<'a> {
+    out: Box<dyn Write + 'a>,
+    format: TraceFormat,
+    lines: u64,
+    error: Option<io::Error>,
+}
+
+impl TraceWriter<'static> {
+    /// Creates (or truncates) a trace file
+    pub fn create(path: impl AsRef<Path>, format: TraceFormat) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), format))
+    }
+}
+
+impl<'a> TraceWriter<'a> {
+    pub fn new(out: impl Write + 'a, format: TraceFormat) -> Self {
+        Self {
+            out: Box::new(out),
+            format,
+            lines: 0,
+            error: None,
+        }
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        self.format
+    }
+
+    /// Lines written so far
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    /// Writes the line for the instruction at PC
+    pub fn write(&mut self, cpu: &CPU, bus: &impl Bus) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = trace_line(self.format, cpu, bus);
+        match writeln!(self.out, "{}", line) {
+            Ok(()) => self.lines += 1,
+            Err(err) => self.error = Some(err),
+        }
+    }
+
+    /// Flushes the output, or returns the first write error
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::flat::FlatBus;
+
+    /// Column of the instruction in a nestest line, after the `*` marker
+    const INSTRUCTION_COLUMN: usize = 16;
+    const REGISTERS_COLUMN: usize = 48;
+
+    fn cpu(pc: u16, [a, x, y, p, sp]: [u8; 5], cycles: u64) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.program_counter = pc;
+        cpu.register_a = a;
+        cpu.register_x = x;
+        cpu.register_y = y;
+        cpu.status = p;
+        cpu.stack_pointer = sp;
+        cpu.cycles = cycles;
+        cpu
+    }
+
+    #[track_caller]
+    fn assert_columns(line: &str, instruction: &str) {
+        assert_eq!(
+            &line[INSTRUCTION_COLUMN..][..instruction.len()],
+            instruction
+        );
+        assert_eq!(line.find("A:"), Some(REGISTERS_COLUMN));
+    }
+
+    #[test]
+    fn first_lines_of_nestest_log() {
+        let mut bus = FlatBus::new();
+        bus.load(0xC000, &[0x4C, 0xF5, 0xC5]);
+        bus.load(0xC5F5, &[0xA2, 0x00]);
+
+        let line = trace_line(
+            TraceFormat::Nestest,
+            &cpu(0xC000, [0x00, 0x00, 0x00, 0x24, 0xFD], 7),
+            &bus,
+        );
+        assert_eq!(
+            line,
+            "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+        );
+        assert_columns(&line, "JMP $C5F5");
+
+        let line = trace_line(
+            TraceFormat::Nestest,
+            &cpu(0xC5F5, [0x00, 0x00, 0x00, 0x24, 0xFD], 10),
+            &bus,
+        );
+        assert_eq!(
+            line,
+            "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10"
+        );
+    }
+
+    #[test]
+    fn undocumented_opcodes_are_marked() {
+        let mut bus = FlatBus::new();
+        bus.load(0x0047, &[0x47, 0x06]);
+        bus.load(0x0647, &[0xEB]);
+        bus.load(0xE8BA, &[0xE3, 0x45]);
+
+        let cpu = cpu(0xE8BA, [0xB2, 0x02, 0x69, 0xE5, 0xFB], 13254);
+        let line = trace_line(TraceFormat::Nestest, &cpu, &bus);
+        assert_eq!(
+            line,
+            "E8BA  E3 45    *ISB ($45,X) @ 47 = 0647 = EB    A:B2 X:02 Y:69 P:E5 SP:FB PPU:116,206 CYC:13254"
+        );
+        assert_eq!(&line[INSTRUCTION_COLUMN - 1..][..1], "*");
+        assert_columns(&line, "ISB");
+
+        // $EB is the undocumented copy of SBC #imm
+        bus.load(0xE8BA, &[0xEB, 0x40]);
+        let line = trace_line(TraceFormat::Nestest, &cpu, &bus);
+        assert_columns(&line, "SBC #$40");
+        assert_eq!(&line[INSTRUCTION_COLUMN - 1..][..1], "*");
+    }
+
+    #[test]
+    fn ppu_position_follows_the_cycle_count() {
+        let mut bus = FlatBus::new();
+        bus.load(0xC000, &[0xEA]);
+
+        // 241 scanlines and 100 dots in
+        let line = trace_line(
+            TraceFormat::Nestest,
+            &cpu(0xC000, [0x00, 0x00, 0x00, 0x24, 0xFD], 27427),
+            &bus,
+        );
+        assert!(line.ends_with(" PPU:241,100 CYC:27427"), "{}", line);
+
+        // And back to the top after a whole frame
+        let frame = SCANLINES_PER_FRAME * DOTS_PER_SCANLINE;
+        let line = trace_line(
+            TraceFormat::Nestest,
+            &cpu(0xC000, [0x00, 0x00, 0x00, 0x24, 0xFD], frame + 7),
+            &bus,
+        );
+        assert!(line.ends_with(&format!(" PPU:  0, 21 CYC:{}", frame + 7)));
+    }
+
+    #[test]
+    fn compact_lines() {
+        let bus = FlatBus::with_image(&[0x4C, 0xF5, 0xC5], 0xC000);
+        let line = trace_line(
+            TraceFormat::Compact,
+            &cpu(0xC000, [0x00, 0x00, 0x00, 0x24, 0xFD], 7),
+            &bus,
+        );
+        assert_eq!(line, "C000 4C F5 C5 A:00 X:00 Y:00 P:24 SP:FD CYC:7");
+    }
+}
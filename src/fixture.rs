@@ -0,0 +1,35 @@
+//! Machine shared by the unit and monitor tests: a flat 64K bus with a
+//! program at `ORIGIN` and every vector set up. The binary's tests reach it
+//! through the `fixture` feature.
+
+use crate::bus::flat::FlatBus;
+use crate::cpu::{CPU, IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR};
+
+/// Where programs are loaded and the reset vector points
+pub const ORIGIN: u16 = 0x0600;
+pub const IRQ_HANDLER: u16 = 0x0700;
+pub const NMI_HANDLER: u16 = 0x0780;
+
+/// Counts X from 0 to 5 around a loop at $0602, then parks at $0607.
+pub const COUNT_TO_5: [u8; 10] = [
+    0xA2, 0x00, // $0600 LDX #$00
+    0xE8, //       $0602 INX
+    0xE0, 0x05, // $0603 CPX #$05
+    0xD0, 0xFB, // $0605 BNE $0602
+    0x4C, 0x07, 0x06, // $0607 JMP $0607
+];
+
+/// A bus holding `program` at `ORIGIN`, with all vectors set up and both
+/// interrupt handlers being a plain RTI, and a CPU that was reset into it
+pub fn machine(program: &[u8]) -> (CPU, FlatBus) {
+    let mut bus = FlatBus::new();
+    bus.load(ORIGIN, program);
+    bus.load(IRQ_HANDLER, &[0x40]);
+    bus.load(NMI_HANDLER, &[0x40]);
+    bus.load(NMI_VECTOR, &NMI_HANDLER.to_le_bytes());
+    bus.load(RESET_VECTOR, &ORIGIN.to_le_bytes());
+    bus.load(IRQ_VECTOR, &IRQ_HANDLER.to_le_bytes());
+    let mut cpu = CPU::new();
+    cpu.reset(&mut bus);
+    (cpu, bus)
+}
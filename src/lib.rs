@@ -0,0 +1,11 @@
+#[path = "bus/bus.rs"]
+pub mod bus;
+#[path = "cpu/cpu.rs"]
+pub mod cpu;
+#[path = "debugger/debugger.rs"]
+pub mod debugger;
+pub mod disassembler;
+
+#[cfg(any(test, feature = "fixture"))]
+#[doc(hidden)]
+pub mod fixture;
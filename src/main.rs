@@ -1,80 +1,112 @@
-// mod bus;
-// mod cpu;
+mod monitor;
 
-// use crate::bus::{Bus, SimpleBus};
-// use crate::cpu::CPU;
+use std::env;
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-// fn main() {
-//     // ---------------------------------------------------------
-//     // ROM PROGRAM (placed at $8000)
-//     //
-//     // 8000: A9 42    LDA #$42
-//     // 8002: 8D 00 60 STA $6000
-//     // 8005: 4C 05 80 JMP $8005
-//     // ---------------------------------------------------------
-//     let rom_program: Vec<u8> = vec![
-//         0xA9, 0x42,       // LDA #$42
-//         0x8D, 0x00, 0x60, // STA $6000
-//         0x4C, 0x05, 0x80, // JMP $8005 (infinite loop)
-//     ];
+use nes6502::bus::SimpleBus;
+use nes6502::bus::ines::InesImage;
+use nes6502::bus::ram::Ram;
+use nes6502::bus::rom::Rom;
+use nes6502::cpu::CPU;
+use nes6502::debugger::{Debugger, dap, gdb};
 
-//     // ---------------------------------------------------------
-//     // Create the system bus
-//     // RAM = 32KB (0x0000 — 0x7FFF)
-//     // ROM = maps to   (0x8000 — 0xFFFF)
-//     // ---------------------------------------------------------
-//     let mut bus = SimpleBus::new(32 * 1024, &rom_program);
+use crate::monitor::Monitor;
 
-//     // ---------------------------------------------------------
-//     // Setup RESET VECTOR at $FFFC–$FFFD
-//     //
-//     // The 6502 reads $FFFC-$FFFD on reset to get the startup PC.
-//     // We want PC = $8000
-//     // ---------------------------------------------------------
-//     bus.ram.write(0xFFFC, 0x00);
-//     bus.ram.write(0xFFFD, 0x80);
+const USAGE: &str = "usage: NES6502-emulator [--gdb PORT | --dap] [ROM]";
 
-//     // ---------------------------------------------------------
-//     // Initialize CPU
-//     // ---------------------------------------------------------
-//     let mut cpu = CPU::new();
-//     cpu.reset(&mut bus);
+/// RAM placed below a ROM image
+const RAM_SIZE: usize = 0x8000;
 
-//     println!("--- CPU Reset Complete ---");
-//     println!(
-//         "PC={:04X}, A={:02X}, X={:02X}, Y={:02X}",
-//         cpu.program_counter, cpu.register_a, cpu.register_x, cpu.register_y
-//     );
-//     println!("---------------------------");
+/// Builds the bus for a ROM image: an iNES (`.nes`) file gets an NROM
+/// board, a raw image is placed at $8000 (mirrored up to $FFFF) with RAM
+/// below it.
+pub fn rom_bus(data: &[u8]) -> Result<SimpleBus, String> {
+    if InesImage::detect(data) {
+        let image = InesImage::parse(data).map_err(|err| err.to_string())?;
+        return image.nrom_bus().map_err(|err| err.to_string());
+    }
+    let rom = Rom::mirrored(data, 0x8000).map_err(|err| err.to_string())?;
+    Ok(SimpleBus::with_rom(RAM_SIZE, rom))
+}
+
+fn main() {
+    let mut gdb_port = None;
+    let mut dap_mode = false;
+    let mut rom_path = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gdb" => {
+                let port = args.next().and_then(|port| port.parse::<u16>().ok());
+                gdb_port = Some(port.unwrap_or_else(|| fail(USAGE)));
+            }
+            "--dap" => dap_mode = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
 
-//     // ---------------------------------------------------------
-//     // Run the CPU for 20 instructions
-//     // and print debug output
-//     // ---------------------------------------------------------
-//     for step in 0..20 {
-//         let pc_before = cpu.program_counter;
+    let mut cpu = CPU::new();
+    let mut bus = match &rom_path {
+        Some(path) => {
+            let data = fs::read(path).unwrap_or_else(|err| fail(&format!("{}: {}", path, err)));
+            let mut bus = rom_bus(&data).unwrap_or_else(|err| fail(&format!("{}: {}", path, err)));
+            cpu.reset(&mut bus);
+            bus
+        }
+        None => {
+            // Plain 64K of RAM; `load` in the monitor maps a ROM
+            let mut bus = SimpleBus::empty();
+            bus.attach(0x0000..=0xFFFF, Ram::new(0x10000));
+            bus
+        }
+    };
 
-//         // Read the opcode before executing
-//         let opcode = bus.read(pc_before);
+    let mut dbg = Debugger::new(&mut cpu, &mut bus);
 
-//         println!(
-//             "Step {:02}: PC={:04X}, Opcode={:02X}",
-//             step, pc_before, opcode
-//         );
+    if let Some(port) = gdb_port {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .unwrap_or_else(|err| fail(&format!("port {}: {}", port, err)));
+        eprintln!("waiting for GDB on 127.0.0.1:{}", port);
+        if let Err(err) = gdb::serve(&mut dbg, &listener) {
+            fail(&err.to_string());
+        }
+        return;
+    }
 
-//         cpu.run_once(&mut bus);
+    if dap_mode {
+        // The editor talks to us over stdin/stdout and launches the ROM itself
+        let loader: dap::Loader<SimpleBus> = Box::new(rom_bus);
+        if let Err(err) = dap::serve(&mut dbg, loader, io::stdin(), io::stdout()) {
+            fail(&err.to_string());
+        }
+        return;
+    }
+
+    // Ctrl-C breaks into a running program instead of killing the monitor
+    let interrupt = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupt);
+    if let Err(err) = ctrlc::set_handler(move || flag.store(true, Ordering::Relaxed)) {
+        eprintln!("warning: Ctrl-C will not break into programs: {}", err);
+    }
+    dbg.set_interrupt_flag(Some(interrupt));
+
+    if let Err(err) = Monitor::new(dbg, io::stdout()).run(io::stdin().lock()) {
+        fail(&err.to_string());
+    }
+}
 
-//         println!(
-//             "       -> A={:02X}, X={:02X}, Y={:02X}, PC={:04X}, RAM[$6000]={:02X}",
-//             cpu.register_a,
-//             cpu.register_x,
-//             cpu.register_y,
-//             cpu.program_counter,
-//             bus.ram.read(0x6000)
-//         );
-//         println!();
-//     }
-// }
-fn main(){
-    println!("lmao");
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
 }
@@ -0,0 +1,778 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::ops::RangeInclusive;
+
+use nes6502::bus::ines::InesImage;
+use nes6502::bus::{Bus, SimpleBus};
+use nes6502::cpu::CpuError;
+use nes6502::debugger::breakpoint::{BreakpointKind, WatchKind};
+use nes6502::debugger::condition::Condition;
+use nes6502::debugger::debuginfo::DebugInfo;
+use nes6502::debugger::trace::{TraceFormat, TraceWriter};
+use nes6502::debugger::{DebugState, Debugger};
+use nes6502::disassembler::disassemble;
+
+/// Instructions shown by `d` when no end address is given
+const DISASM_LINES: usize = 12;
+
+/// Bytes shown by `m` when no end address is given
+const DUMP_BYTES: usize = 128;
+
+/// How far back `d` looks for an instruction boundary leading to PC
+const DISASM_LOOKBEHIND: u16 = 9;
+
+/// Instructions `d` shows above PC
+const DISASM_CONTEXT: usize = 3;
+
+const HELP: &str = "\
+Execution:
+  s, step [n]                 step n instructions (into subroutines)
+  n, next [n]                 step n instructions, JSR counts as one
+  ret, finish                 run until the current subroutine returns
+  g, c, continue [addr]       continue (optionally from addr) until a break
+  until <addr>                run to addr
+  rs, rstep [n]               step n instructions backwards
+  rc, rcontinue               run backwards until a breakpoint or watched write
+  reset                       reset the CPU through the RESET vector
+Breakpoints:
+  b, break [addr[-addr]] [if cond]      break on execution (no args: list)
+  b brk|irq|nmi [if cond]               break on BRK or interrupt entry
+  w, watch [r|w|rw] addr[-addr] [if cond]  break on memory access
+  del, delete [id]            delete one breakpoint (no id: all)
+  enable <id>, disable <id>   toggle a breakpoint
+  ignore <id> <n>             let n hits pass before breaking
+State:
+  r, registers [reg=val ...]  show registers, or set A X Y SP P PC
+  m, mem [start [end]]        dump memory
+  > <addr> <byte> ...         write bytes to memory
+  d, disass [start [end]]     disassemble (default: around PC)
+  bt, backtrace               show the call stack (JSR and interrupt frames)
+  sym, symbols <file>         load labels from an ld65 debug file (--dbgfile)
+  l, load <file> [addr]       load a ROM image at $8000 and reset,
+                              or raw bytes into memory at addr
+  trace [on|off]              print every executed instruction
+  trace file <file> [compact] stream a nestest.log style (or compact) trace
+Session:
+  history                     list previous commands (!n repeats one)
+  help, ?                     this text
+  q, quit, x                  leave the monitor
+Numbers are hex ($ or 0x optional), counts are decimal.
+Pressing Enter repeats the last s, n, m or d; m and d continue where they stopped.";
+
+/// What the REPL does after a command
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Commands an empty line repeats; everything else (loading, writing
+/// memory, adding breakpoints, ...) would do harm when run twice by accident
+const REPEATABLE: [&str; 8] = ["s", "step", "n", "next", "m", "mem", "d", "disass"];
+
+/// Writes a line to the monitor's output; a failed write fails the command
+macro_rules! outln {
+    ($out:expr) => {
+        writeln!($out).map_err(|err| err.to_string())?
+    };
+    ($out:expr, $($arg:tt)*) => {
+        writeln!($out, $($arg)*).map_err(|err| err.to_string())?
+    };
+}
+
+/// Interactive machine-language monitor around a `Debugger`, in the style of
+/// the VICE and Mesen monitors. Everything it prints goes to `out`.
+pub struct Monitor<'a, W: Write> {
+    dbg: Debugger<'a, SimpleBus>,
+    out: W,
+    history: Vec<String>,
+
+    /// Command run when an empty line is entered
+    repeat: Option<String>,
+
+    /// Where `m` and `d` without arguments continue from
+    next_dump: Option<u16>,
+    next_disasm: Option<u16>,
+
+    /// Labels for backtraces, loaded with `symbols`
+    debug_info: Option<DebugInfo>,
+}
+
+impl<'a, W: Write> Monitor<'a, W> {
+    pub fn new(dbg: Debugger<'a, SimpleBus>, out: W) -> Self {
+        Self {
+            dbg,
+            out,
+            history: Vec::new(),
+            repeat: None,
+            next_dump: None,
+            next_disasm: None,
+            debug_info: None,
+        }
+    }
+
+    /// Read and execute commands until `quit` or end of input
+    pub fn run(&mut self, mut input: impl BufRead) -> io::Result<()> {
+        self.show_position().map_err(io::Error::other)?;
+
+        loop {
+            write!(self.out, "(C:${:04X}) ", self.dbg.cpu.program_counter)?;
+            self.out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(self.out)?;
+                return Ok(());
+            }
+
+            let line = line.trim();
+            let command = if line.is_empty() {
+                match &self.repeat {
+                    Some(command) => command.clone(),
+                    None => continue,
+                }
+            } else if let Some(number) = line.strip_prefix('!') {
+                match self.recall(number) {
+                    Ok(command) => {
+                        writeln!(self.out, "{}", command)?;
+                        command
+                    }
+                    Err(message) => {
+                        writeln!(self.out, "error: {}", message)?;
+                        continue;
+                    }
+                }
+            } else {
+                line.to_string()
+            };
+
+            if !line.is_empty() {
+                self.history.push(command.clone());
+            }
+            let word = command.split_whitespace().next().unwrap_or_default();
+            self.repeat = REPEATABLE.contains(&word).then(|| command.clone());
+
+            match self.execute(&command) {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Quit) => return Ok(()),
+                Err(message) => writeln!(self.out, "error: {}", message)?,
+            }
+        }
+    }
+
+    /// Looks up `!n` in the history
+    fn recall(&self, number: &str) -> Result<String, String> {
+        let index = parse_count(number)?;
+        index
+            .checked_sub(1)
+            .and_then(|i| self.history.get(i))
+            .cloned()
+            .ok_or_else(|| format!("no history entry {}", index))
+    }
+
+    /// Executes one command line
+    fn execute(&mut self, line: &str) -> Result<Flow, String> {
+        // Everything after ` if ` is a breakpoint condition
+        let (line, condition) = match line.split_once(" if ") {
+            Some((head, condition)) => (head, Some(condition)),
+            None => (line, None),
+        };
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+
+        if condition.is_some() && !matches!(command, "b" | "break" | "w" | "watch") {
+            return Err(format!("'{}' does not take a condition", command));
+        }
+
+        match command {
+            "s" | "step" => {
+                let count = optional_count(&args)?;
+                self.run_command(|dbg| {
+                    let mut state = dbg.step()?;
+                    for _ in 1..count {
+                        // `step` itself doesn't check execution breakpoints
+                        if dbg.last_break.is_some() || dbg.check_before() {
+                            break;
+                        }
+                        state = dbg.step()?;
+                    }
+                    Ok(state)
+                })?;
+            }
+            "n" | "next" => {
+                let count = optional_count(&args)?;
+                self.run_command(|dbg| {
+                    let mut state = dbg.step_over()?;
+                    for _ in 1..count {
+                        if dbg.last_break.is_some() || dbg.check_before() {
+                            break;
+                        }
+                        state = dbg.step_over()?;
+                    }
+                    Ok(state)
+                })?;
+            }
+            "ret" | "finish" => self.run_command(|dbg| dbg.step_out())?,
+            "g" | "c" | "continue" => {
+                if let Some(addr) = args.first() {
+                    self.dbg.cpu.program_counter = parse_addr(addr)?;
+                }
+                self.run_command(|dbg| dbg.run_until_break())?;
+            }
+            "until" => {
+                let addr = parse_addr(args.first().ok_or("missing address")?)?;
+                self.run_command(|dbg| dbg.run_to(addr))?;
+            }
+            "rs" | "rstep" => {
+                let count = optional_count(&args)?;
+                let mut undone = 0;
+                while undone < count && self.dbg.reverse_step().is_some() {
+                    undone += 1;
+                }
+                if undone < count {
+                    outln!(self.out, "-- stopped: start of history");
+                }
+                self.show_position()?;
+            }
+            "rc" | "rcontinue" => {
+                self.dbg.reverse_continue();
+                match &self.dbg.last_break {
+                    Some(reason) => outln!(self.out, "-- stopped: {}", reason),
+                    None => outln!(self.out, "-- stopped: start of history"),
+                }
+                self.show_position()?;
+            }
+            "reset" => {
+                // Stepping back across a reset isn't meaningful
+                self.dbg.history.clear();
+                self.dbg.call_stack.clear();
+                self.dbg.cpu.reset(&mut *self.dbg.bus);
+                self.show_position()?;
+            }
+            "b" | "break" => self.add_breakpoint(&args, condition)?,
+            "w" | "watch" => self.add_watchpoint(&args, condition)?,
+            "del" | "delete" => match args.first() {
+                Some(id) => {
+                    let id = parse_count(id)?;
+                    if !self.dbg.remove_breakpoint(id) {
+                        return Err(format!("no breakpoint #{}", id));
+                    }
+                }
+                None => {
+                    let ids: Vec<usize> = self.dbg.breakpoints().iter().map(|bp| bp.id).collect();
+                    for id in ids {
+                        self.dbg.remove_breakpoint(id);
+                    }
+                }
+            },
+            "enable" | "disable" => {
+                let id = parse_count(args.first().ok_or("missing breakpoint id")?)?;
+                if !self.dbg.set_breakpoint_enabled(id, command == "enable") {
+                    return Err(format!("no breakpoint #{}", id));
+                }
+            }
+            "ignore" => {
+                let id = parse_count(args.first().ok_or("missing breakpoint id")?)?;
+                let count = parse_count(args.get(1).ok_or("missing count")?)?;
+                let bp = self
+                    .dbg
+                    .breakpoint_mut(id)
+                    .ok_or_else(|| format!("no breakpoint #{}", id))?;
+                bp.ignore_count = bp.hit_count + count as u64;
+            }
+            "r" | "registers" => {
+                for assignment in &args {
+                    self.set_register(assignment)?;
+                }
+                self.dbg
+                    .write_registers(&mut self.out)
+                    .map_err(|err| err.to_string())?;
+            }
+            "m" | "mem" => {
+                let start = match args.first() {
+                    Some(addr) => parse_addr(addr)?,
+                    None => self.next_dump.unwrap_or(self.dbg.cpu.program_counter),
+                };
+                let len = match args.get(1) {
+                    Some(end) => (parse_addr(end)?.wrapping_sub(start) as usize) + 1,
+                    None => DUMP_BYTES,
+                };
+                self.dbg
+                    .write_ram(&mut self.out, start, len)
+                    .map_err(|err| err.to_string())?;
+                self.next_dump = Some(start.wrapping_add(len as u16));
+                self.repeat = Some(command.to_string());
+            }
+            ">" => {
+                let addr = parse_addr(args.first().ok_or("missing address")?)?;
+                if args.len() < 2 {
+                    return Err("missing bytes".into());
+                }
+                for (i, byte) in args[1..].iter().enumerate() {
+                    let value = parse_byte(byte)?;
+                    self.dbg.bus.write(addr.wrapping_add(i as u16), value);
+                }
+            }
+            "d" | "disass" => {
+                let pc = self.dbg.cpu.program_counter;
+                let start = match args.first() {
+                    Some(addr) => parse_addr(addr)?,
+                    None => self
+                        .next_disasm
+                        .unwrap_or_else(|| self.disasm_start_before(pc)),
+                };
+                let end = args.get(1).map(|end| parse_addr(end)).transpose()?;
+                self.disassemble(start, end)?;
+                self.repeat = Some(command.to_string());
+            }
+            "l" | "load" => self.load(&args)?,
+            "bt" | "backtrace" => {
+                let pc = self.dbg.cpu.program_counter;
+                let backtrace = self.dbg.call_stack.backtrace(pc, self.debug_info.as_ref());
+                write!(self.out, "{}", backtrace).map_err(|err| err.to_string())?;
+            }
+            "sym" | "symbols" => {
+                let path = args.first().ok_or("missing file name")?.trim_matches('"');
+                let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+                let info = DebugInfo::parse(&text).map_err(|err| format!("{}: {}", path, err))?;
+                self.debug_info = Some(info);
+                outln!(self.out, "loaded symbols from {}", path);
+            }
+            "trace" if args.first() == Some(&"file") => {
+                let path = args.get(1).ok_or("missing file name")?.trim_matches('"');
+                let format = match args.get(2) {
+                    None | Some(&"nestest") => TraceFormat::Nestest,
+                    Some(&"compact") => TraceFormat::Compact,
+                    Some(other) => {
+                        return Err(format!("expected nestest or compact, got '{}'", other));
+                    }
+                };
+                let output = TraceWriter::create(path, format)
+                    .map_err(|err| format!("{}: {}", path, err))?;
+                self.close_trace_file()?;
+                self.dbg.set_trace_output(Some(output));
+                outln!(self.out, "tracing to {}", path);
+            }
+            "trace" => {
+                let on = match args.first() {
+                    Some(&"on") => true,
+                    Some(&"off") => false,
+                    None => !self.dbg.is_tracing(),
+                    Some(other) => return Err(format!("expected on or off, got '{}'", other)),
+                };
+                self.dbg.set_tracing(on);
+                self.dbg.take_trace();
+                if !on {
+                    self.close_trace_file()?;
+                }
+                outln!(self.out, "trace {}", if on { "on" } else { "off" });
+            }
+            "history" => {
+                for (i, command) in self.history.iter().enumerate() {
+                    outln!(self.out, "{:4}  {}", i + 1, command);
+                }
+            }
+            "help" | "?" => outln!(self.out, "{}", HELP),
+            "q" | "quit" | "x" => return Ok(Flow::Quit),
+            other => return Err(format!("unknown command '{}' (try 'help')", other)),
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    /// Runs an execution command, then prints the trace, why it stopped
+    /// and where the CPU is now
+    fn run_command(
+        &mut self,
+        command: impl FnOnce(&mut Debugger<'a, SimpleBus>) -> Result<DebugState, CpuError>,
+    ) -> Result<(), String> {
+        let result = command(&mut self.dbg);
+
+        for state in self.dbg.take_trace() {
+            outln!(
+                self.out,
+                "{:<28} A:{:02X} X:{:02X} Y:{:02X} SP:{:02X} P:{:02X}",
+                state.disasm,
+                state.a,
+                state.x,
+                state.y,
+                state.sp,
+                state.status
+            );
+        }
+        for mismatch in self.dbg.call_stack.take_mismatches() {
+            outln!(self.out, "-- call stack: {}", mismatch);
+        }
+        result.map_err(|err| err.to_string())?;
+
+        if let Some(reason) = &self.dbg.last_break {
+            outln!(self.out, "-- stopped: {}", reason);
+        }
+        self.show_position()?;
+        Ok(())
+    }
+
+    /// Stops streaming the trace to a file, reporting write errors
+    fn close_trace_file(&mut self) -> Result<(), String> {
+        let Some(output) = self.dbg.set_trace_output(None) else {
+            return Ok(());
+        };
+        let lines = output.lines();
+        output
+            .finish()
+            .map_err(|err| format!("trace file: {}", err))?;
+        outln!(self.out, "trace file closed ({} lines)", lines);
+        Ok(())
+    }
+
+    /// Prints the next instruction and the registers
+    fn show_position(&mut self) -> Result<(), String> {
+        let line = disassemble(&*self.dbg.bus, self.dbg.cpu.program_counter);
+        outln!(self.out, ".C:{}", line.pretty());
+        self.dbg
+            .write_registers(&mut self.out)
+            .map_err(|err| err.to_string())?;
+        self.next_disasm = None;
+        Ok(())
+    }
+
+    fn add_breakpoint(&mut self, args: &[&str], condition: Option<&str>) -> Result<(), String> {
+        let kind = match args.first() {
+            None if condition.is_none() => {
+                for bp in self.dbg.breakpoints() {
+                    outln!(self.out, "{}", bp);
+                }
+                return Ok(());
+            }
+            None => {
+                BreakpointKind::Execute(self.dbg.cpu.program_counter..=self.dbg.cpu.program_counter)
+            }
+            Some(&"brk") => BreakpointKind::Brk,
+            Some(&"irq") => BreakpointKind::Interrupt(nes6502::cpu::Interrupt::Irq),
+            Some(&"nmi") => BreakpointKind::Interrupt(nes6502::cpu::Interrupt::Nmi),
+            Some(range) => BreakpointKind::Execute(parse_range(range)?),
+        };
+        self.finish_breakpoint(kind, condition)
+    }
+
+    fn add_watchpoint(&mut self, args: &[&str], condition: Option<&str>) -> Result<(), String> {
+        let (kind, range) = match args {
+            [range] => (WatchKind::Access, range),
+            [kind, range] => {
+                let kind = match *kind {
+                    "r" => WatchKind::Read,
+                    "w" => WatchKind::Write,
+                    "rw" => WatchKind::Access,
+                    other => return Err(format!("expected r, w or rw, got '{}'", other)),
+                };
+                (kind, range)
+            }
+            _ => return Err("usage: watch [r|w|rw] addr[-addr] [if cond]".into()),
+        };
+        let kind = BreakpointKind::Watch(kind, parse_range(range)?);
+        self.finish_breakpoint(kind, condition)
+    }
+
+    /// Registers a breakpoint with its optional condition and prints it
+    fn finish_breakpoint(
+        &mut self,
+        kind: BreakpointKind,
+        condition: Option<&str>,
+    ) -> Result<(), String> {
+        let condition = condition
+            .map(Condition::parse)
+            .transpose()
+            .map_err(|err| err.to_string())?;
+
+        let id = self.dbg.add_breakpoint_kind(kind);
+        let bp = self
+            .dbg
+            .breakpoint_mut(id)
+            .expect("breakpoint was just added");
+        bp.condition = condition;
+        outln!(self.out, "{}", bp);
+        Ok(())
+    }
+
+    /// Applies `reg=value` to the CPU
+    fn set_register(&mut self, assignment: &str) -> Result<(), String> {
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("expected reg=value, got '{}'", assignment))?;
+        let cpu = &mut *self.dbg.cpu;
+        match name.to_ascii_uppercase().as_str() {
+            "A" => cpu.register_a = parse_byte(value)?,
+            "X" => cpu.register_x = parse_byte(value)?,
+            "Y" => cpu.register_y = parse_byte(value)?,
+            "SP" => cpu.stack_pointer = parse_byte(value)?,
+            "P" => cpu.status = parse_byte(value)?,
+            "PC" => cpu.program_counter = parse_addr(value)?,
+            other => return Err(format!("unknown register '{}'", other)),
+        }
+        Ok(())
+    }
+
+    /// Finds an address a few bytes before `pc` from which decoding lands
+    /// exactly on `pc` after at most `DISASM_CONTEXT` instructions, so the
+    /// listing shows some context above it. Falls back to `pc`.
+    fn disasm_start_before(&self, pc: u16) -> u16 {
+        for back in (1..=DISASM_LOOKBEHIND).rev() {
+            let start = pc.wrapping_sub(back);
+            let mut addr = start;
+            let mut count = 0;
+            while addr.wrapping_sub(start) < back {
+                addr = disassemble(&*self.dbg.bus, addr).next_pc;
+                count += 1;
+            }
+            if addr == pc && count <= DISASM_CONTEXT {
+                return start;
+            }
+        }
+        pc
+    }
+
+    /// Lists instructions from `start` up to `end`, or `DISASM_LINES` of them
+    fn disassemble(&mut self, start: u16, end: Option<u16>) -> Result<(), String> {
+        let pc = self.dbg.cpu.program_counter;
+        let mut addr = start;
+        let mut lines = 0;
+        loop {
+            let done = match end {
+                Some(end) => addr.wrapping_sub(start) > end.wrapping_sub(start),
+                None => lines == DISASM_LINES,
+            };
+            if done {
+                break;
+            }
+
+            let line = disassemble(&*self.dbg.bus, addr);
+            let marker = if addr == pc { '>' } else { ' ' };
+            outln!(self.out, "{}C:{}", marker, line.pretty());
+
+            // Stop instead of wrapping around to `start` again
+            if line.next_pc.wrapping_sub(start) < addr.wrapping_sub(start) {
+                break;
+            }
+            addr = line.next_pc;
+            lines += 1;
+        }
+        self.next_disasm = Some(addr);
+        Ok(())
+    }
+
+    /// `load <file>` maps the file as a ROM at $8000 (mirrored up to $FFFF)
+    /// and resets; `load <file> <addr>` writes it into memory at `addr`.
+    fn load(&mut self, args: &[&str]) -> Result<(), String> {
+        let path = args.first().ok_or("missing file name")?;
+        let path = path.trim_matches('"');
+        let data = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        match args.get(1) {
+            Some(addr) => {
+                let addr = parse_addr(addr)?;
+                for (i, byte) in data.iter().enumerate() {
+                    self.dbg.bus.write(addr.wrapping_add(i as u16), *byte);
+                }
+                outln!(
+                    self.out,
+                    "loaded {} bytes at ${:04X}-${:04X}",
+                    data.len(),
+                    addr,
+                    addr.wrapping_add(data.len().saturating_sub(1) as u16)
+                );
+            }
+            None => {
+                *self.dbg.bus = crate::rom_bus(&data)?;
+                self.dbg.history.clear();
+                self.dbg.call_stack.clear();
+                self.dbg.cpu.reset(&mut *self.dbg.bus);
+                match InesImage::parse(&data) {
+                    Ok(image) => outln!(
+                        self.out,
+                        "loaded iNES image, mapper {}, {}K PRG ROM at $8000",
+                        image.mapper,
+                        image.prg_rom.len() / 1024
+                    ),
+                    Err(_) => outln!(self.out, "loaded {} bytes ROM at $8000", data.len()),
+                }
+                self.show_position()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses a hex address, with or without a `$` or `0x` prefix
+fn parse_addr(text: &str) -> Result<u16, String> {
+    let digits = strip_hex_prefix(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("bad address '{}'", text))
+}
+
+/// Parses a hex byte, with or without a `$` or `0x` prefix
+fn parse_byte(text: &str) -> Result<u8, String> {
+    let digits = strip_hex_prefix(text);
+    u8::from_str_radix(digits, 16).map_err(|_| format!("bad byte '{}'", text))
+}
+
+fn strip_hex_prefix(text: &str) -> &str {
+    text.strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text)
+}
+
+/// Parses `addr` or `start-end`
+fn parse_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    match text.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse_addr(start)?, parse_addr(end)?);
+            if end < start {
+                return Err(format!("empty range '{}'", text));
+            }
+            Ok(start..=end)
+        }
+        None => {
+            let addr = parse_addr(text)?;
+            Ok(addr..=addr)
+        }
+    }
+}
+
+/// Parses a decimal count
+fn parse_count(text: &str) -> Result<usize, String> {
+    text.parse().map_err(|_| format!("bad number '{}'", text))
+}
+
+/// First argument as a count, defaulting to 1
+fn optional_count(args: &[&str]) -> Result<usize, String> {
+    args.first().map_or(Ok(1), |count| parse_count(count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nes6502::bus::ram::Ram;
+    use nes6502::fixture::{COUNT_TO_5, machine};
+
+    /// Runs a monitor session over 64K of RAM holding the test machine for
+    /// `program` and returns everything it printed
+    fn session(program: &[u8], input: &str) -> String {
+        let (mut cpu, image) = machine(program);
+        let mut ram = Ram::new(0x10000);
+        ram.load(0x0000, image.memory());
+        let mut bus = SimpleBus::empty();
+        bus.attach(0x0000..=0xFFFF, ram);
+
+        let mut monitor = Monitor::new(Debugger::new(&mut cpu, &mut bus), Vec::new());
+        monitor.run(input.as_bytes()).unwrap();
+        String::from_utf8(monitor.out).unwrap()
+    }
+
+    /// Lines of `output` that show the next instruction
+    fn positions(output: &str) -> Vec<&str> {
+        output
+            .lines()
+            .filter_map(|line| line.split(".C:").nth(1))
+            .map(|rest| &rest[..4])
+            .collect()
+    }
+
+    #[test]
+    fn shows_the_position_and_prompt() {
+        let output = session(&COUNT_TO_5, "");
+        assert_eq!(
+            output,
+            ".C:0600  A2 00      LDX #$00\n\
+             PC=0600  A=00  X=00  Y=00  SP=FD  STATUS=00100100\n\
+             (C:$0600) \n"
+        );
+    }
+
+    #[test]
+    fn enter_repeats_a_step() {
+        let output = session(&COUNT_TO_5, "s\n\n\n");
+        assert_eq!(positions(&output), ["0600", "0602", "0603", "0605"]);
+
+        let output = session(&COUNT_TO_5, "n 2\n\n");
+        assert_eq!(positions(&output), ["0600", "0603", "0602"]);
+    }
+
+    #[test]
+    fn enter_continues_a_memory_dump() {
+        let output = session(&COUNT_TO_5, "m 0600 0603\n\n");
+        assert!(output.contains("\n0600: A2 00 E8 E0 \n"));
+        assert!(output.contains("\n0604: 05 D0 FB 4C 07 06 00"));
+    }
+
+    #[test]
+    fn enter_does_not_repeat_other_commands() {
+        // A second breakpoint would be #2
+        let output = session(&COUNT_TO_5, "b 0605\n\nb\n");
+        assert!(output.contains("#1 exec $0605 hits=0"));
+        assert!(!output.contains("#2"));
+
+        let output = session(&COUNT_TO_5, "s\nreset\n\n");
+        assert_eq!(positions(&output), ["0600", "0602", "0600"]);
+
+        let output = session(&COUNT_TO_5, "b 0605\ndel 1\n\n");
+        assert!(!output.contains("error"));
+    }
+
+    #[test]
+    fn stepping_stops_at_an_execution_breakpoint() {
+        let output = session(&COUNT_TO_5, "b 0605\ns 10\n");
+        assert!(output.contains("-- stopped: breakpoint #1\n"));
+        assert_eq!(positions(&output), ["0600", "0605"]);
+
+        let output = session(&COUNT_TO_5, "b 0603\nn 10\n");
+        assert!(output.contains("-- stopped: breakpoint #1\n"));
+        assert_eq!(positions(&output), ["0600", "0603"]);
+    }
+
+    #[test]
+    fn continue_stops_at_a_conditional_breakpoint() {
+        let output = session(&COUNT_TO_5, "b 0602 if X == 3\ng\nr\n");
+        assert!(output.contains("#1 exec $0602 if X == 3 hits=0\n"));
+        assert!(output.contains("-- stopped: breakpoint #1\n"));
+        assert!(output.contains("PC=0602  A=00  X=03"));
+    }
+
+    #[test]
+    fn history_recalls_commands() {
+        let output = session(&COUNT_TO_5, "s\nr x=42\nhistory\n!2\n!9\n");
+        assert!(output.contains("   1  s\n   2  r x=42\n"));
+        assert!(output.contains("(C:$0602) r x=42\n"));
+        assert!(output.contains("error: no history entry 9\n"));
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let output = session(&COUNT_TO_5, "bogus\ns if A\nm zz\n");
+        assert!(output.contains("error: unknown command 'bogus' (try 'help')\n"));
+        assert!(output.contains("error: 's' does not take a condition\n"));
+        assert!(output.contains("error: bad address 'zz'\n"));
+    }
+
+    #[test]
+    fn load_describes_the_image() {
+        let dir = std::env::temp_dir();
+        let raw = dir.join(format!("monitor-test-{}.bin", std::process::id()));
+        let ines = dir.join(format!("monitor-test-{}.nes", std::process::id()));
+
+        let mut prg = vec![0xEA; 0x4000];
+        prg[0x3FFC..0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+        fs::write(&raw, &prg).unwrap();
+        let mut image = b"NES\x1a\x01\x00\x00\x00".to_vec();
+        image.resize(16, 0);
+        image.extend_from_slice(&prg);
+        fs::write(&ines, &image).unwrap();
+
+        let input = format!("l {}\nl {}\n", raw.display(), ines.display());
+        let output = session(&COUNT_TO_5, &input);
+        fs::remove_file(&raw).unwrap();
+        fs::remove_file(&ines).unwrap();
+
+        assert!(output.contains("loaded 16384 bytes ROM at $8000\n"));
+        assert!(output.contains("loaded iNES image, mapper 0, 16K PRG ROM at $8000\n"));
+        assert_eq!(positions(&output), ["0600", "C000", "C000"]);
+    }
+}
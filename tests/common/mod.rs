@@ -0,0 +1,38 @@
+//! Helpers shared by the conformance tests.
+
+// Each test crate uses only some of them
+#![allow(dead_code)]
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Set to 1 to fail, rather than skip, suites whose files are missing
+const REQUIRE_VAR: &str = "REQUIRE_FIXTURES";
+
+/// Where test ROMs and reference logs are placed (see `fixtures/README.md`).
+pub fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Reads a fixture, or returns `None` (after saying so) if it isn't there,
+/// so suites whose files haven't been downloaded are skipped, not failed.
+pub fn fixture(name: &str) -> Option<Vec<u8>> {
+    let path = fixtures_dir().join(name);
+    match fs::read(&path) {
+        Ok(data) => Some(data),
+        Err(err) => {
+            missing(path.display(), err);
+            None
+        }
+    }
+}
+
+/// Reports a missing fixture before the suite skips itself. Panics instead
+/// when `REQUIRE_FIXTURES=1`, for runs where the files must be present.
+pub fn missing(what: impl fmt::Display, err: impl fmt::Display) {
+    if std::env::var(REQUIRE_VAR).is_ok_and(|value| value == "1") {
+        panic!("{}: {} ({}=1 is set)", what, err, REQUIRE_VAR);
+    }
+    eprintln!("skipping: {}: {}", what, err);
+}
@@ -0,0 +1,201 @@
+//! Debug Adapter Protocol session over in-memory streams.
+//!
+//! Scripts the requests an editor sends when starting a debug session with a
+//! source breakpoint, runs `dap::serve` on them and checks the responses and
+//! events it writes back.
+
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use serde_json::{Value, json};
+
+use nes6502::bus::flat::FlatBus;
+use nes6502::cpu::CPU;
+use nes6502::debugger::{Debugger, dap};
+
+/// A 32K ROM at $8000 whose reset routine counts X up forever:
+///
+/// ```text
+///  4  reset:
+///  5      ldx #0          ; $8000
+///  6  loop: inx           ; $8002
+///  7      ; keep counting
+///  8      jmp loop        ; $8003
+/// ```
+fn rom() -> Vec<u8> {
+    let mut rom = vec![0xEA; 0x8000];
+    rom[..6].copy_from_slice(&[0xA2, 0x00, 0xE8, 0x4C, 0x02, 0x80]);
+    rom[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+    rom
+}
+
+/// ld65 debug info for `rom`
+const DBG: &str = "\
+version\tmajor=2,minor=0
+file\tid=0,name=\"main.s\",size=120,mtime=0x5F000000,mod=0
+seg\tid=0,name=\"CODE\",start=0x008000,size=0x0006,addrsize=absolute,type=ro
+span\tid=0,seg=0,start=0,size=2
+span\tid=1,seg=0,start=2,size=1
+span\tid=2,seg=0,start=3,size=3
+line\tid=0,file=0,line=5,span=0
+line\tid=1,file=0,line=6,span=1
+line\tid=2,file=0,line=8,span=2
+sym\tid=0,name=\"reset\",addrsize=absolute,scope=0,def=0,val=0x8000,seg=0,type=lab
+sym\tid=1,name=\"loop\",addrsize=absolute,scope=0,def=1,val=0x8002,seg=0,type=lab
+";
+
+/// Frames requests the way an editor sends them, numbering them from 1
+fn requests(requests: &[(&str, Value)]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for (seq, (command, arguments)) in requests.iter().enumerate() {
+        let body = json!({
+            "seq": seq + 1,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        input.extend_from_slice(
+            format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes(),
+        );
+    }
+    input
+}
+
+/// Splits the server's output into messages, checking each frame's length
+fn messages(mut output: &str) -> Vec<Value> {
+    let mut messages = Vec::new();
+    while !output.is_empty() {
+        let (header, rest) = output.split_once("\r\n\r\n").expect("header block");
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .expect("Content-Length header")
+            .parse()
+            .unwrap();
+        messages.push(serde_json::from_str(&rest[..length]).unwrap());
+        output = &rest[length..];
+    }
+    messages
+}
+
+/// A directory holding `game.bin` and `game.dbg`, removed on drop
+struct Project(PathBuf);
+
+impl Project {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("nes6502-dap-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("game.bin"), rom()).unwrap();
+        fs::write(dir.join("game.dbg"), DBG).unwrap();
+        Self(dir)
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn dap_session_stops_at_a_source_breakpoint() {
+    let project = Project::new();
+    let input = requests(&[
+        ("initialize", json!({ "adapterID": "nes6502" })),
+        ("launch", json!({ "program": project.path("game.bin") })),
+        (
+            "setBreakpoints",
+            json!({
+                "source": { "path": project.path("main.s") },
+                "breakpoints": [{ "line": 7 }, { "line": 2 }, { "line": 40 }],
+            }),
+        ),
+        ("configurationDone", json!({})),
+        ("stackTrace", json!({ "threadId": 1 })),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0x8000", "count": 3 }),
+        ),
+        ("disconnect", json!({})),
+    ]);
+
+    let mut cpu = CPU::new();
+    let mut bus = FlatBus::new();
+    let mut dbg = Debugger::new(&mut cpu, &mut bus);
+    let host_flag = Arc::new(AtomicBool::new(false));
+    dbg.set_interrupt_flag(Some(Arc::clone(&host_flag)));
+    let loader: dap::Loader<FlatBus> = Box::new(|data| Ok(FlatBus::with_image(data, 0x8000)));
+    let mut output = Vec::new();
+    dap::serve(&mut dbg, loader, Cursor::new(input), &mut output).unwrap();
+
+    let messages = messages(std::str::from_utf8(&output).unwrap());
+    let kinds: Vec<String> = messages
+        .iter()
+        .map(|m| match m["type"].as_str().unwrap() {
+            "response" => format!("{} response", m["command"].as_str().unwrap()),
+            _ => format!("{} event", m["event"].as_str().unwrap()),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "initialize response",
+            "launch response",
+            "initialized event",
+            "setBreakpoints response",
+            "configurationDone response",
+            "stopped event",
+            "stackTrace response",
+            "readMemory response",
+            "disconnect response",
+        ]
+    );
+    for (seq, message) in messages.iter().enumerate() {
+        assert_eq!(message["seq"], json!(seq + 1));
+        if message["type"] == "response" {
+            assert_eq!(message["success"], json!(true), "{}", message);
+        }
+    }
+
+    assert_eq!(messages[0]["body"]["supportsStepBack"], json!(true));
+
+    // Line 7 has no code and moves to line 8; line 2 moves down to line 5
+    let breakpoints = &messages[3]["body"]["breakpoints"];
+    assert_eq!(breakpoints[0]["verified"], json!(true));
+    assert_eq!(breakpoints[0]["line"], json!(8));
+    assert_eq!(breakpoints[1]["line"], json!(5));
+    assert_eq!(breakpoints[2]["verified"], json!(false));
+    assert_eq!(breakpoints[2]["message"], json!("no code at this line"));
+
+    // Running from reset stops before the JMP on line 8
+    let stopped = &messages[5]["body"];
+    assert_eq!(stopped["reason"], json!("breakpoint"));
+    assert_eq!(stopped["hitBreakpointIds"], json!([breakpoints[0]["id"]]));
+
+    let frame = &messages[6]["body"]["stackFrames"][0];
+    assert_eq!(frame["name"], json!("loop+1"));
+    assert_eq!(frame["line"], json!(8));
+    assert_eq!(frame["source"]["path"], json!(project.path("main.s")));
+    assert_eq!(frame["instructionPointerReference"], json!("0x8003"));
+
+    // A2 00 E8
+    assert_eq!(messages[7]["body"]["data"], json!("ogDo"));
+    assert_eq!(messages[7]["body"]["unreadableBytes"], json!(0));
+
+    let restored = dbg.set_interrupt_flag(None);
+    assert!(
+        restored.is_some_and(|flag| Arc::ptr_eq(&flag, &host_flag)),
+        "the host's interrupt flag was not restored"
+    );
+    drop(dbg);
+    assert_eq!(cpu.program_counter, 0x8003);
+    assert_eq!(cpu.register_x, 1);
+}
@@ -0,0 +1,394 @@
+//! Differential fuzzing of `CPU::run_once` against the model in `reference`.
+//!
+//! Each case is a random CPU state, an instruction at PC and a memory
+//! background derived from a seed. Both sides take one step from it and
+//! must agree on registers, pending interrupts, the writes they made (in
+//! order) and the cycle count. A failing case is shrunk to a minimal one
+//! and printed as a `Case` literal to paste into `REGRESSIONS`.
+//!
+//! `FUZZ_CASES=n` sets how many cases run (default 20000), `FUZZ_SEED=n`
+//! picks another random sequence.
+
+mod reference;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use nes6502::bus::Bus;
+use nes6502::cpu::{CPU, CpuError, CpuVariant};
+use reference::{Memory, Model, Outcome};
+
+const DEFAULT_CASES: u64 = 20_000;
+const DEFAULT_SEED: u64 = 0x6502_6502;
+
+/// Values that tend to find edge cases, picked more often than chance would
+const INTERESTING: [u8; 8] = [0x00, 0x01, 0x0f, 0x7f, 0x80, 0x99, 0xfe, 0xff];
+
+/// Cases that must keep passing: minimized failures, and corner cases
+/// worth checking on every run whatever the random sequence hits.
+#[rustfmt::skip]
+const REGRESSIONS: &[Case] = &[
+    // Decimal ADC: N from the uncorrected high nibble, Z from the binary sum
+    Case { pc: 0x0200, a: 0x99, x: 0x00, y: 0x00, sp: 0xfd, p: 0x08, nmi: false, irq: false, ricoh: false, code: [0x69, 0x01, 0x00], memory: 0x0 },
+    // The 2A03 ignores D
+    Case { pc: 0x0200, a: 0x09, x: 0x00, y: 0x00, sp: 0xfd, p: 0x08, nmi: false, irq: false, ricoh: true, code: [0x69, 0x01, 0x00], memory: 0x0 },
+    // ARR with its decimal fixup
+    Case { pc: 0x0200, a: 0xff, x: 0x00, y: 0x00, sp: 0xfd, p: 0x09, nmi: false, irq: false, ricoh: false, code: [0x6b, 0xff, 0x00], memory: 0x0 },
+    // JMP ($02FF) takes its high byte from $0200
+    Case { pc: 0x0200, a: 0x00, x: 0x00, y: 0x00, sp: 0xfd, p: 0x20, nmi: false, irq: false, ricoh: false, code: [0x6c, 0xff, 0x02], memory: 0x1 },
+    // Operand bytes wrap from $FFFF to $0000
+    Case { pc: 0xffff, a: 0x00, x: 0x00, y: 0x00, sp: 0xfd, p: 0x20, nmi: false, irq: false, ricoh: false, code: [0xad, 0x34, 0x12], memory: 0x3 },
+    // ($FF),Y reads the pointer's high byte from $00
+    Case { pc: 0x0200, a: 0x00, x: 0x00, y: 0x10, sp: 0xfd, p: 0x20, nmi: false, irq: false, ricoh: false, code: [0xb1, 0xff, 0x00], memory: 0x5 },
+    // SHX crossing a page stores into the page given by the stored value
+    Case { pc: 0x0200, a: 0x00, x: 0x0f, y: 0x01, sp: 0xfd, p: 0x20, nmi: false, irq: false, ricoh: false, code: [0x9e, 0xff, 0x12], memory: 0x0 },
+    // NMI wins over a pending, unmasked IRQ
+    Case { pc: 0x0200, a: 0x00, x: 0x00, y: 0x00, sp: 0xfd, p: 0x20, nmi: true, irq: true, ricoh: false, code: [0xea, 0x00, 0x00], memory: 0x7 },
+    // BRK with the stack pointer wrapping from $00 to $FF
+    Case { pc: 0x0200, a: 0x00, x: 0x00, y: 0x00, sp: 0x01, p: 0x20, nmi: false, irq: false, ricoh: false, code: [0x00, 0x00, 0x00], memory: 0x9 },
+];
+
+/// Everything that determines one step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Case {
+    pc: u16,
+    a: u8,
+    x: u8,
+    y: u8,
+    sp: u8,
+    p: u8,
+    nmi: bool,
+    irq: bool,
+    ricoh: bool,
+    /// Instruction bytes placed at PC
+    code: [u8; 3],
+    /// Seed of the memory background, 0 for all-zero memory
+    memory: u64,
+}
+
+impl Case {
+    fn generate(rng: &mut Rng) -> Self {
+        let pc = match rng.below(4) {
+            // Operands wrapping past the end of a page or of memory
+            0 => ((rng.next() as u16) << 8) | (0xfd + rng.below(3) as u16),
+            _ => rng.next() as u16,
+        };
+        let memory = match rng.below(8) {
+            0 => 0,
+            _ => rng.next() | 1,
+        };
+        Self {
+            pc,
+            a: rng.byte(),
+            x: rng.byte(),
+            y: rng.byte(),
+            sp: rng.byte(),
+            p: rng.next() as u8,
+            nmi: rng.below(16) == 0,
+            irq: rng.below(8) == 0,
+            ricoh: rng.below(8) == 0,
+            code: [rng.next() as u8, rng.byte(), rng.byte()],
+            memory,
+        }
+    }
+
+    /// The initial memory, shared by both sides
+    fn memory(&self) -> Memory {
+        let mut memory = Memory::new(self.memory);
+        for (i, byte) in self.code.iter().enumerate() {
+            memory.set(self.pc.wrapping_add(i as u16), *byte);
+        }
+        memory
+    }
+
+    /// Simpler variants to try while shrinking: each field moved towards 0.
+    /// Every candidate is strictly smaller somewhere, so shrinking ends.
+    fn shrink(&self) -> Vec<Case> {
+        let mut candidates = Vec::new();
+        let mut push = |case: Case| {
+            if case != *self {
+                candidates.push(case);
+            }
+        };
+        push(Case { memory: 0, ..*self });
+        push(Case {
+            nmi: false,
+            ..*self
+        });
+        push(Case {
+            irq: false,
+            ..*self
+        });
+        push(Case {
+            ricoh: false,
+            ..*self
+        });
+        for pc in [0, self.pc / 2, self.pc & 0xff00] {
+            if pc < self.pc {
+                push(Case { pc, ..*self });
+            }
+        }
+        for value in smaller(self.a) {
+            push(Case { a: value, ..*self });
+        }
+        for value in smaller(self.x) {
+            push(Case { x: value, ..*self });
+        }
+        for value in smaller(self.y) {
+            push(Case { y: value, ..*self });
+        }
+        for value in smaller(self.sp) {
+            push(Case { sp: value, ..*self });
+        }
+        for bit in 0..8 {
+            push(Case {
+                p: self.p & !(1 << bit),
+                ..*self
+            });
+        }
+        // The opcode stays, only its operand shrinks
+        for i in 1..3 {
+            for value in smaller(self.code[i]) {
+                let mut code = self.code;
+                code[i] = value;
+                push(Case { code, ..*self });
+            }
+        }
+        candidates
+    }
+
+    /// The case as Rust source for `REGRESSIONS`
+    fn literal(&self) -> String {
+        format!(
+            "Case {{ pc: 0x{:04x}, a: 0x{:02x}, x: 0x{:02x}, y: 0x{:02x}, sp: 0x{:02x}, \
+             p: 0x{:02x}, nmi: {}, irq: {}, ricoh: {}, \
+             code: [0x{:02x}, 0x{:02x}, 0x{:02x}], memory: 0x{:x} }},",
+            self.pc,
+            self.a,
+            self.x,
+            self.y,
+            self.sp,
+            self.p,
+            self.nmi,
+            self.irq,
+            self.ricoh,
+            self.code[0],
+            self.code[1],
+            self.code[2],
+            self.memory
+        )
+    }
+}
+
+/// 0 and half of `value`, if they're smaller
+fn smaller(value: u8) -> Vec<u8> {
+    let mut values = vec![0, value / 2];
+    values.retain(|&smaller| smaller < value);
+    values.dedup();
+    values
+}
+
+/// The CPU side uses the reference memory too, so both start from the same bytes
+impl Bus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        Memory::read(self, addr)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        Memory::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        Memory::write(self, addr, data)
+    }
+}
+
+/// State after one step, in a form both sides can produce
+#[derive(Debug, PartialEq, Eq)]
+struct After {
+    pc: u16,
+    a: u8,
+    x: u8,
+    y: u8,
+    sp: u8,
+    p: u8,
+    nmi: bool,
+    irq: bool,
+    /// None when the CPU jammed
+    cycles: Option<u64>,
+    writes: Vec<(u16, u8)>,
+}
+
+fn run_cpu(case: &Case) -> Result<After, String> {
+    let mut memory = case.memory();
+    let variant = if case.ricoh {
+        CpuVariant::Ricoh2A03
+    } else {
+        CpuVariant::Nmos6502
+    };
+    let mut cpu = CPU::with_variant(variant);
+    cpu.program_counter = case.pc;
+    cpu.register_a = case.a;
+    cpu.register_x = case.x;
+    cpu.register_y = case.y;
+    cpu.stack_pointer = case.sp;
+    cpu.status = case.p;
+    cpu.nmi_pending = case.nmi;
+    cpu.irq_pending = case.irq;
+
+    let cycles = match cpu.run_once(&mut memory) {
+        Ok(outcome) => Some(outcome.cycles()),
+        Err(CpuError::Jammed { .. }) => None,
+        Err(err) => return Err(err.to_string()),
+    };
+    Ok(After {
+        pc: cpu.program_counter,
+        a: cpu.register_a,
+        x: cpu.register_x,
+        y: cpu.register_y,
+        sp: cpu.stack_pointer,
+        p: cpu.status,
+        nmi: cpu.nmi_pending,
+        irq: cpu.irq_pending,
+        cycles,
+        writes: memory.writes,
+    })
+}
+
+fn run_reference(case: &Case) -> After {
+    let mut memory = case.memory();
+    let mut model = Model {
+        pc: case.pc,
+        a: case.a,
+        x: case.x,
+        y: case.y,
+        sp: case.sp,
+        p: case.p,
+        nmi: case.nmi,
+        irq: case.irq,
+        bcd: !case.ricoh,
+    };
+    let cycles = match model.step(&mut memory) {
+        Outcome::Cycles(cycles) => Some(cycles),
+        Outcome::Jam => None,
+    };
+    After {
+        pc: model.pc,
+        a: model.a,
+        x: model.x,
+        y: model.y,
+        sp: model.sp,
+        p: model.p,
+        nmi: model.nmi,
+        irq: model.irq,
+        cycles,
+        writes: memory.writes,
+    }
+}
+
+/// Runs both sides; describes the difference, or the CPU's panic, if any
+fn check(case: &Case) -> Result<(), String> {
+    let actual = panic::catch_unwind(AssertUnwindSafe(|| run_cpu(case)))
+        .map_err(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            format!("CPU panicked: {}", message)
+        })?
+        .map_err(|err| format!("CPU failed: {}", err))?;
+    let expected = run_reference(case);
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected {:02X?}\n  actual   {:02X?}",
+            expected, actual
+        ))
+    }
+}
+
+/// Greedily replaces `case` by simpler variants that still fail
+fn minimize(mut case: Case) -> Case {
+    while let Some(simpler) = case
+        .shrink()
+        .into_iter()
+        .find(|candidate| check(candidate).is_err())
+    {
+        case = simpler;
+    }
+    case
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    let value = std::env::var(name).ok()?;
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+#[test]
+fn cpu_matches_reference_model() {
+    let cases = env_u64("FUZZ_CASES").unwrap_or(DEFAULT_CASES);
+    let seed = env_u64("FUZZ_SEED").unwrap_or(DEFAULT_SEED);
+    let mut rng = Rng::new(seed);
+
+    for i in 0..cases {
+        let case = Case::generate(&mut rng);
+        if check(&case).is_err() {
+            let minimal = minimize(case);
+            let diff = check(&minimal).unwrap_err();
+            panic!(
+                "case {} of FUZZ_SEED=0x{:x} differs from the reference model\n  \
+                 {}\nminimized (add it to REGRESSIONS):\n  {}",
+                i,
+                seed,
+                diff,
+                minimal.literal()
+            );
+        }
+    }
+}
+
+#[test]
+fn regressions() {
+    let failures: Vec<String> = REGRESSIONS
+        .iter()
+        .filter_map(|case| {
+            let diff = check(case).err()?;
+            Some(format!("{}\n  {}", case.literal(), diff))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// SplitMix64, so runs are reproducible without external crates
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// A random byte, one time in four an `INTERESTING` one
+    fn byte(&mut self) -> u8 {
+        if self.below(4) == 0 {
+            INTERESTING[self.below(INTERESTING.len() as u64) as usize]
+        } else {
+            self.next() as u8
+        }
+    }
+}
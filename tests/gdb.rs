@@ -0,0 +1,213 @@
+//! GDB remote stub session over loopback.
+//!
+//! Plays the GDB side of the protocol against `gdb::serve` running a small
+//! program on a flat bus, including acks, resends and no-ack mode.
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::Duration;
+
+use nes6502::bus::flat::FlatBus;
+use nes6502::cpu::CPU;
+use nes6502::debugger::{Debugger, gdb};
+
+/// Where the program is loaded and the reset vector points
+const ORIGIN: u16 = 0x0600;
+
+/// Stores X to $0200 while counting it from 1 to 5, then parks at $060A.
+const PROGRAM: [u8; 13] = [
+    0xA2, 0x00, //       $0600 LDX #$00
+    0xE8, //             $0602 INX
+    0x8E, 0x00, 0x02, // $0603 STX $0200
+    0xE0, 0x05, //       $0606 CPX #$05
+    0xD0, 0xF8, //       $0608 BNE $0602
+    0x4C, 0x0A, 0x06, // $060A JMP $060A
+];
+
+/// Fails the test instead of hanging if the stub stops answering
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait for a stop reply before sending Ctrl-C again
+const RETRY: Duration = Duration::from_millis(20);
+
+/// The GDB end of the connection
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn connect(addr: std::net::SocketAddr) -> Self {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        Self { stream }
+    }
+
+    /// Sends Ctrl-C until the running target stops, as a request that
+    /// arrives before the run has started is discarded. Returns the stop reply.
+    fn interrupt(&mut self) -> String {
+        self.stream.set_read_timeout(Some(RETRY)).unwrap();
+        let mut byte = [0];
+        let mut attempts = 0;
+        loop {
+            self.write(&[0x03]);
+            match self.stream.read(&mut byte) {
+                Ok(1) => break,
+                _ if attempts * RETRY < TIMEOUT => attempts += 1,
+                other => panic!("target didn't stop: {:?}", other),
+            }
+        }
+        self.stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+
+        assert_eq!(byte[0] as char, '$');
+        self.packet_body()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).unwrap();
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    /// Sends a framed packet (no escaping needed for the packets used here)
+    fn send(&mut self, packet: &str) {
+        let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        self.write(format!("${}#{:02x}", packet, checksum).as_bytes());
+    }
+
+    /// Reads one reply packet and checks its checksum
+    fn reply(&mut self) -> String {
+        assert_eq!(self.read_byte() as char, '$');
+        self.packet_body()
+    }
+
+    /// Reads the rest of a packet after its `$` and checks the checksum
+    fn packet_body(&mut self) -> String {
+        let mut body = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => body.push(byte),
+            }
+        }
+        let digits = [self.read_byte(), self.read_byte()];
+        let checksum = u8::from_str_radix(std::str::from_utf8(&digits).unwrap(), 16).unwrap();
+        assert_eq!(
+            checksum,
+            body.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+        );
+        String::from_utf8(body).unwrap()
+    }
+
+    /// A request in ack mode: the stub acks it, then we ack its reply
+    fn request(&mut self, packet: &str) -> String {
+        self.send(packet);
+        assert_eq!(self.read_byte() as char, '+', "ack for {}", packet);
+        let reply = self.reply();
+        self.write(b"+");
+        reply
+    }
+}
+
+#[test]
+fn gdb_session_over_loopback() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let host_flag = Arc::new(AtomicBool::new(false));
+    let server = thread::spawn({
+        let host_flag = Arc::clone(&host_flag);
+        move || {
+            let mut bus = FlatBus::new();
+            bus.load(ORIGIN, &PROGRAM);
+            bus.load(0xFFFC, &ORIGIN.to_le_bytes());
+            let mut cpu = CPU::new();
+            cpu.reset(&mut bus);
+
+            let mut dbg = Debugger::new(&mut cpu, &mut bus);
+            // Only Ctrl-C gets the last `c` out of the final loop
+            dbg.limits.stop_on_self_loop = false;
+            dbg.set_interrupt_flag(Some(Arc::clone(&host_flag)));
+
+            gdb::serve(&mut dbg, &listener).unwrap();
+
+            let restored = dbg
+                .set_interrupt_flag(None)
+                .is_some_and(|flag| Arc::ptr_eq(&flag, &host_flag));
+            (restored, dbg.breakpoints().len())
+        }
+    });
+
+    let mut gdb = Client::connect(addr);
+
+    let features = gdb.request("qSupported:multiprocess+;swbreak+");
+    assert!(features.starts_with("PacketSize=1000;QStartNoAckMode+;"));
+    assert_eq!(gdb.request("?"), "S05");
+
+    // A corrupted packet is nacked; a nack makes the stub resend its reply
+    gdb.write(b"$g#00");
+    assert_eq!(gdb.read_byte() as char, '-');
+    gdb.write(b"-");
+    assert_eq!(gdb.reply(), "S05");
+    gdb.write(b"+");
+
+    // Registers: A, X, Y, SP, P, then PC little-endian
+    assert_eq!(gdb.request("g"), "000000fd240006");
+    assert_eq!(gdb.request("G110000fd240006"), "OK");
+    assert_eq!(gdb.request("g"), "110000fd240006");
+    assert_eq!(gdb.request("G1100"), "E01");
+    assert_eq!(gdb.request("p5"), "0006");
+
+    // Memory
+    assert_eq!(gdb.request("m600,4"), "a200e88e");
+    assert_eq!(gdb.request("M300,2:beef"), "OK");
+    assert_eq!(gdb.request("m300,2"), "beef");
+    assert_eq!(gdb.request("M300,2:be"), "E01");
+
+    // Breakpoints stop before their instruction
+    assert_eq!(gdb.request("Z0,606,1"), "OK");
+    assert_eq!(gdb.request("c"), "S05");
+    assert_eq!(gdb.request("p5"), "0606");
+    assert_eq!(gdb.request("p1"), "01");
+    assert_eq!(gdb.request("z0,606,1"), "OK");
+
+    // A single step
+    assert_eq!(gdb.request("s"), "S05");
+    assert_eq!(gdb.request("p5"), "0806");
+
+    // Watchpoints report the address in a T packet
+    assert_eq!(gdb.request("Z2,200,1"), "OK");
+    assert_eq!(gdb.request("c"), "T05watch:0200;");
+    assert_eq!(gdb.request("p5"), "0606");
+    assert_eq!(gdb.request("m200,1"), "02");
+
+    // From here on nothing is acked
+    assert_eq!(gdb.request("QStartNoAckMode"), "OK");
+    gdb.send("p1");
+    assert_eq!(gdb.reply(), "02");
+
+    // Without the watchpoint only Ctrl-C stops the final loop
+    gdb.send("z2,200,1");
+    assert_eq!(gdb.reply(), "OK");
+    gdb.send("c");
+    assert_eq!(gdb.interrupt(), "S02");
+    gdb.send("p5");
+    assert_eq!(gdb.reply(), "0a06");
+
+    // Left in place when GDB detaches; the stub removes it
+    gdb.send("Z0,602,1");
+    assert_eq!(gdb.reply(), "OK");
+    gdb.send("D");
+    assert_eq!(gdb.reply(), "OK");
+
+    let (restored, breakpoints) = server.join().unwrap();
+    assert!(restored, "the host's interrupt flag was not restored");
+    assert_eq!(breakpoints, 0, "GDB's breakpoints outlived the session");
+}
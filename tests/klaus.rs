@@ -0,0 +1,176 @@
+//! Klaus Dormann's 6502 functional and decimal tests.
+//!
+//! Each image is loaded into 64K of flat RAM and run from its entry point
+//! until the PC traps in a self-loop (`JMP *` or a branch to itself), or
+//! reaches the 65C02 STP opcode the decimal test ends on. The tests stop
+//! like that both on success and on the first failure, so the stop address
+//! (or the error byte) tells which one it was.
+//! Needs `6502_functional_test.bin` / `6502_decimal_test.bin` in
+//! `tests/fixtures`.
+
+mod common;
+
+use nes6502::bus::Bus;
+use nes6502::bus::flat::FlatBus;
+use nes6502::cpu::CPU;
+use nes6502::disassembler::disassemble;
+
+/// Far more instructions than either test needs (~30M for the functional test)
+const MAX_INSTRUCTIONS: u64 = 200_000_000;
+
+/// 65C02 STP. `end_of_test` is `db $db` in the distributed decimal test; an
+/// NMOS CPU runs it as DCP abs,Y and carries on past DONE into the
+/// subroutines after it, so the run has to stop in front of it.
+const STP_OPCODE: u8 = 0xDB;
+
+/// How a test tells that it passed once it has stopped
+enum Success {
+    /// The trap is at this address
+    TrapAt(u16),
+
+    /// The byte at this address is zero
+    ZeroAt(u16),
+}
+
+struct Suite {
+    file: &'static str,
+    /// Entry point; images shorter than 64K are loaded here too
+    entry: u16,
+    /// First instructions at the entry point, checked before running so an
+    /// image from another build fails at once instead of never stopping
+    signature: &'static [u8],
+    /// Opcode the test ends on instead of trapping, if any. It only
+    /// appears as an instruction at the test's DONE label.
+    end_opcode: Option<u8>,
+    success: Success,
+}
+
+/// The default build of `6502_functional_test.a65` (with decimal tests)
+/// shipped in the repository's `bin_files/`. $3469 is the `success` trap in
+/// the `6502_functional_test.lst` listing next to it; other assembler
+/// options move it.
+const FUNCTIONAL: Suite = Suite {
+    file: "6502_functional_test.bin",
+    entry: 0x0400,
+    // start: CLD, LDX #$FF, TXS
+    signature: &[0xD8, 0xA2, 0xFF, 0x9A],
+    end_opcode: None,
+    success: Success::TrapAt(0x3469),
+};
+
+/// `6502_decimal_test.a65` assembled as distributed: code at `org $200`,
+/// and ERROR the twelfth zero page variable (after N1, N2, HA, HNVZC, DA,
+/// DNVZC, AR, NF, VF, ZF and CF), so $0B. It holds 1 until the test passes.
+/// Both outcomes end at DONE on `end_of_test` (STP); builds that patched it
+/// to `JMP *` trap there instead, which stops the run just the same.
+const DECIMAL: Suite = Suite {
+    file: "6502_decimal_test.bin",
+    entry: 0x0200,
+    // TEST: LDY #1, STY ERROR
+    signature: &[0xA0, 0x01, 0x84, 0x0B],
+    end_opcode: Some(STP_OPCODE),
+    success: Success::ZeroAt(0x000B),
+};
+
+/// Where and after how long the program stopped
+struct Trap {
+    pc: u16,
+    instructions: u64,
+    cycles: u64,
+}
+
+/// Runs until the PC stops moving, or up to (not including) `end_opcode`
+fn run_to_trap(cpu: &mut CPU, bus: &mut FlatBus, end_opcode: Option<u8>) -> Result<Trap, String> {
+    let start_cycles = cpu.cycles;
+    for instructions in 0..MAX_INSTRUCTIONS {
+        let pc = cpu.program_counter;
+        if end_opcode == Some(bus.peek(pc)) {
+            return Ok(Trap {
+                pc,
+                instructions,
+                cycles: cpu.cycles - start_cycles,
+            });
+        }
+        if let Err(err) = cpu.run_once(bus) {
+            return Err(format!("{} after {} instructions", err, instructions));
+        }
+        if cpu.program_counter == pc {
+            return Ok(Trap {
+                pc,
+                instructions: instructions + 1,
+                cycles: cpu.cycles - start_cycles,
+            });
+        }
+    }
+    Err(format!(
+        "no stop after {} instructions, PC=${:04X}",
+        MAX_INSTRUCTIONS, cpu.program_counter
+    ))
+}
+
+fn run_suite(suite: &Suite) {
+    let Some(image) = common::fixture(suite.file) else {
+        return;
+    };
+    let load_addr = if image.len() == 0x10000 {
+        0
+    } else {
+        suite.entry
+    };
+    assert!(
+        load_addr as usize + image.len() <= 0x10000,
+        "{} doesn't fit at ${:04X}",
+        suite.file,
+        load_addr
+    );
+
+    let mut bus = FlatBus::with_image(&image, load_addr);
+    let entry = suite.entry as usize;
+    assert_eq!(
+        &bus.memory()[entry..entry + suite.signature.len()],
+        suite.signature,
+        "{} isn't the expected build: unknown code at ${:04X}",
+        suite.file,
+        suite.entry
+    );
+    let mut cpu = CPU::new();
+    cpu.reset(&mut bus);
+    cpu.program_counter = suite.entry;
+
+    let trap = run_to_trap(&mut cpu, &mut bus, suite.end_opcode)
+        .unwrap_or_else(|err| panic!("{}: {}", suite.file, err));
+    let passed = match suite.success {
+        Success::TrapAt(addr) => trap.pc == addr,
+        Success::ZeroAt(addr) => bus.peek(addr) == 0,
+    };
+
+    let line = disassemble(&bus, trap.pc);
+    let instruction = format!("{} {}", line.mnemonic, line.operand);
+    let report = format!(
+        "{}: stopped at ${:04X} ({}) after {} instructions, {} cycles\n  \
+         A={:02X} X={:02X} Y={:02X} P={:02X} SP={:02X}",
+        suite.file,
+        trap.pc,
+        instruction.trim_end(),
+        trap.instructions,
+        trap.cycles,
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status,
+        cpu.stack_pointer
+    );
+    // The stop address points at the failed check in the test's listing
+    assert!(passed, "FAILED {}", report);
+    eprintln!("passed {}", report);
+}
+
+#[test]
+fn klaus_functional_test() {
+    run_suite(&FUNCTIONAL);
+}
+
+#[test]
+fn klaus_decimal_test() {
+    run_suite(&DECIMAL);
+}
@@ -0,0 +1,127 @@
+//! nestest.nes conformance run.
+//!
+//! Runs the nestest ROM in automation mode (from $C000, no PPU needed) on the
+//! 2A03 variant and an NROM bus, and compares every instruction with the
+//! golden `nestest.log`, field by field. Needs `nestest.nes` and
+//! `nestest.log` in `tests/fixtures`.
+
+mod common;
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use nes6502::bus::Bus;
+use nes6502::bus::ines::InesImage;
+use nes6502::cpu::{CPU, CpuVariant};
+use nes6502::debugger::trace::{TraceFormat, trace_line};
+
+/// Automation mode entry point
+const START: u16 = 0xC000;
+
+/// Where nestest leaves its error codes (0 means every test passed)
+const RESULT_ADDRS: [u16; 2] = [0x0002, 0x0003];
+
+/// Matching lines shown before a divergence
+const CONTEXT: usize = 8;
+
+/// Columns of the disassembly (with the `*` marking undocumented opcodes)
+/// and where the registers start in a nestest.log line
+const DISASM_COLUMNS: Range<usize> = 15..48;
+const REGISTERS_COLUMN: usize = 48;
+
+/// Register fields compared; the PPU position is left out since there is
+/// no PPU to produce it
+const REGISTER_FIELDS: [&str; 6] = ["A", "X", "Y", "P", "SP", "CYC"];
+
+/// Names the fields in which `actual` differs from `expected`
+fn differences(expected: &str, actual: &str) -> Vec<String> {
+    let mut diffs = Vec::new();
+
+    for (name, range) in [
+        ("PC", 0..4),
+        ("bytes", 6..14),
+        ("disassembly", DISASM_COLUMNS),
+    ] {
+        let (want, got) = (column(expected, range.clone()), column(actual, range));
+        if want != got {
+            diffs.push(format!("{}: expected '{}', got '{}'", name, want, got));
+        }
+    }
+
+    for name in REGISTER_FIELDS {
+        let (want, got) = (register(expected, name), register(actual, name));
+        if want != got {
+            diffs.push(format!(
+                "{}: expected {}, got {}",
+                name,
+                want.unwrap_or("nothing"),
+                got.unwrap_or("nothing")
+            ));
+        }
+    }
+    diffs
+}
+
+fn column(line: &str, range: Range<usize>) -> &str {
+    line.get(range).unwrap_or_default().trim()
+}
+
+/// Value of a `NAME:value` field in the register part of a line
+fn register<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    line.get(REGISTERS_COLUMN..)?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix(name)?.strip_prefix(':'))
+}
+
+#[test]
+fn nestest_matches_golden_log() {
+    let (Some(rom), Some(log)) = (
+        common::fixture("nestest.nes"),
+        common::fixture("nestest.log"),
+    ) else {
+        return;
+    };
+    let log = String::from_utf8_lossy(&log);
+
+    let image = InesImage::parse(&rom).expect("nestest.nes");
+    let mut bus = image.nrom_bus().expect("nestest.nes");
+    // The log was recorded on a NES, so binary-only ADC/SBC
+    let mut cpu = CPU::with_variant(CpuVariant::Ricoh2A03);
+    cpu.reset(&mut bus);
+    cpu.program_counter = START;
+
+    let mut context = VecDeque::with_capacity(CONTEXT);
+    for (index, expected) in log.lines().enumerate() {
+        let actual = trace_line(TraceFormat::Nestest, &cpu, &bus);
+
+        let diffs = differences(expected, &actual);
+        if !diffs.is_empty() {
+            let mut report = format!("diverged at nestest.log line {}:\n", index + 1);
+            for line in &context {
+                report += &format!("            {}\n", line);
+            }
+            report += &format!("  expected: {}\n  actual:   {}\n", expected, actual);
+            for diff in diffs {
+                report += &format!("  {}\n", diff);
+            }
+            panic!("{}", report);
+        }
+
+        if let Err(err) = cpu.run_once(&mut bus) {
+            panic!("line {}: {}\n  {}", index + 1, err, actual);
+        }
+        if context.len() == CONTEXT {
+            context.pop_front();
+        }
+        context.push_back(actual);
+    }
+
+    for addr in RESULT_ADDRS {
+        let code = bus.peek(addr);
+        assert_eq!(
+            code, 0,
+            "nestest reports error ${:02X} at ${:04X}",
+            code, addr
+        );
+    }
+}
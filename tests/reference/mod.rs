@@ -0,0 +1,622 @@
+//! A deliberately plain 6502 model to test the real core against.
+//!
+//! Every opcode is one row of `TABLE` (operation, addressing mode, base
+//! cycles and whether a page crossing costs a cycle), and every operation
+//! is one arm of the `match` in `step`. Nothing is shared with
+//! `nes6502::cpu`; decimal mode follows Bruce Clark's description of the
+//! NMOS chip and the undocumented opcodes "NMOS 6510 Unintended Opcodes".
+
+use std::collections::HashMap;
+
+use self::Mode::*;
+use self::Op::*;
+
+pub const N: u8 = 0x80;
+pub const V: u8 = 0x40;
+pub const U: u8 = 0x20;
+pub const B: u8 = 0x10;
+pub const D: u8 = 0x08;
+pub const I: u8 = 0x04;
+pub const Z: u8 = 0x02;
+pub const C: u8 = 0x01;
+
+/// ORed into A by XAA and LXA, matching `UnstableOpcodes::Emulate`'s default
+pub const MAGIC: u8 = 0xee;
+
+#[rustfmt::skip]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Adc, And, Asl, Bcc, Bcs, Beq, Bit, Bmi, Bne, Bpl, Brk, Bvc, Bvs, Clc,
+    Cld, Cli, Clv, Cmp, Cpx, Cpy, Dec, Dex, Dey, Eor, Inc, Inx, Iny, Jmp,
+    Jsr, Lda, Ldx, Ldy, Lsr, Nop, Ora, Pha, Php, Pla, Plp, Rol, Ror, Rti,
+    Rts, Sbc, Sec, Sed, Sei, Sta, Stx, Sty, Tax, Tay, Tsx, Txa, Txs, Tya,
+    // Undocumented
+    Ahx, Alr, Anc, Arr, Axs, Dcp, Isc, Kil, Las, Lax, Lxa, Rla, Rra, Sax,
+    Shx, Shy, Slo, Sre, Tas, Xaa,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Imp,
+    Acc,
+    Imm,
+    Zp0,
+    Zpx,
+    Zpy,
+    Abs,
+    Abx,
+    Aby,
+    Ind,
+    Izx,
+    Izy,
+    Rel,
+}
+
+/// One opcode: operation, addressing mode, base cycles, page-cross penalty
+type Entry = (Op, Mode, u8, bool);
+
+const fn e(op: Op, mode: Mode, cycles: u8) -> Entry {
+    (op, mode, cycles, false)
+}
+
+/// An opcode that takes one more cycle when indexing crosses a page
+const fn p(op: Op, mode: Mode, cycles: u8) -> Entry {
+    (op, mode, cycles, true)
+}
+
+#[rustfmt::skip]
+pub const TABLE: [Entry; 256] = [
+    // $00
+    e(Brk, Imp, 7), e(Ora, Izx, 6), e(Kil, Imp, 2), e(Slo, Izx, 8),
+    e(Nop, Zp0, 3), e(Ora, Zp0, 3), e(Asl, Zp0, 5), e(Slo, Zp0, 5),
+    e(Php, Imp, 3), e(Ora, Imm, 2), e(Asl, Acc, 2), e(Anc, Imm, 2),
+    e(Nop, Abs, 4), e(Ora, Abs, 4), e(Asl, Abs, 6), e(Slo, Abs, 6),
+    // $10
+    e(Bpl, Rel, 2), p(Ora, Izy, 5), e(Kil, Imp, 2), e(Slo, Izy, 8),
+    e(Nop, Zpx, 4), e(Ora, Zpx, 4), e(Asl, Zpx, 6), e(Slo, Zpx, 6),
+    e(Clc, Imp, 2), p(Ora, Aby, 4), e(Nop, Imp, 2), e(Slo, Aby, 7),
+    p(Nop, Abx, 4), p(Ora, Abx, 4), e(Asl, Abx, 7), e(Slo, Abx, 7),
+    // $20
+    e(Jsr, Abs, 6), e(And, Izx, 6), e(Kil, Imp, 2), e(Rla, Izx, 8),
+    e(Bit, Zp0, 3), e(And, Zp0, 3), e(Rol, Zp0, 5), e(Rla, Zp0, 5),
+    e(Plp, Imp, 4), e(And, Imm, 2), e(Rol, Acc, 2), e(Anc, Imm, 2),
+    e(Bit, Abs, 4), e(And, Abs, 4), e(Rol, Abs, 6), e(Rla, Abs, 6),
+    // $30
+    e(Bmi, Rel, 2), p(And, Izy, 5), e(Kil, Imp, 2), e(Rla, Izy, 8),
+    e(Nop, Zpx, 4), e(And, Zpx, 4), e(Rol, Zpx, 6), e(Rla, Zpx, 6),
+    e(Sec, Imp, 2), p(And, Aby, 4), e(Nop, Imp, 2), e(Rla, Aby, 7),
+    p(Nop, Abx, 4), p(And, Abx, 4), e(Rol, Abx, 7), e(Rla, Abx, 7),
+    // $40
+    e(Rti, Imp, 6), e(Eor, Izx, 6), e(Kil, Imp, 2), e(Sre, Izx, 8),
+    e(Nop, Zp0, 3), e(Eor, Zp0, 3), e(Lsr, Zp0, 5), e(Sre, Zp0, 5),
+    e(Pha, Imp, 3), e(Eor, Imm, 2), e(Lsr, Acc, 2), e(Alr, Imm, 2),
+    e(Jmp, Abs, 3), e(Eor, Abs, 4), e(Lsr, Abs, 6), e(Sre, Abs, 6),
+    // $50
+    e(Bvc, Rel, 2), p(Eor, Izy, 5), e(Kil, Imp, 2), e(Sre, Izy, 8),
+    e(Nop, Zpx, 4), e(Eor, Zpx, 4), e(Lsr, Zpx, 6), e(Sre, Zpx, 6),
+    e(Cli, Imp, 2), p(Eor, Aby, 4), e(Nop, Imp, 2), e(Sre, Aby, 7),
+    p(Nop, Abx, 4), p(Eor, Abx, 4), e(Lsr, Abx, 7), e(Sre, Abx, 7),
+    // $60
+    e(Rts, Imp, 6), e(Adc, Izx, 6), e(Kil, Imp, 2), e(Rra, Izx, 8),
+    e(Nop, Zp0, 3), e(Adc, Zp0, 3), e(Ror, Zp0, 5), e(Rra, Zp0, 5),
+    e(Pla, Imp, 4), e(Adc, Imm, 2), e(Ror, Acc, 2), e(Arr, Imm, 2),
+    e(Jmp, Ind, 5), e(Adc, Abs, 4), e(Ror, Abs, 6), e(Rra, Abs, 6),
+    // $70
+    e(Bvs, Rel, 2), p(Adc, Izy, 5), e(Kil, Imp, 2), e(Rra, Izy, 8),
+    e(Nop, Zpx, 4), e(Adc, Zpx, 4), e(Ror, Zpx, 6), e(Rra, Zpx, 6),
+    e(Sei, Imp, 2), p(Adc, Aby, 4), e(Nop, Imp, 2), e(Rra, Aby, 7),
+    p(Nop, Abx, 4), p(Adc, Abx, 4), e(Ror, Abx, 7), e(Rra, Abx, 7),
+    // $80
+    e(Nop, Imm, 2), e(Sta, Izx, 6), e(Nop, Imm, 2), e(Sax, Izx, 6),
+    e(Sty, Zp0, 3), e(Sta, Zp0, 3), e(Stx, Zp0, 3), e(Sax, Zp0, 3),
+    e(Dey, Imp, 2), e(Nop, Imm, 2), e(Txa, Imp, 2), e(Xaa, Imm, 2),
+    e(Sty, Abs, 4), e(Sta, Abs, 4), e(Stx, Abs, 4), e(Sax, Abs, 4),
+    // $90
+    e(Bcc, Rel, 2), e(Sta, Izy, 6), e(Kil, Imp, 2), e(Ahx, Izy, 6),
+    e(Sty, Zpx, 4), e(Sta, Zpx, 4), e(Stx, Zpy, 4), e(Sax, Zpy, 4),
+    e(Tya, Imp, 2), e(Sta, Aby, 5), e(Txs, Imp, 2), e(Tas, Aby, 5),
+    e(Shy, Abx, 5), e(Sta, Abx, 5), e(Shx, Aby, 5), e(Ahx, Aby, 5),
+    // $A0
+    e(Ldy, Imm, 2), e(Lda, Izx, 6), e(Ldx, Imm, 2), e(Lax, Izx, 6),
+    e(Ldy, Zp0, 3), e(Lda, Zp0, 3), e(Ldx, Zp0, 3), e(Lax, Zp0, 3),
+    e(Tay, Imp, 2), e(Lda, Imm, 2), e(Tax, Imp, 2), e(Lxa, Imm, 2),
+    e(Ldy, Abs, 4), e(Lda, Abs, 4), e(Ldx, Abs, 4), e(Lax, Abs, 4),
+    // $B0
+    e(Bcs, Rel, 2), p(Lda, Izy, 5), e(Kil, Imp, 2), p(Lax, Izy, 5),
+    e(Ldy, Zpx, 4), e(Lda, Zpx, 4), e(Ldx, Zpy, 4), e(Lax, Zpy, 4),
+    e(Clv, Imp, 2), p(Lda, Aby, 4), e(Tsx, Imp, 2), p(Las, Aby, 4),
+    p(Ldy, Abx, 4), p(Lda, Abx, 4), p(Ldx, Aby, 4), p(Lax, Aby, 4),
+    // $C0
+    e(Cpy, Imm, 2), e(Cmp, Izx, 6), e(Nop, Imm, 2), e(Dcp, Izx, 8),
+    e(Cpy, Zp0, 3), e(Cmp, Zp0, 3), e(Dec, Zp0, 5), e(Dcp, Zp0, 5),
+    e(Iny, Imp, 2), e(Cmp, Imm, 2), e(Dex, Imp, 2), e(Axs, Imm, 2),
+    e(Cpy, Abs, 4), e(Cmp, Abs, 4), e(Dec, Abs, 6), e(Dcp, Abs, 6),
+    // $D0
+    e(Bne, Rel, 2), p(Cmp, Izy, 5), e(Kil, Imp, 2), e(Dcp, Izy, 8),
+    e(Nop, Zpx, 4), e(Cmp, Zpx, 4), e(Dec, Zpx, 6), e(Dcp, Zpx, 6),
+    e(Cld, Imp, 2), p(Cmp, Aby, 4), e(Nop, Imp, 2), e(Dcp, Aby, 7),
+    p(Nop, Abx, 4), p(Cmp, Abx, 4), e(Dec, Abx, 7), e(Dcp, Abx, 7),
+    // $E0
+    e(Cpx, Imm, 2), e(Sbc, Izx, 6), e(Nop, Imm, 2), e(Isc, Izx, 8),
+    e(Cpx, Zp0, 3), e(Sbc, Zp0, 3), e(Inc, Zp0, 5), e(Isc, Zp0, 5),
+    e(Inx, Imp, 2), e(Sbc, Imm, 2), e(Nop, Imp, 2), e(Sbc, Imm, 2),
+    e(Cpx, Abs, 4), e(Sbc, Abs, 4), e(Inc, Abs, 6), e(Isc, Abs, 6),
+    // $F0
+    e(Beq, Rel, 2), p(Sbc, Izy, 5), e(Kil, Imp, 2), e(Isc, Izy, 8),
+    e(Nop, Zpx, 4), e(Sbc, Zpx, 4), e(Inc, Zpx, 6), e(Isc, Zpx, 6),
+    e(Sed, Imp, 2), p(Sbc, Aby, 4), e(Nop, Imp, 2), e(Isc, Aby, 7),
+    p(Nop, Abx, 4), p(Sbc, Abx, 4), e(Inc, Abx, 7), e(Isc, Abx, 7),
+];
+
+/// 64K of memory: bytes that were set or written, over a background that
+/// is a pure function of `seed` (all zero for seed 0). Writes are logged.
+#[derive(Debug, Clone)]
+pub struct Memory {
+    seed: u64,
+    bytes: HashMap<u16, u8>,
+    pub writes: Vec<(u16, u8)>,
+}
+
+impl Memory {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            bytes: HashMap::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Sets a byte without logging a write
+    pub fn set(&mut self, addr: u16, value: u8) {
+        self.bytes.insert(addr, value);
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match self.bytes.get(&addr) {
+            Some(value) => *value,
+            None if self.seed == 0 => 0,
+            None => background(self.seed, addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) {
+        self.bytes.insert(addr, value);
+        self.writes.push((addr, value));
+    }
+
+    fn read16(&self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.read(addr), self.read(addr.wrapping_add(1))])
+    }
+}
+
+/// SplitMix64 finalizer of seed and address
+fn background(seed: u64, addr: u16) -> u8 {
+    let mut z = seed.wrapping_add((addr as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)) as u8
+}
+
+/// Everything the model keeps between steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Model {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    pub p: u8,
+    pub nmi: bool,
+    pub irq: bool,
+    /// False for the 2A03, whose ADC/SBC ignore D
+    pub bcd: bool,
+}
+
+/// What one `step` did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// An instruction or interrupt entry took this many cycles
+    Cycles(u64),
+
+    /// A KIL opcode locked up the CPU; nothing else changed
+    Jam,
+}
+
+impl Model {
+    fn flag(&self, flag: u8) -> bool {
+        self.p & flag != 0
+    }
+
+    fn set(&mut self, flag: u8, on: bool) {
+        if on {
+            self.p |= flag;
+        } else {
+            self.p &= !flag;
+        }
+    }
+
+    fn nz(&mut self, value: u8) -> u8 {
+        self.set(Z, value == 0);
+        self.set(N, value & 0x80 != 0);
+        value
+    }
+
+    fn push(&mut self, mem: &mut Memory, value: u8) {
+        mem.write(0x0100 | self.sp as u16, value);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    fn pull(&mut self, mem: &mut Memory) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        mem.read(0x0100 | self.sp as u16)
+    }
+
+    fn interrupt(&mut self, mem: &mut Memory, pc: u16, p: u8, vector: u16) {
+        let [lo, hi] = pc.to_le_bytes();
+        self.push(mem, hi);
+        self.push(mem, lo);
+        self.push(mem, p);
+        self.p |= I;
+        self.pc = mem.read16(vector);
+    }
+
+    fn compare(&mut self, register: u8, m: u8) {
+        self.set(C, register >= m);
+        self.nz(register.wrapping_sub(m));
+    }
+
+    fn adc(&mut self, m: u8) {
+        let a = self.a;
+        let c = self.p & C;
+        let binary = a as u16 + m as u16 + c as u16;
+        if !(self.bcd && self.flag(D)) {
+            let r = binary as u8;
+            self.set(C, binary > 0xff);
+            self.set(V, (a ^ r) & (m ^ r) & 0x80 != 0);
+            self.a = self.nz(r);
+            return;
+        }
+
+        // Decimal: Z from the binary sum, N and V before the high nibble fixup
+        let mut al = (a & 0x0f) as i16 + (m & 0x0f) as i16 + c as i16;
+        if al >= 0x0a {
+            al = ((al + 0x06) & 0x0f) + 0x10;
+        }
+        let signed = (a & 0xf0) as i8 as i16 + (m & 0xf0) as i8 as i16 + al;
+        let mut sum = (a & 0xf0) as i16 + (m & 0xf0) as i16 + al;
+        self.set(Z, binary as u8 == 0);
+        self.set(N, sum & 0x80 != 0);
+        self.set(V, !(-128..=127).contains(&signed));
+        if sum >= 0xa0 {
+            sum += 0x60;
+        }
+        self.set(C, sum >= 0x100);
+        self.a = sum as u8;
+    }
+
+    fn sbc(&mut self, m: u8) {
+        let a = self.a;
+        let borrow = 1 - (self.p & C) as i16;
+        let binary = a as i16 - m as i16 - borrow;
+        let r = binary as u8;
+        self.set(C, binary >= 0);
+        self.set(V, (a ^ m) & (a ^ r) & 0x80 != 0);
+        self.nz(r);
+        if !(self.bcd && self.flag(D)) {
+            self.a = r;
+            return;
+        }
+
+        // Decimal: flags as in binary, only A is corrected
+        let mut al = (a & 0x0f) as i16 - (m & 0x0f) as i16 - borrow;
+        if al < 0 {
+            al = ((al - 0x06) & 0x0f) - 0x10;
+        }
+        let mut diff = (a & 0xf0) as i16 - (m & 0xf0) as i16 + al;
+        if diff < 0 {
+            diff -= 0x60;
+        }
+        self.a = diff as u8;
+    }
+
+    fn asl(&mut self, m: u8) -> u8 {
+        self.set(C, m & 0x80 != 0);
+        self.nz(m << 1)
+    }
+
+    fn lsr(&mut self, m: u8) -> u8 {
+        self.set(C, m & 0x01 != 0);
+        self.nz(m >> 1)
+    }
+
+    fn rol(&mut self, m: u8) -> u8 {
+        let r = (m << 1) | (self.p & C);
+        self.set(C, m & 0x80 != 0);
+        self.nz(r)
+    }
+
+    fn ror(&mut self, m: u8) -> u8 {
+        let r = (m >> 1) | ((self.p & C) << 7);
+        self.set(C, m & 0x01 != 0);
+        self.nz(r)
+    }
+
+    fn arr(&mut self, m: u8) {
+        let t = self.a & m;
+        let mut r = (t >> 1) | ((self.p & C) << 7);
+        self.nz(r);
+        if !(self.bcd && self.flag(D)) {
+            self.set(C, r & 0x40 != 0);
+            self.set(V, ((r >> 6) ^ (r >> 5)) & 1 != 0);
+            self.a = r;
+            return;
+        }
+
+        self.set(V, (t ^ r) & 0x40 != 0);
+        if (t & 0x0f) + (t & 0x01) > 0x05 {
+            r = (r & 0xf0) | (r.wrapping_add(0x06) & 0x0f);
+        }
+        let carry = (t & 0xf0) as u16 + (t & 0x10) as u16 > 0x50;
+        if carry {
+            r = r.wrapping_add(0x60);
+        }
+        self.set(C, carry);
+        self.a = r;
+    }
+
+    /// AHX/TAS/SHX/SHY: stores `value & (H + 1)` where H is the high byte
+    /// of the unindexed address; crossing a page puts that in the high byte
+    fn store_and_high(&mut self, mem: &mut Memory, addr: u16, index: u8, value: u8) {
+        let base = addr.wrapping_sub(index as u16);
+        let stored = value & ((base >> 8) as u8).wrapping_add(1);
+        let target = if base >> 8 != addr >> 8 {
+            ((stored as u16) << 8) | (addr & 0xff)
+        } else {
+            addr
+        };
+        mem.write(target, stored);
+    }
+
+    /// Decodes the operand of `mode` and moves PC past the instruction.
+    /// Returns the effective address (the operand byte for immediate, the
+    /// target for branches) and whether indexing crossed a page.
+    fn operand(&mut self, mem: &Memory, mode: Mode) -> (u16, bool) {
+        let pc = self.pc;
+        let b1 = mem.read(pc.wrapping_add(1));
+        let b2 = mem.read(pc.wrapping_add(2));
+        let word = u16::from_le_bytes([b1, b2]);
+        let indexed = |base: u16, index: u8| {
+            let addr = base.wrapping_add(index as u16);
+            (addr, base >> 8 != addr >> 8)
+        };
+        let zp_pointer =
+            |zp: u8| u16::from_le_bytes([mem.read(zp as u16), mem.read(zp.wrapping_add(1) as u16)]);
+
+        let (size, result) = match mode {
+            Imp | Acc => (1, (0, false)),
+            Imm => (2, (pc.wrapping_add(1), false)),
+            Zp0 => (2, (b1 as u16, false)),
+            Zpx => (2, (b1.wrapping_add(self.x) as u16, false)),
+            Zpy => (2, (b1.wrapping_add(self.y) as u16, false)),
+            Abs => (3, (word, false)),
+            Abx => (3, indexed(word, self.x)),
+            Aby => (3, indexed(word, self.y)),
+            // The high byte comes from the same page as the low byte
+            Ind => {
+                let hi = (word & 0xff00) | (word.wrapping_add(1) & 0x00ff);
+                (
+                    3,
+                    (u16::from_le_bytes([mem.read(word), mem.read(hi)]), false),
+                )
+            }
+            Izx => (2, (zp_pointer(b1.wrapping_add(self.x)), false)),
+            Izy => (2, indexed(zp_pointer(b1), self.y)),
+            Rel => {
+                let next = pc.wrapping_add(2);
+                (2, (next.wrapping_add(b1 as i8 as u16), false))
+            }
+        };
+        self.pc = pc.wrapping_add(size);
+        result
+    }
+}
+
+impl Model {
+    /// Services a pending interrupt or executes one instruction
+    pub fn step(&mut self, mem: &mut Memory) -> Outcome {
+        if self.nmi {
+            self.nmi = false;
+            self.interrupt(mem, self.pc, (self.p & !B) | U, 0xfffa);
+            return Outcome::Cycles(7);
+        }
+        if self.irq && !self.flag(I) {
+            self.irq = false;
+            self.interrupt(mem, self.pc, (self.p & !B) | U, 0xfffe);
+            return Outcome::Cycles(7);
+        }
+
+        let (op, mode, base, penalty) = TABLE[mem.read(self.pc) as usize];
+        if op == Kil {
+            return Outcome::Jam;
+        }
+        let (addr, crossed) = self.operand(mem, mode);
+        let mut cycles = base as u64 + (penalty && crossed) as u64;
+
+        // Operand value, from A in accumulator mode
+        let m = if mode == Acc { self.a } else { mem.read(addr) };
+
+        // Read-modify-write: memory or A gets `f(m)`, which is also returned
+        let modify = |cpu: &mut Model, mem: &mut Memory, f: fn(&mut Model, u8) -> u8| {
+            let r = f(cpu, m);
+            if mode == Acc {
+                cpu.a = r;
+            } else {
+                mem.write(addr, r);
+            }
+            r
+        };
+
+        let branch = match op {
+            Bpl => Some(!self.flag(N)),
+            Bmi => Some(self.flag(N)),
+            Bvc => Some(!self.flag(V)),
+            Bvs => Some(self.flag(V)),
+            Bcc => Some(!self.flag(C)),
+            Bcs => Some(self.flag(C)),
+            Bne => Some(!self.flag(Z)),
+            Beq => Some(self.flag(Z)),
+            _ => None,
+        };
+        if let Some(taken) = branch {
+            if taken {
+                cycles += 1 + (self.pc >> 8 != addr >> 8) as u64;
+                self.pc = addr;
+            }
+            return Outcome::Cycles(cycles);
+        }
+
+        match op {
+            Lda => self.a = self.nz(m),
+            Ldx => self.x = self.nz(m),
+            Ldy => self.y = self.nz(m),
+            Lax => {
+                self.a = self.nz(m);
+                self.x = m;
+            }
+            Sta => mem.write(addr, self.a),
+            Stx => mem.write(addr, self.x),
+            Sty => mem.write(addr, self.y),
+            Sax => mem.write(addr, self.a & self.x),
+            Tax => self.x = self.nz(self.a),
+            Tay => self.y = self.nz(self.a),
+            Txa => self.a = self.nz(self.x),
+            Tya => self.a = self.nz(self.y),
+            Tsx => self.x = self.nz(self.sp),
+            Txs => self.sp = self.x,
+            Inx => self.x = self.nz(self.x.wrapping_add(1)),
+            Iny => self.y = self.nz(self.y.wrapping_add(1)),
+            Dex => self.x = self.nz(self.x.wrapping_sub(1)),
+            Dey => self.y = self.nz(self.y.wrapping_sub(1)),
+            And => self.a = self.nz(self.a & m),
+            Ora => self.a = self.nz(self.a | m),
+            Eor => self.a = self.nz(self.a ^ m),
+            Adc => self.adc(m),
+            Sbc => self.sbc(m),
+            Cmp => self.compare(self.a, m),
+            Cpx => self.compare(self.x, m),
+            Cpy => self.compare(self.y, m),
+            Bit => {
+                self.set(Z, self.a & m == 0);
+                self.set(N, m & 0x80 != 0);
+                self.set(V, m & 0x40 != 0);
+            }
+            Asl => {
+                modify(self, mem, Model::asl);
+            }
+            Lsr => {
+                modify(self, mem, Model::lsr);
+            }
+            Rol => {
+                modify(self, mem, Model::rol);
+            }
+            Ror => {
+                modify(self, mem, Model::ror);
+            }
+            Inc => {
+                modify(self, mem, |cpu, m| cpu.nz(m.wrapping_add(1)));
+            }
+            Dec => {
+                modify(self, mem, |cpu, m| cpu.nz(m.wrapping_sub(1)));
+            }
+            Slo => {
+                let r = modify(self, mem, Model::asl);
+                self.a = self.nz(self.a | r);
+            }
+            Rla => {
+                let r = modify(self, mem, Model::rol);
+                self.a = self.nz(self.a & r);
+            }
+            Sre => {
+                let r = modify(self, mem, Model::lsr);
+                self.a = self.nz(self.a ^ r);
+            }
+            Rra => {
+                let r = modify(self, mem, Model::ror);
+                self.adc(r);
+            }
+            Dcp => {
+                let r = modify(self, mem, |_, m| m.wrapping_sub(1));
+                self.compare(self.a, r);
+            }
+            Isc => {
+                let r = modify(self, mem, |_, m| m.wrapping_add(1));
+                self.sbc(r);
+            }
+            Anc => {
+                self.a = self.nz(self.a & m);
+                self.set(C, self.a & 0x80 != 0);
+            }
+            Alr => self.a = self.lsr(self.a & m),
+            Arr => self.arr(m),
+            Axs => {
+                let t = self.a & self.x;
+                self.compare(t, m);
+                self.x = t.wrapping_sub(m);
+            }
+            Xaa => self.a = self.nz((self.a | MAGIC) & self.x & m),
+            Lxa => {
+                self.a = self.nz((self.a | MAGIC) & m);
+                self.x = self.a;
+            }
+            Las => {
+                self.sp &= m;
+                self.a = self.nz(self.sp);
+                self.x = self.sp;
+            }
+            Ahx => self.store_and_high(mem, addr, self.y, self.a & self.x),
+            Tas => {
+                self.sp = self.a & self.x;
+                self.store_and_high(mem, addr, self.y, self.sp);
+            }
+            Shx => self.store_and_high(mem, addr, self.y, self.x),
+            Shy => self.store_and_high(mem, addr, self.x, self.y),
+            Clc => self.p &= !C,
+            Sec => self.p |= C,
+            Cli => self.p &= !I,
+            Sei => self.p |= I,
+            Cld => self.p &= !D,
+            Sed => self.p |= D,
+            Clv => self.p &= !V,
+            Pha => self.push(mem, self.a),
+            Php => self.push(mem, self.p | B | U),
+            Pla => {
+                let value = self.pull(mem);
+                self.a = self.nz(value);
+            }
+            Plp => self.p = (self.pull(mem) & !B) | U,
+            Jmp => self.pc = addr,
+            Jsr => {
+                let [lo, hi] = self.pc.wrapping_sub(1).to_le_bytes();
+                self.push(mem, hi);
+                self.push(mem, lo);
+                self.pc = addr;
+            }
+            Rts => {
+                let lo = self.pull(mem);
+                let hi = self.pull(mem);
+                self.pc = u16::from_le_bytes([lo, hi]).wrapping_add(1);
+            }
+            Rti => {
+                self.p = (self.pull(mem) & !B) | U;
+                let lo = self.pull(mem);
+                let hi = self.pull(mem);
+                self.pc = u16::from_le_bytes([lo, hi]);
+            }
+            // The padding byte after BRK is skipped on return
+            Brk => self.interrupt(mem, self.pc.wrapping_add(1), self.p | B | U, 0xfffe),
+            Nop => {}
+            Kil | Bpl | Bmi | Bvc | Bvs | Bcc | Bcs | Bne | Beq => unreachable!(),
+        }
+        Outcome::Cycles(cycles)
+    }
+}
@@ -0,0 +1,286 @@
+//! Tom Harte's SingleStepTests (ProcessorTests) for the 6502.
+//!
+//! Every `*.json` file in `tests/fixtures/singlestep` (the `6502/v1` set,
+//! run on the NMOS variant) and `tests/fixtures/singlestep-2a03` (the
+//! `nes6502/v1` set, run on the 2A03 with its binary-only ADC/SBC) holds the
+//! vectors for one opcode: initial registers and RAM, the state after one
+//! instruction, and the bus activity of every cycle. Each vector is run with
+//! one `run_once` on a sparse `RecordingBus` and the final registers, RAM and
+//! cycle count are checked. Set `SINGLESTEP_BUS=1` to also compare the exact
+//! cycle-by-cycle reads and writes, and `SINGLESTEP_FILTER=a9` to run only
+//! the files whose name starts with that.
+//!
+//! The CPU only makes the bus accesses an instruction needs, not the dummy
+//! ones of the real chip: the read of the next byte by one-byte
+//! instructions, the re-read of zero page,X/Y and (zp,X) bases, the read of
+//! the unfixed address on an indexed page cross or store, the extra write of
+//! read-modify-write instructions, and the stack and branch dummy cycles.
+//! So the bus check only applies to the opcodes in `BUS_EXACT`, whose every
+//! cycle is a real access; for those, any difference is a bug.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use nes6502::bus::Bus;
+use nes6502::bus::access::AccessKind;
+use nes6502::bus::recording::RecordingBus;
+use nes6502::cpu::{CPU, CpuVariant};
+use nes6502::disassembler::{AddrMode, OPCODES};
+use serde_json::Value;
+
+/// Failing vectors described per file before the rest are only counted
+const MAX_REPORTED: usize = 3;
+
+/// Opcodes with no dummy bus cycles, the only ones `SINGLESTEP_BUS=1`
+/// compares cycle by cycle
+#[rustfmt::skip]
+const BUS_EXACT: [u8; 64] = [
+    // Immediate, including the undocumented ANC, ALR, ARR, ANE, LXA, AXS,
+    // SBC and NOP forms
+    0x09, 0x29, 0x49, 0x69, 0xA0, 0xA2, 0xA9, 0xC0, 0xC9, 0xE0, 0xE9,
+    0x0B, 0x2B, 0x4B, 0x6B, 0x8B, 0xAB, 0xCB, 0xEB,
+    0x80, 0x82, 0x89, 0xC2, 0xE2,
+    // Zero page loads, compares, BIT, LAX and NOPs
+    0x05, 0x24, 0x25, 0x45, 0x65, 0xA4, 0xA5, 0xA6, 0xA7, 0xC4, 0xC5, 0xE4, 0xE5,
+    0x04, 0x44, 0x64,
+    // Zero page stores, including SAX
+    0x84, 0x85, 0x86, 0x87,
+    // Absolute loads, compares, BIT, LAX and NOP
+    0x0D, 0x2C, 0x2D, 0x4D, 0x6D, 0xAC, 0xAD, 0xAE, 0xAF, 0xCC, 0xCD, 0xEC, 0xED,
+    0x0C,
+    // Absolute stores, including SAX
+    0x8C, 0x8D, 0x8E, 0x8F,
+    // JMP absolute and indirect
+    0x4C, 0x6C,
+];
+
+/// One bus cycle: address, value and direction
+type Cycle = (u16, u8, AccessKind);
+
+/// Registers and RAM of a vector's `initial` or `final` object
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+impl State {
+    fn parse(json: &Value) -> Option<Self> {
+        let byte = |key: &str| json[key].as_u64().map(|value| value as u8);
+        let ram = json["ram"]
+            .as_array()?
+            .iter()
+            .map(|pair| Some((pair[0].as_u64()? as u16, pair[1].as_u64()? as u8)))
+            .collect::<Option<_>>()?;
+        Some(Self {
+            pc: json["pc"].as_u64()? as u16,
+            s: byte("s")?,
+            a: byte("a")?,
+            x: byte("x")?,
+            y: byte("y")?,
+            p: byte("p")?,
+            ram,
+        })
+    }
+}
+
+fn parse_cycles(json: &Value) -> Option<Vec<Cycle>> {
+    json.as_array()?
+        .iter()
+        .map(|cycle| {
+            let addr = cycle[0].as_u64()? as u16;
+            let value = cycle[1].as_u64()? as u8;
+            let kind = match cycle[2].as_str()? {
+                "read" => AccessKind::Read,
+                "write" => AccessKind::Write,
+                _ => return None,
+            };
+            Some((addr, value, kind))
+        })
+        .collect()
+}
+
+/// Runs one vector; returns what went wrong, if anything
+fn run_vector(vector: &Value, variant: CpuVariant, check_bus: bool) -> Result<(), String> {
+    let initial = State::parse(&vector["initial"]).ok_or("bad initial state")?;
+    let expected = State::parse(&vector["final"]).ok_or("bad final state")?;
+    let cycles = parse_cycles(&vector["cycles"]).ok_or("bad cycle list")?;
+
+    let mut bus = RecordingBus::with_memory(initial.ram.iter().copied());
+    let mut cpu = CPU::with_variant(variant);
+    cpu.program_counter = initial.pc;
+    cpu.stack_pointer = initial.s;
+    cpu.register_a = initial.a;
+    cpu.register_x = initial.x;
+    cpu.register_y = initial.y;
+    cpu.status = initial.p;
+
+    let start_cycles = cpu.cycles;
+    cpu.run_once(&mut bus).map_err(|err| err.to_string())?;
+
+    let mut diffs = Vec::new();
+    let registers = [
+        ("PC", expected.pc, cpu.program_counter),
+        ("S", expected.s as u16, cpu.stack_pointer as u16),
+        ("A", expected.a as u16, cpu.register_a as u16),
+        ("X", expected.x as u16, cpu.register_x as u16),
+        ("Y", expected.y as u16, cpu.register_y as u16),
+        ("P", expected.p as u16, cpu.status as u16),
+    ];
+    for (name, want, got) in registers {
+        if want != got {
+            diffs.push(format!(
+                "{}: expected ${:02X}, got ${:02X}",
+                name, want, got
+            ));
+        }
+    }
+    for &(addr, want) in &expected.ram {
+        let got = bus.peek(addr);
+        if want != got {
+            diffs.push(format!(
+                "${:04X}: expected ${:02X}, got ${:02X}",
+                addr, want, got
+            ));
+        }
+    }
+
+    let elapsed = cpu.cycles - start_cycles;
+    if elapsed != cycles.len() as u64 {
+        diffs.push(format!(
+            "cycles: expected {}, got {}",
+            cycles.len(),
+            elapsed
+        ));
+    }
+    if check_bus && let Err(diff) = bus.check_accesses(&cycles) {
+        diffs.push(diff);
+    }
+
+    if diffs.is_empty() {
+        Ok(())
+    } else {
+        Err(diffs.join("\n    "))
+    }
+}
+
+/// Runs every vector of one file; returns a report if any failed
+fn run_file(path: &Path, variant: CpuVariant, check_bus: bool) -> Option<String> {
+    let text = fs::read_to_string(path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    let vectors: Vec<Value> =
+        serde_json::from_str(&text).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+
+    let mut failed = 0;
+    let mut report = String::new();
+    for vector in &vectors {
+        if let Err(diff) = run_vector(vector, variant, check_bus) {
+            failed += 1;
+            if failed <= MAX_REPORTED {
+                report += &format!("  {}:\n    {}\n", vector["name"], diff);
+            }
+        }
+    }
+
+    (failed > 0).then(|| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        format!(
+            "{}: {} of {} vectors failed\n{}",
+            name,
+            failed,
+            vectors.len(),
+            report
+        )
+    })
+}
+
+/// The opcode a file is named after (`a9.json`)
+fn file_opcode(path: &Path) -> Option<u8> {
+    u8::from_str_radix(path.file_stem()?.to_str()?, 16).ok()
+}
+
+/// Runs the files in `tests/fixtures/<dir>` on `variant`
+fn run_set(dir: &str, variant: CpuVariant) {
+    let dir = common::fixtures_dir().join(dir);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) => return common::missing(dir.display(), err),
+    };
+    let check_bus = std::env::var("SINGLESTEP_BUS").is_ok_and(|value| value == "1");
+    let filter = std::env::var("SINGLESTEP_FILTER").unwrap_or_default();
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with(filter.as_str()))
+        })
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return common::missing(dir.display(), "no matching *.json files");
+    }
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let opcode = file_opcode(path);
+        // A jammed CPU only ever repeats the same bus cycle
+        if opcode.is_some_and(|opcode| OPCODES[opcode as usize].mnemonic == "KIL") {
+            continue;
+        }
+        let bus_exact = opcode.is_some_and(|opcode| BUS_EXACT.contains(&opcode));
+        if let Some(report) = run_file(path, variant, check_bus && bus_exact) {
+            failures.push(report);
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} opcode files failed:\n{}",
+        failures.len(),
+        paths.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn single_step_vectors() {
+    run_set("singlestep", CpuVariant::Nmos6502);
+}
+
+#[test]
+fn single_step_vectors_2a03() {
+    run_set("singlestep-2a03", CpuVariant::Ricoh2A03);
+}
+
+#[test]
+fn bus_exact_opcodes_have_no_dummy_cycles() {
+    for opcode in BUS_EXACT {
+        let info = OPCODES[opcode as usize];
+        assert!(
+            matches!(
+                info.mode,
+                AddrMode::Imm | AddrMode::Zp | AddrMode::Abs | AddrMode::Ind
+            ),
+            "${:02X} {} {:?}",
+            opcode,
+            info.mnemonic,
+            info.mode
+        );
+        assert!(
+            !matches!(
+                info.mnemonic,
+                "JSR" | "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC"
+            ) && !matches!(info.mnemonic, "SLO" | "RLA" | "SRE" | "RRA" | "DCP" | "ISC"),
+            "${:02X} {} is read-modify-write",
+            opcode,
+            info.mnemonic
+        );
+    }
+}